@@ -0,0 +1,9 @@
+//! command line parsing shared by the binaries, which include it with `mod cli;`
+
+/// parse the value after `--name`, or use the default value when the option is not given
+pub fn parse_option<T: std::str::FromStr>(args: &[String], name: &str, default: T) -> Result<T, String> {
+    match args.iter().position(|arg| arg == name).and_then(|i| args.get(i + 1)) {
+        Some(value) => value.parse::<T>().map_err(|_| format!("invalid value for {}: {}", name, value)),
+        None => Ok(default),
+    }
+}
@@ -1,27 +1,12 @@
 use nn::NeuralNetwork;
-use image::GenericImageView;
+use nn::dataset::mnist_shards;
+use nn::loader::DataLoader;
 use ndarray::{Array, Dim};
 use std::fs::File;
 use std::io::prelude::*;
-use csv::{ReaderBuilder, StringRecord};
 use std::error::Error;
 use std::env;
 
-fn get_img_pixels_data(record: &StringRecord) -> Vec<u8> {
-    let mut image_vec: Vec<u8> = vec![];
-    let mut count = 0;
-    for data_value in record.iter() {
-        if count == 0 {
-            count += 1;
-            continue;
-        } else {
-            image_vec.push(data_value.parse::<u8>().unwrap());
-        }
-    }
-    image_vec
-}
-
-
 fn main() -> Result<(), Box<dyn Error>> {
    
     // arg 1 for train data set Path
@@ -71,43 +56,17 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // read full mnist train data file from 0 to 60 for i in range(0, 60]
     println!("Start to train Full mnist the neural network");
-    let mut count = 0;
-
-    let file_prefix = format!("{}/file", train_data_set_path);
-    while count <= 60 {
-        let file_path = format!("{}{}.csv", file_prefix, count);
-
-        let mut file = File::open(&file_path).expect("you must download the dataset first, https://pjreddie.com/projects/mnist-in-csv/");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-
-        let mut reader = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(b',')
-        .from_reader(contents.as_bytes());
-
-        for result in reader.records() {
-            let record = result?;
-            
-            let image_vec: Vec<u8> = get_img_pixels_data(&record);
-            let image_vec: Vec<f32> = image_vec.iter().map(|x| *x as f32).collect();
-            let image_array: Array<f32, Dim<[usize; 2]>> = Array::from_shape_vec((28, 28), image_vec).unwrap();
-            // scale the input to range 0.01 to 1.00
-            let scaled_input_data = image_array / 255.0 * 0.99 + 0.01;
-
-            let mut target_list: Vec<f32> = vec![0.01; 10];
-            let real_digit = record.get(0).unwrap();
-            let real_digit = real_digit.parse::<u8>().unwrap();
-            target_list[real_digit as usize] = 0.99;
-
-            nn.train(&scaled_input_data.iter().cloned().collect(), &target_list);
+    let loader = DataLoader::builder(mnist_shards(train_data_set_path, 61)).build();
+    for batch in loader {
+        let batch = batch?;
+        for sample in &batch.samples {
+            nn.train(&sample.input, &sample.target);
         }
-        count += 1;
     }
 
     println!("End to train full mnist data the neural network");
     
-    let output_list: Vec<f32> = nn.predict(&scaled_input_data.iter().cloned().collect());
+    let output_list: Vec<f32> = nn.predict(&scaled_input_data.iter().cloned().collect::<Vec<f32>>());
 
     // find the max value's index in the output list
     let mut max_value = 0.0;
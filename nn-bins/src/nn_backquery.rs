@@ -0,0 +1,37 @@
+use nn::{NeuralNetwork, Sample};
+use nn::visualize::sample_image;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("./nn-backquery <model_file> [output_dir]");
+        println!("run every label backwards through a trained MNIST model and save what the network thinks the digit looks like");
+        println!("Example: ./nn-backquery ./mnist.model ./images/backquery");
+        return Ok(());
+    }
+
+    let nn: NeuralNetwork = NeuralNetwork::load(&args[1])?;
+    let output_dir = Path::new(args.get(2).map(String::as_str).unwrap_or("./images/backquery"));
+    let (weight_ih, weight_ho) = nn.weights();
+    if weight_ih.ncols() != 28 * 28 {
+        return Err(format!("expected a model with 784 inputs, not {}", weight_ih.ncols()).into());
+    }
+    fs::create_dir_all(output_dir)?;
+
+    for label in 0..weight_ho.nrows() {
+        // 0.99 for the label and 0.01 for every other output, like the training targets
+        let mut target_list = vec![0.01; weight_ho.nrows()];
+        target_list[label] = 0.99;
+        // saved like the dataset records, with the same pixel mapping
+        let sample = Sample { input: nn.backquery(&target_list), target: target_list, label: label as u8 };
+        let file_name = output_dir.join(format!("backquery_{}.png", label));
+        sample_image(&sample, 1).save(&file_name)?;
+        println!("saved {}", file_name.display());
+    }
+
+    Ok(())
+}
@@ -0,0 +1,50 @@
+mod cli;
+
+use cli::parse_option;
+use nn::cross_validation::cross_validate;
+use nn::dataset::{csv_files, read_mnist_csv, MNIST_CLASSES, MNIST_PIXELS};
+use nn::trainer::Trainer;
+use nn::{Activation, NeuralNetwork};
+use std::env;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("./nn-cross-validate <data_set_path> [--k N] [--hidden N] [--learning-rate F] [--epochs N] [--batch-size N] [--activation NAME] [--seed N]");
+        println!("stratified k-fold cross-validation of a MNIST csv file or a directory of csv files (default 5 folds)");
+        println!("every fold is the test set once, the mean ± standard deviation of the test metrics is printed at the end");
+        println!("Example: ./nn-cross-validate ./dataset/my_own.csv --k 10 --hidden 100 --epochs 5");
+        return Ok(());
+    }
+
+    let mut samples = vec![];
+    for file in csv_files(&args[1])? {
+        samples.extend(read_mnist_csv(file)?);
+    }
+    let k: usize = parse_option(&args, "--k", 5)?;
+    let hidden_nodes: i32 = parse_option(&args, "--hidden", 200)?;
+    let learning_rate: f32 = parse_option(&args, "--learning-rate", 0.1)?;
+    let activation: Activation = parse_option(&args, "--activation", Activation::Sigmoid)?;
+    let seed: u64 = parse_option(&args, "--seed", 0)?;
+    let trainer = Trainer::new()
+        .epochs(parse_option(&args, "--epochs", 1)?)
+        .batch_size(parse_option(&args, "--batch-size", 1)?)
+        .seed(seed);
+
+    println!("{} samples, {} folds", samples.len(), k);
+    let result = cross_validate(&trainer, || {
+        let mut nn = NeuralNetwork::new_with_seed(MNIST_PIXELS as i32, hidden_nodes, MNIST_CLASSES as i32, learning_rate, seed);
+        nn.set_activation(activation);
+        nn
+    }, &samples, k, seed)?;
+
+    println!("fold  samples  test loss  test accuracy");
+    for fold in &result.folds {
+        println!("{:>4}  {:>7}  {:>9.5}  {:>13.4}", fold.fold, fold.evaluation.count, fold.evaluation.loss, fold.evaluation.accuracy);
+    }
+    println!("test loss: {}", result.loss);
+    println!("test accuracy: {}", result.accuracy);
+
+    Ok(())
+}
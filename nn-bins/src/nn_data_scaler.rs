@@ -30,15 +30,10 @@ fn main() -> Result<(), Box<dyn Error>> {
         .delimiter(b',')
         .from_reader(contents.as_bytes());
 
-    let mut count = 0;
     let mut records: Vec<StringRecord> = vec![];
-    for result in reader.records() {
-        if count == 100 {
-            break;
-        }
+    for result in reader.records().take(100) {
         let record = result?;
         records.push(record);
-        count += 1;
     }
 
     println!("Records len: {:?}", records.len());
@@ -0,0 +1,67 @@
+mod cli;
+
+use cli::parse_option;
+use nn::dataset::read_mnist_csv;
+use nn::ensemble::{average_weights, load_network_or_checkpoint, Combination, Ensemble};
+use nn::evaluation::{evaluate, evaluate_with};
+use std::env;
+use std::error::Error;
+
+fn usage() {
+    println!("./nn-ensemble evaluate <test_data_set_file> <model_file>... [--combine average|vote] [--weights W,W,...] [--save PATH]");
+    println!("./nn-ensemble average <output_model_file> <model_or_checkpoint_file>...");
+    println!("evaluate combines the outputs of models with the same inputs and outputs, e.g. trained with different seeds");
+    println!("or hidden sizes, by averaging, majority vote or a weighted average with one weight per model,");
+    println!("and compares the ensemble with every model. --save writes the ensemble as JSON");
+    println!("average writes a model whose weights are the mean of the weights of models or checkpoints of the same shape");
+    println!("Example: ./nn-ensemble evaluate ./dataset/mnist_test.csv ./seed0.model ./seed1.model ./seed2.model --combine vote");
+    println!("Example: ./nn-ensemble average ./averaged.model ./checkpoints/epoch3.checkpoint ./checkpoints/epoch4.checkpoint");
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 {
+        usage();
+        return Ok(());
+    }
+    // the files are the arguments before the first option
+    let files: Vec<&String> = args[3..].iter().take_while(|arg| !arg.starts_with("--")).collect();
+
+    match args[1].as_str() {
+        "evaluate" => {
+            let mut ensemble = Ensemble::open(&files)?;
+            let weights: String = parse_option(&args, "--weights", String::new())?;
+            let combination = if weights.is_empty() {
+                parse_option(&args, "--combine", Combination::Average)?
+            } else {
+                Combination::Weighted(weights.split(',').map(|w| w.trim().parse()).collect::<Result<_, _>>()?)
+            };
+            ensemble.set_combination(combination)?;
+            let save_path: String = parse_option(&args, "--save", String::new())?;
+
+            let test_samples = read_mnist_csv(&args[2])?;
+            println!("{} test samples", test_samples.len());
+            println!("model  accuracy  loss");
+            for (file, member) in files.iter().zip(ensemble.members()) {
+                let evaluation = evaluate(member, &test_samples);
+                println!("{}  {:.4}  {:.6}", file, evaluation.accuracy, evaluation.loss);
+            }
+            let evaluation = evaluate_with(|input_list| ensemble.predict(input_list), &test_samples);
+            println!("ensemble ({:?})  {:.4}  {:.6}", ensemble.combination(), evaluation.accuracy, evaluation.loss);
+
+            if !save_path.is_empty() {
+                ensemble.save(&save_path)?;
+                println!("saved the ensemble to {}", save_path);
+            }
+        }
+        "average" => {
+            let networks = files.iter().map(load_network_or_checkpoint).collect::<Result<Vec<_>, _>>()?;
+            let average = average_weights(&networks)?;
+            average.save(&args[2])?;
+            println!("saved the average of {} models to {}", networks.len(), args[2]);
+        }
+        _ => usage(),
+    }
+
+    Ok(())
+}
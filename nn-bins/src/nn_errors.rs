@@ -0,0 +1,57 @@
+mod cli;
+
+use cli::parse_option;
+use nn::dataset::read_mnist_csv;
+use nn::evaluation::{evaluate_with_errors, sort_misclassifications, write_misclassifications, ErrorOrder};
+use nn::model::{AnyModel, Model};
+use nn::visualize::contact_sheet;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        println!("./nn-errors <model_file> <test_data_set_file> [--sort confidence|row|label|predicted] [--top N] [--columns N] [--rows M] [--sheet PATH] [--csv PATH]");
+        println!("list the misclassified test samples, the most confident mistakes first by default");
+        println!("the model can be of any kind: a network, an ensemble, a quantised model or an ONNX file");
+        println!("--sheet writes them as a contact sheet with label>predicted captions, --csv exports them for review with nn-image --review");
+        println!("Example: ./nn-errors ./mnist.model ./dataset/mnist_test.csv --sheet ./images/errors.png --csv ./errors.csv");
+        return Ok(());
+    }
+
+    let order: ErrorOrder = parse_option(&args, "--sort", ErrorOrder::Confidence)?;
+    let top: usize = parse_option(&args, "--top", 20)?;
+    let columns: u32 = parse_option(&args, "--columns", 10)?;
+    let rows: u32 = parse_option(&args, "--rows", 10)?;
+    let sheet: String = parse_option(&args, "--sheet", String::new())?;
+    let csv: String = parse_option(&args, "--csv", String::new())?;
+
+    let model = AnyModel::load(&args[1])?;
+    let test_samples = read_mnist_csv(&args[2])?;
+    let (evaluation, mut errors) = evaluate_with_errors(|input_list| model.predict(input_list), &test_samples);
+    sort_misclassifications(&mut errors, order);
+
+    println!("{}", model.metadata());
+    println!("accuracy: {:.4}, {} of {} test samples are misclassified", evaluation.accuracy, errors.len(), evaluation.count);
+    println!("   row  label  predicted  confidence");
+    for error in errors.iter().take(top) {
+        println!("{:>6}  {:>5}  {:>9}  {:>10.4}", error.row, error.label, error.predicted, error.confidence);
+    }
+
+    if !sheet.is_empty() {
+        // e.g. "3>5 .97" for a 3 predicted as a 5 with an output of 0.97
+        let tiles = errors.iter().map(|error| {
+            let confidence = (error.confidence * 100.0).round().min(99.0);
+            (&test_samples[error.row], format!("{}>{} .{:02}", error.label, error.predicted, confidence))
+        });
+        contact_sheet(tiles, columns, rows, 1).save(&sheet)?;
+        println!("saved the first {} errors to {}", errors.len().min((columns * rows) as usize), sheet);
+    }
+    if !csv.is_empty() {
+        write_misclassifications(File::create(&csv)?, &errors)?;
+        println!("saved {} errors to {}", errors.len(), csv);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,58 @@
+mod cli;
+
+use cli::parse_option;
+use nn::NeuralNetwork;
+use nn::dataset::read_mnist_csv;
+use nn::evaluation::argmax;
+use nn::explain::{occlusion_map, saliency_map, Occlusion};
+use nn::visualize::{overlay, sample_image};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        println!("./nn-explain <model_file> <data_set_file> [--index N] [--class C] [--patch N] [--stride N] [--output-dir DIR]");
+        println!("show why the model predicts a class for one sample, as saliency and occlusion maps over the input image");
+        println!("the predicted class is explained unless --class is given");
+        println!("Example: ./nn-explain ./mnist.model ./dataset/mnist_test.csv --index 8");
+        return Ok(());
+    }
+
+    let nn: NeuralNetwork = NeuralNetwork::load(&args[1])?;
+    let samples = read_mnist_csv(&args[2])?;
+    let index: usize = parse_option(&args, "--index", 0)?;
+    let defaults = Occlusion::default();
+    let occlusion = Occlusion {
+        patch: parse_option(&args, "--patch", defaults.patch)?,
+        stride: parse_option(&args, "--stride", defaults.stride)?,
+        ..defaults
+    };
+    let output_dir: PathBuf = parse_option(&args, "--output-dir", PathBuf::from("./images/explain"))?;
+    fs::create_dir_all(&output_dir)?;
+
+    let sample = samples.get(index).ok_or_else(|| format!("{} has only {} samples", args[2], samples.len()))?;
+    let output_list = nn.predict(&sample.input);
+    let class: usize = parse_option(&args, "--class", argmax(&output_list))?;
+    if class >= output_list.len() {
+        return Err(format!("--class {} is not one of the {} outputs of the model", class, output_list.len()).into());
+    }
+    println!("sample {} has label {}, the output of class {} is {:.4}", index, sample.label, class, output_list[class]);
+
+    let saliency = saliency_map(&nn, &sample.input, class);
+    let occlusion = occlusion_map(|input_list| nn.predict(input_list), &sample.input, (28, 28), class, occlusion);
+    let images = [
+        ("input", sample_image(sample, 8)),
+        ("saliency", overlay(&sample.input, &saliency, 8)),
+        ("occlusion", overlay(&sample.input, &occlusion, 8)),
+    ];
+    for (name, image) in images {
+        let path = output_dir.join(format!("{}_{}_class{}.png", name, index, class));
+        image.save(&path)?;
+        println!("saved {}", path.display());
+    }
+
+    Ok(())
+}
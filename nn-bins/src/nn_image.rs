@@ -1,70 +1,76 @@
-use ndarray::{Array, Dim};
-use image::{ImageBuffer, GrayImage};
-use std::fs::File;
-use std::io::prelude::*;
-use csv::{ReaderBuilder, StringRecord};
+mod cli;
+
+use cli::parse_option;
+use nn::browser::DatasetBrowser;
+use nn::dataset::unscale_pixels;
+use nn::evaluation::read_misclassifications;
+use nn::visualize::{contact_sheet, sample_image};
+use nn::Sample;
+use std::env;
 use std::error::Error;
+use std::fs::File;
 
-fn get_img_pixels_data(record: &StringRecord) -> Vec<u8> {
-    let mut image_vec: Vec<u8> = vec![];
-    let mut count = 0;
-    for data_value in record.iter() {
-        if count == 0 {
-            count += 1;
-            continue;
-        } else {
-            image_vec.push(data_value.parse::<u8>().unwrap());
-        }
+// print the 28*28 digit with darker characters for brighter pixels
+fn print_sample(sample: &Sample) {
+    const SHADES: &[u8] = b" .:-=+*#%@";
+    for row in unscale_pixels(&sample.input).chunks(28) {
+        let line: String = row.iter().map(|&gray| SHADES[gray as usize * (SHADES.len() - 1) / 255] as char).collect();
+        println!("{}", line);
     }
-    image_vec
-}
-
-fn save_record_to_image(record: &StringRecord, file_name: &str) {
-    // read digital pixels and convert to ndarray image
-    let image_vec: Vec<u8> = get_img_pixels_data(record);
-
-    let image_array: Array<u8, Dim<[usize; 2]>> = Array::from_shape_vec((28, 28), image_vec).unwrap();
-    let image_buffer = ImageBuffer::from_fn(28, 28, |x, y| {
-        // use [[y, x]] instead of [[x, y]] to access the pixel value at (x, y) in the pixels array is 
-        // because ndarray uses row-major order to store its elements.
-        let gray_value = image_array[[y as usize, x as usize]];
-        GrayImage::from_pixel(1, 1, image::Luma([gray_value]))
-            .get_pixel(0, 0)
-            .to_owned()
-    });
-
-    image_buffer.save(file_name).unwrap();
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
-   
-    let mut file = File::open("./dataset/mnist_train/file0.csv").expect("you must download the dataset first, https://pjreddie.com/projects/mnist-in-csv/");
-    let mut contents = String::new();
-    file.read_to_string(&mut contents)?;
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("./nn-image <csv_file_or_dir> [--label L] [--page P] [--index ROW] [--columns N] [--rows M] [--sheet PATH] [--save PATH] [--review CSV]");
+        println!("page through a MNIST csv file (or a directory of shards), --sheet writes the page as a contact sheet with label:row captions");
+        println!("Example: ./nn-image ./dataset/mnist_test.csv --label 5 --page 2 --sheet ./images/fives.png");
+        println!("--review only shows the rows of a csv file written by nn-errors --csv, in its order");
+        println!("Example: ./nn-image ./dataset/mnist_train --index 1234 --save ./images/sample.png");
+        return Ok(());
+    }
 
-    let mut reader = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(b',')
-        .from_reader(contents.as_bytes());
+    let columns: u32 = parse_option(&args, "--columns", 10)?;
+    let rows: u32 = parse_option(&args, "--rows", 10)?;
+    let label: Option<u8> = args.iter().any(|arg| arg == "--label").then(|| parse_option(&args, "--label", 0)).transpose()?;
+    let index: Option<usize> = args.iter().any(|arg| arg == "--index").then(|| parse_option(&args, "--index", 0)).transpose()?;
+    let sheet: String = parse_option(&args, "--sheet", String::new())?;
+    let save: String = parse_option(&args, "--save", String::new())?;
+    let review: String = parse_option(&args, "--review", String::new())?;
+
+    let mut browser = DatasetBrowser::open(&args[1], (columns * rows) as usize)
+        .map_err(|err| format!("{} (you must download the dataset first, https://pjreddie.com/projects/mnist-in-csv/)", err))?;
+    let counts = browser.label_counts();
+    println!("{} samples, per label: {:?}", browser.samples().len(), counts);
+    if !review.is_empty() {
+        let errors = read_misclassifications(File::open(&review)?)?;
+        browser.set_selection(Some(errors.iter().map(|error| error.row).collect()));
+    }
+    browser.set_label_filter(label);
+    browser.set_page(parse_option(&args, "--page", 0)?);
 
-    let mut count = 0;
-    let mut records: Vec<StringRecord> = vec![];
-    for result in reader.records() {
-        if count == 100 {
-            break;
+    if let Some(row) = index {
+        let sample = browser.jump_to(row).ok_or_else(|| format!("there is no row {}", row))?.clone();
+        println!("row {} has label {}", row, sample.label);
+        print_sample(&sample);
+        if !save.is_empty() {
+            sample_image(&sample, 1).save(&save)?;
+            println!("saved {}", save);
         }
-        let record = result?;
-        records.push(record);
-        count += 1;
     }
 
-    println!("Records len: {:?}", records.len());
-    
-    save_record_to_image(&records[0], "./images/five.png");
-    save_record_to_image(&records[1], "./images/zero.png");
-    save_record_to_image(&records[2], "./images/four.png");
+    let filter = browser.label_filter().map_or("all labels".to_string(), |label| format!("label {}", label));
+    println!("{} samples of {}, page {}/{}", browser.len(), filter, browser.page() + 1, browser.page_count());
+    let page: Vec<String> = browser.page_rows().iter().map(|&row| format!("{:>6}:{}", row, browser.samples()[row].label)).collect();
+    for line in page.chunks(columns as usize) {
+        println!("{}", line.join(" "));
+    }
 
+    if !sheet.is_empty() {
+        let tiles = browser.page_rows().iter().map(|&row| (&browser.samples()[row], format!("{}:{}", browser.samples()[row].label, row)));
+        contact_sheet(tiles, columns, rows, 1).save(&sheet)?;
+        println!("saved {}", sheet);
+    }
 
     Ok(())
-   
-}
\ No newline at end of file
+}
@@ -0,0 +1,81 @@
+mod cli;
+
+use cli::parse_option;
+use ndarray_rand::rand::SeedableRng;
+use nn::dataset::mnist_shards;
+use nn::evaluation::argmax;
+use nn::layers::{ActivationLayer, Conv2d, Dense, Flatten, MaxPool2d, Reshape};
+use nn::loader::DataLoader;
+use nn::sequential::{Loss, Sequential};
+use nn::Activation;
+use rand_chacha::ChaCha8Rng;
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    // nn-mnist-lenet [--epochs N] [--batch-size N] [--learning-rate F] [--workers N] [--seed N] [--save PATH]
+    // a LeNet-5 style convolutional network trained on the same MNIST files as nn-mnist-two
+    let args: Vec<String> = env::args().collect();
+    let epochs: usize = parse_option(&args, "--epochs", 3)?;
+    let batch_size: usize = parse_option(&args, "--batch-size", 32)?;
+    let learning_rate: f32 = parse_option(&args, "--learning-rate", 0.05)?;
+    let workers: usize = parse_option(&args, "--workers", 2)?;
+    let seed: u64 = parse_option(&args, "--seed", 0)?;
+    let save: String = parse_option(&args, "--save", String::new())?;
+
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut model = Sequential::new(&[28 * 28], Loss::CrossEntropy, learning_rate)
+        .push(Reshape::new(&[1, 28, 28]))
+        .push(Conv2d::new(1, 6, 5, &mut rng).padding(2))
+        .push(ActivationLayer::new(Activation::Relu))
+        .push(MaxPool2d::new(2))
+        .push(Conv2d::new(6, 16, 5, &mut rng))
+        .push(ActivationLayer::new(Activation::Relu))
+        .push(MaxPool2d::new(2))
+        .push(Flatten::new())
+        .push(Dense::new(16 * 5 * 5, 120, &mut rng))
+        .push(ActivationLayer::new(Activation::Relu))
+        .push(Dense::new(120, 84, &mut rng))
+        .push(ActivationLayer::new(Activation::Relu))
+        .push(Dense::new(84, 10, &mut rng))
+        .push(ActivationLayer::new(Activation::Softmax));
+
+    let shards = mnist_shards("./dataset/mnist_train", 61);
+    for epoch in 1..=epochs {
+        let loader = DataLoader::builder(shards.clone())
+            .batch_size(batch_size)
+            .workers(workers)
+            .build();
+
+        let mut loss_sum = 0.0;
+        let mut batches = 0;
+        for batch in loader {
+            let batch = batch?;
+            loss_sum += model.train_batch(&batch.samples);
+            batches += 1;
+        }
+        println!("epoch {}: train loss {:.4}", epoch, loss_sum / batches.max(1) as f32);
+    }
+
+    let test_loader = DataLoader::builder(vec![PathBuf::from("./dataset/mnist_test.csv")]).build();
+    let mut correct = 0;
+    let mut total = 0;
+    for batch in test_loader {
+        let batch = batch?;
+        for sample in &batch.samples {
+            if argmax(&model.predict(&sample.input)) == sample.label as usize {
+                correct += 1;
+            }
+            total += 1;
+        }
+    }
+    println!("LeNet test accuracy: {}", correct as f32 / total.max(1) as f32);
+
+    if !save.is_empty() {
+        model.save(&save)?;
+        println!("saved the model to {}", save);
+    }
+
+    Ok(())
+}
@@ -56,7 +56,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         let real_digit = real_digit.parse::<u8>().unwrap();
         target_list[real_digit as usize] = 0.99;
 
-        nn.train(&scaled_input_data.iter().cloned().collect(), &target_list);
+        nn.train(&scaled_input_data.iter().cloned().collect::<Vec<f32>>(), &target_list);
        
     }
 
@@ -85,7 +85,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         let correct_digit = record.get(0).unwrap();
         let correct_digit = correct_digit.parse::<u8>().unwrap();
         
-        let output_list: Vec<f32> = nn.predict(&scaled_input_data.iter().cloned().collect());
+        let output_list: Vec<f32> = nn.predict(&scaled_input_data.iter().cloned().collect::<Vec<f32>>());
 
         // find the max value's index in the output list
         let mut max_value = 0.0;
@@ -1,111 +1,78 @@
+mod cli;
+
+use cli::parse_option;
 use nn::NeuralNetwork;
-use ndarray::{Array, Dim};
-use std::fs::File;
-use std::io::prelude::*;
-use csv::{ReaderBuilder, StringRecord};
+use nn::dataset::mnist_shards;
+use nn::loader::DataLoader;
+use std::env;
 use std::error::Error;
-
-fn get_img_pixels_data(record: &StringRecord) -> Vec<u8> {
-    let mut image_vec: Vec<u8> = vec![];
-    let mut count = 0;
-    for data_value in record.iter() {
-        if count == 0 {
-            count += 1;
-            continue;
-        } else {
-            image_vec.push(data_value.parse::<u8>().unwrap());
-        }
-    }
-    image_vec
-}
-
+use std::path::PathBuf;
 
 fn main() -> Result<(), Box<dyn Error>> {
+    // nn-mnist-two [--threads N] [--batch-size N] [--workers N] [--seed N]
+    // without --threads every sample updates the weights immediately (the way of the book),
+    // with --threads each mini batch is split across the threads and their updates are averaged.
+    let args: Vec<String> = env::args().collect();
+    let threads: usize = parse_option(&args, "--threads", 0)?;
+    let batch_size: usize = parse_option(&args, "--batch-size", 100)?;
+    let workers: usize = parse_option(&args, "--workers", 2)?;
+    let seed: u64 = parse_option(&args, "--seed", 0)?;
+
     // Why output nodes is 10?
     // Because there are 10 digits(0,1,2,3,4,5,6,7,8,9) in the MNIST dataset, so we choose 10 as the output nodes.
-    let mut nn = NeuralNetwork::new(28 * 28, 200, 10, 0.1);
+    let mut nn = NeuralNetwork::new_with_seed(28 * 28, 200, 10, 0.1, seed);
 
     // read full mnist train data file from 0 to 60 for i in range(0, 60]
     println!("Start to train Full mnist the neural network");
-    let mut count = 0;
-    let file_prefix = "./dataset/mnist_train/file";
-    while count <= 60 {
-        let file_path = format!("{}{}.csv", file_prefix, count);
-
-        println!("Start to train data from file: {:?}", file_path);
-
-        let mut file = File::open(&file_path).expect("you must download the dataset first, https://pjreddie.com/projects/mnist-in-csv/");
-        let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-
-        let mut reader = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(b',')
-        .from_reader(contents.as_bytes());
-
-        for result in reader.records() {
-            let record = result?;
-            
-            let image_vec: Vec<u8> = get_img_pixels_data(&record);
-            let image_vec: Vec<f32> = image_vec.iter().map(|x| *x as f32).collect();
-            let image_array: Array<f32, Dim<[usize; 2]>> = Array::from_shape_vec((28, 28), image_vec).unwrap();
-            // scale the input to range 0.01 to 1.00
-            let scaled_input_data = image_array / 255.0 * 0.99 + 0.01;
-
-            let mut target_list: Vec<f32> = vec![0.01; 10];
-            let real_digit = record.get(0).unwrap();
-            let real_digit = real_digit.parse::<u8>().unwrap();
-            target_list[real_digit as usize] = 0.99;
-
-            nn.train(&scaled_input_data.iter().cloned().collect(), &target_list);
+    let shards = mnist_shards("./dataset/mnist_train", 61);
+    let loader = DataLoader::builder(shards.clone())
+        .batch_size(batch_size)
+        .workers(workers)
+        .build();
+
+    let mut current_shard = None;
+    for batch in loader {
+        let batch = batch?;
+        if current_shard != Some(batch.shard) {
+            println!("Start to train data from file: {:?}", shards[batch.shard]);
+            current_shard = Some(batch.shard);
         }
-        count += 1;
 
-        println!("End to train data from file: {:?}", file_path);
+        if threads > 0 {
+            nn.train_batch(&batch.samples, threads);
+        } else {
+            for sample in &batch.samples {
+                nn.train(&sample.input, &sample.target);
+            }
+        }
     }
 
     println!("End to train full mnist data the neural network");
-    
-    let mut file = File::open("./dataset/mnist_test.csv").expect("you must download the dataset first, https://pjreddie.com/projects/mnist-in-csv/");
-    let mut contents = String::new();
-        file.read_to_string(&mut contents)?;
-    
-    let mut reader = ReaderBuilder::new()
-        .has_headers(false)
-        .delimiter(b',')
-        .from_reader(contents.as_bytes());
-
-    let mut scored_card: Vec<u64> = vec![];
-    for result in reader.records() {
-        let record = result?;
-        
-        let image_vec: Vec<u8> = get_img_pixels_data(&record);
-        let image_vec: Vec<f32> = image_vec.iter().map(|x| *x as f32).collect();
-        let image_array: Array<f32, Dim<[usize; 2]>> = Array::from_shape_vec((28, 28), image_vec).unwrap();
-        // scale the input to range 0.01 to 1.00
-        let scaled_input_data = image_array / 255.0 * 0.99 + 0.01;
 
-        let correct_digit = record.get(0).unwrap();
-        let correct_digit = correct_digit.parse::<u8>().unwrap();
-        
-        let output_list: Vec<f32> = nn.predict(&scaled_input_data.iter().cloned().collect());
+    let test_loader = DataLoader::builder(vec![PathBuf::from("./dataset/mnist_test.csv")]).build();
 
-        // find the max value's index in the output list
-        let mut max_value = 0.0;
-        let mut max_index = 0;
-        for (index, value) in output_list.iter().enumerate() {
-            if *value > max_value {
-                max_value = *value;
-                max_index = index;
+    let mut scored_card: Vec<u64> = vec![];
+    for batch in test_loader {
+        let batch = batch?;
+        for sample in &batch.samples {
+            let output_list: Vec<f32> = nn.predict(&sample.input);
+
+            // find the max value's index in the output list
+            let mut max_value = 0.0;
+            let mut max_index = 0;
+            for (index, value) in output_list.iter().enumerate() {
+                if *value > max_value {
+                    max_value = *value;
+                    max_index = index;
+                }
             }
-        }
 
-        if max_index == correct_digit as usize {
-            scored_card.push(1);
-        } else {
-            scored_card.push(0);
+            if max_index == sample.label as usize {
+                scored_card.push(1);
+            } else {
+                scored_card.push(0);
+            }
         }
-       
     }
 
     // calculate the performance score, the performance score is the ratio of correct answers to the total number of tests
@@ -113,5 +80,5 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("full mnist train data with 200 hidden layers and 0.1 learning rate result performance score: {}", performance_score);
 
     Ok(())
-   
-}
\ No newline at end of file
+
+}
@@ -58,7 +58,7 @@ fn main() -> Result<(), Box<dyn Error>> {
         let real_digit = real_digit.parse::<u8>().unwrap();
         target_list[real_digit as usize] = 0.99;
 
-        nn.train(&scaled_input_data.iter().cloned().collect(), &target_list);
+        nn.train(&scaled_input_data.iter().cloned().collect::<Vec<f32>>(), &target_list);
        
     }
 
@@ -85,7 +85,7 @@ fn main() -> Result<(), Box<dyn Error>> {
     let image_array: Array<f32, Dim<[usize; 2]>> = Array::from_shape_vec((28, 28), image_vec).unwrap();
     // scale the input to range 0.01 to 1.00
     let scaled_data = image_array / 255.0 * 0.99 + 0.01;
-    let target_list = nn.predict(&scaled_data.iter().cloned().collect());
+    let target_list = nn.predict(&scaled_data.iter().cloned().collect::<Vec<f32>>());
 
     println!("The digit seven's predicted vector is: {:?}", target_list);
     println!("The max value in the vector index 7 from above vector");
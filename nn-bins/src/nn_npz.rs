@@ -0,0 +1,32 @@
+mod cli;
+
+use cli::parse_option;
+use nn::NeuralNetwork;
+use nn::npy;
+use std::env;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 || !["export", "import"].contains(&args[1].as_str()) {
+        println!("./nn-npz export <model_file> <npz_file>");
+        println!("./nn-npz import <npz_file> <model_file> [--learning-rate 0.1]");
+        println!("exchange the wih/who weights with the Python version of the book as a numpy.savez archive");
+        println!("Example: ./nn-npz export ./mnist.model ./mnist.npz");
+        return Ok(());
+    }
+
+    if args[1] == "export" {
+        let nn: NeuralNetwork = NeuralNetwork::load(&args[2])?;
+        npy::save_npz(&nn, &args[3])?;
+        println!("exported the weights of {} to {}", args[2], args[3]);
+    } else {
+        let learning_rate = parse_option(&args, "--learning-rate", 0.1)?;
+        let nn: NeuralNetwork = npy::load_npz(&args[2], learning_rate)?;
+        nn.save(&args[3])?;
+        let (weight_ih, weight_ho) = nn.weights();
+        println!("imported a {}-{}-{} network to {}", weight_ih.ncols(), weight_ih.nrows(), weight_ho.nrows(), args[3]);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,31 @@
+use nn::NeuralNetwork;
+use nn::dataset::read_mnist_csv;
+use nn::evaluation::evaluate_with;
+use nn::onnx;
+use std::env;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 4 || !["export", "evaluate"].contains(&args[1].as_str()) {
+        println!("./nn-onnx export <model_file> <onnx_file>");
+        println!("./nn-onnx evaluate <onnx_file> <test_data_set_file>");
+        println!("export a model saved by nn-train to ONNX, or evaluate an ONNX multi layer perceptron on a test set");
+        println!("Example: ./nn-onnx export ./mnist.model ./mnist.onnx");
+        return Ok(());
+    }
+
+    if args[1] == "export" {
+        let nn: NeuralNetwork = NeuralNetwork::load(&args[2])?;
+        onnx::save_network(&nn, &args[3])?;
+        println!("exported {} to {}", args[2], args[3]);
+    } else {
+        let model = onnx::load(&args[2])?;
+        let test_samples = read_mnist_csv(&args[3])?;
+        let evaluation = evaluate_with(|input_list| model.predict(input_list), &test_samples);
+        println!("{} layers, {} test samples", model.layers().len(), test_samples.len());
+        println!("accuracy: {:.4}, loss: {:.6}", evaluation.accuracy, evaluation.loss);
+    }
+
+    Ok(())
+}
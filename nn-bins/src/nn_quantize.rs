@@ -0,0 +1,49 @@
+mod cli;
+
+use cli::parse_option;
+use nn::NeuralNetwork;
+use nn::dataset::read_mnist_csv;
+use nn::evaluation::{evaluate, evaluate_with};
+use nn::quantize::QuantizedNetwork;
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::time::Instant;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        println!("./nn-quantize <model_file> <test_data_set_file> [--save PATH]");
+        println!("quantise the weights of a model saved by nn-train to int8 and compare its accuracy with the f32 model");
+        println!("Example: ./nn-quantize ./mnist.model ./dataset/mnist_test.csv --save ./mnist.q8");
+        return Ok(());
+    }
+
+    let model_file = &args[1];
+    let test_data_set_file = &args[2];
+    let save: String = parse_option(&args, "--save", String::new())?;
+
+    let nn: NeuralNetwork = NeuralNetwork::load(model_file)?;
+    let quantized = QuantizedNetwork::from_network(&nn);
+    let test_samples = read_mnist_csv(test_data_set_file)?;
+
+    let start = Instant::now();
+    let f32_evaluation = evaluate(&nn, &test_samples);
+    let f32_time = start.elapsed();
+    let start = Instant::now();
+    let int8_evaluation = evaluate_with(|input_list| quantized.predict(input_list), &test_samples);
+    let int8_time = start.elapsed();
+
+    println!("{} test samples", test_samples.len());
+    println!("model  accuracy  loss      size (bytes)  time");
+    println!("f32    {:.4}    {:.6}  {:>12}  {:?}", f32_evaluation.accuracy, f32_evaluation.loss, fs::metadata(model_file)?.len(), f32_time);
+    println!("int8   {:.4}    {:.6}  {:>12}  {:?}", int8_evaluation.accuracy, int8_evaluation.loss, quantized.encoded_len(), int8_time);
+    println!("accuracy change: {:+.4}", int8_evaluation.accuracy - f32_evaluation.accuracy);
+
+    if !save.is_empty() {
+        quantized.save(&save)?;
+        println!("saved the quantised model to {}", save);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+mod cli;
+
+use cli::parse_option;
+use nn::dataset::{csv_files, read_mnist_csv, MNIST_CLASSES, MNIST_PIXELS};
+use nn::evaluation::evaluate;
+use nn::sweep::{write_results, Sweep};
+use nn::trainer::TrainingData;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        println!("./nn-sweep <sweep_config_file> <train_data_set_path> [--results PATH] [--save PATH] [--test FILE]");
+        println!("train a network for every trial of a grid or random search and rank them by validation accuracy");
+        println!("the config is a JSON file with the values to try, see nn::sweep, e.g.");
+        println!(r#"  {{"search": "grid", "hidden_nodes": [100, 200], "learning_rate": [0.1, 0.2], "epochs": [1, 2], "parallel": 4}}"#);
+        println!("--results writes the ranked table as csv (default ./sweep.csv), --save the best model (default ./sweep.model)");
+        println!("--test evaluates the best model on a test set, which is never used to rank the trials");
+        println!("Example: ./nn-sweep ./sweep.json ./dataset/mnist_train --test ./dataset/mnist_test.csv");
+        return Ok(());
+    }
+
+    let sweep = Sweep::load(&args[1])?;
+    let data = TrainingData::Shards(csv_files(&args[2])?);
+    let results_path: String = parse_option(&args, "--results", "./sweep.csv".to_string())?;
+    let save_path: String = parse_option(&args, "--save", "./sweep.model".to_string())?;
+    let test_path: String = parse_option(&args, "--test", String::new())?;
+
+    let trials = sweep.trials().len();
+    println!("{} trials, {} at a time", trials, sweep.parallel.max(1));
+    let results = sweep.run(MNIST_PIXELS, MNIST_CLASSES, &data, |result| {
+        let hyperparameters = &result.hyperparameters;
+        println!("trial {}: hidden {}, learning rate {}, epochs {}, {}, {}: validation accuracy {:.4} ({:.0}s)",
+            result.trial, hyperparameters.hidden_nodes, hyperparameters.learning_rate, hyperparameters.epochs,
+            hyperparameters.activation, hyperparameters.optimizer, result.validation.accuracy, result.seconds);
+    })?;
+
+    write_results(File::create(&results_path)?, &results)?;
+    println!("saved the ranked results to {}", results_path);
+    let Some(best) = results.first() else {
+        return Ok(());
+    };
+    best.network.save(&save_path)?;
+    println!("the best trial is {} with validation accuracy {:.4}, saved to {}", best.trial, best.validation.accuracy, save_path);
+    if !test_path.is_empty() {
+        let evaluation = evaluate(&best.network, &read_mnist_csv(&test_path)?);
+        println!("test loss: {:.5}, performance score: {}", evaluation.loss, evaluation.accuracy);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,144 @@
+mod cli;
+
+use cli::parse_option;
+use nn::{Activation, NeuralNetwork, Optimizer, Schedule};
+use nn::dataset::{mnist_shards, read_mnist_csv};
+use nn::evaluation::evaluate;
+use nn::metrics::{CsvSink, JsonlSink, MetricsLogger, TensorBoardSink};
+use nn::trainer::{Checkpoint, Trainer, TrainingData};
+use std::env;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 3 {
+        println!("./nn-train <train_data_set_path> <test_data_set_file> [options]");
+        println!("Options:");
+        println!("  --hidden N            number of hidden nodes (default 200)");
+        println!("  --learning-rate F     learning rate (default 0.1)");
+        println!("  --activation NAME     activation of the hidden layer, sigmoid, relu or tanh (default sigmoid)");
+        println!("  --epochs N            maximum number of epochs (default 5)");
+        println!("  --batch-size N        samples per weight update (default 1)");
+        println!("  --threads N           threads sharing a batch (default 1)");
+        println!("  --validation-split F  ratio of the train data held out for validation (default 0.1)");
+        println!("  --patience N          stop after N epochs without improvement (default 2)");
+        println!("  --seed N              seed of the weights and the validation split (default 0)");
+        println!("  --momentum F          use SGD with momentum instead of plain SGD");
+        println!("  --lr-decay F          multiply the learning rate by F every --lr-decay-steps updates");
+        println!("  --lr-decay-steps N    weight updates between two learning rate decays (default 10000)");
+        println!("  --checkpoint PATH     write a checkpoint every --checkpoint-every updates and after every epoch");
+        println!("  --checkpoint-every N  weight updates between two checkpoints (default 10000)");
+        println!("  --resume PATH         continue the run saved in a checkpoint, use the same options as the first run");
+        println!("  --metrics-csv PATH    write the loss, accuracy, learning rate and gradient norms to a csv file");
+        println!("  --metrics-jsonl PATH  write the same metrics as JSON Lines");
+        println!("  --log-dir DIR         write the same metrics as a TensorBoard event file in DIR");
+        println!("  --log-every N         weight updates between two metrics records (default 100)");
+        println!("  --save PATH           save the trained model");
+        println!("Example: ./nn-train ./dataset/mnist_train ./dataset/mnist_test.csv --epochs 10");
+        return Ok(());
+    }
+
+    let train_data_set_path = &args[1];
+    let test_data_set_file = &args[2];
+    let hidden_nodes: i32 = parse_option(&args, "--hidden", 200)?;
+    let learning_rate: f32 = parse_option(&args, "--learning-rate", 0.1)?;
+    let seed: u64 = parse_option(&args, "--seed", 0)?;
+    let batch_size: usize = parse_option(&args, "--batch-size", 1)?;
+    let validation_split: f32 = parse_option(&args, "--validation-split", 0.1)?;
+    let activation: Activation = parse_option(&args, "--activation", Activation::Sigmoid)?;
+    // the trainer and the network panic on these values
+    if hidden_nodes <= 0 {
+        return Err("--hidden must be greater than 0".into());
+    }
+    if batch_size == 0 {
+        return Err("--batch-size must be greater than 0".into());
+    }
+    if !(0.0..1.0).contains(&validation_split) {
+        return Err("--validation-split must be in range [0, 1)".into());
+    }
+    if activation == Activation::Softmax {
+        return Err("--activation can not be softmax, it is the activation of the output layer".into());
+    }
+
+    let trainer = Trainer::new()
+        .epochs(parse_option(&args, "--epochs", 5)?)
+        .batch_size(batch_size)
+        .threads(parse_option(&args, "--threads", 1)?)
+        .validation_split(validation_split)
+        .patience(parse_option(&args, "--patience", 2)?)
+        .seed(seed);
+    let lr_decay: f32 = parse_option(&args, "--lr-decay", 1.0)?;
+    let trainer = if lr_decay != 1.0 {
+        trainer.schedule(Schedule::StepDecay { step_size: parse_option(&args, "--lr-decay-steps", 10000)?, gamma: lr_decay })
+    } else {
+        trainer
+    };
+    let checkpoint_path: String = parse_option(&args, "--checkpoint", String::new())?;
+    let trainer = if !checkpoint_path.is_empty() {
+        trainer.checkpoint(&checkpoint_path, parse_option(&args, "--checkpoint-every", 10000)?)
+    } else {
+        trainer
+    };
+    let metrics_csv: String = parse_option(&args, "--metrics-csv", String::new())?;
+    let metrics_jsonl: String = parse_option(&args, "--metrics-jsonl", String::new())?;
+    let log_dir: String = parse_option(&args, "--log-dir", String::new())?;
+    let trainer = if !metrics_csv.is_empty() || !metrics_jsonl.is_empty() || !log_dir.is_empty() {
+        let mut logger = MetricsLogger::new(parse_option(&args, "--log-every", 100)?);
+        if !metrics_csv.is_empty() {
+            logger = logger.sink(CsvSink::create(&metrics_csv)?);
+        }
+        if !metrics_jsonl.is_empty() {
+            logger = logger.sink(JsonlSink::create(&metrics_jsonl)?);
+        }
+        if !log_dir.is_empty() {
+            logger = logger.sink(TensorBoardSink::create(&log_dir)?);
+        }
+        trainer.metrics(logger)
+    } else {
+        trainer
+    };
+    let data = TrainingData::Shards(mnist_shards(train_data_set_path, 61));
+
+    let resume_path: String = parse_option(&args, "--resume", String::new())?;
+    let (nn, report) = if !resume_path.is_empty() {
+        let checkpoint = Checkpoint::load(&resume_path)?;
+        println!("Resume training at epoch {}, shard {}, row {}", checkpoint.epoch, checkpoint.shard, checkpoint.row);
+        trainer.resume(checkpoint, &data)?
+    } else {
+        let mut nn = NeuralNetwork::new_with_seed(28 * 28, hidden_nodes, 10, learning_rate, seed);
+        nn.set_activation(activation);
+        let momentum: f32 = parse_option(&args, "--momentum", 0.0)?;
+        if momentum > 0.0 {
+            nn.set_optimizer(Optimizer::Momentum { momentum });
+        }
+        println!("Start to train the neural network");
+        let report = trainer.fit(&mut nn, &data)?;
+        (nn, report)
+    };
+    for metrics in &report.history {
+        match metrics.validation {
+            Some(validation) => println!("epoch {}: train loss {:.5}, validation loss {:.5}, validation accuracy {:.4}",
+                metrics.epoch, metrics.train_loss, validation.loss, validation.accuracy),
+            None => println!("epoch {}: train loss {:.5}", metrics.epoch, metrics.train_loss),
+        }
+    }
+    if report.stopped_early {
+        println!("Stopped early, the validation loss did not improve");
+    }
+    if let Some(best_epoch) = report.best_epoch {
+        println!("Restored the weights of epoch {}", best_epoch);
+    }
+    println!("End to train the neural network");
+
+    let save_path: String = parse_option(&args, "--save", String::new())?;
+    if !save_path.is_empty() {
+        nn.save(&save_path)?;
+        println!("Saved the model to {}", save_path);
+    }
+
+    let test_samples = read_mnist_csv(test_data_set_file)?;
+    let evaluation = evaluate(&nn, &test_samples);
+    println!("test loss: {:.5}, performance score: {}", evaluation.loss, evaluation.accuracy);
+
+    Ok(())
+}
@@ -0,0 +1,59 @@
+mod cli;
+
+use cli::parse_option;
+use nn::NeuralNetwork;
+use nn::dataset::read_mnist_csv;
+use nn::visualize::{network_activations, weight_tiles, Histogram};
+use std::env;
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        println!("./nn-visualize <model_file> [--output-dir DIR] [--input CSV --index N] [--bins N]");
+        println!("render the input weights of every hidden unit, weight histograms and optionally the activations for one sample");
+        println!("Example: ./nn-visualize ./mnist.model --input ./dataset/mnist_test.csv --index 0");
+        return Ok(());
+    }
+
+    let nn: NeuralNetwork = NeuralNetwork::load(&args[1])?;
+    let output_dir: PathBuf = parse_option(&args, "--output-dir", PathBuf::from("./images/visualize"))?;
+    let input: Option<String> = args.iter().position(|arg| arg == "--input").and_then(|i| args.get(i + 1)).cloned();
+    let index: usize = parse_option(&args, "--index", 0)?;
+    let bins: usize = parse_option(&args, "--bins", 50)?;
+    fs::create_dir_all(&output_dir)?;
+
+    let (weight_ih, weight_ho) = nn.weights();
+    // MNIST inputs are drawn as 28*28 tiles, any other square input as a square tile
+    let side = (weight_ih.ncols() as f32).sqrt().round() as u32;
+    if (side * side) as usize == weight_ih.ncols() {
+        let path = output_dir.join("weights_ih.png");
+        weight_tiles(weight_ih, (side, side), 2).save(&path)?;
+        println!("saved {}", path.display());
+    } else {
+        println!("the {} inputs are not a square image, skipping the weight tiles", weight_ih.ncols());
+    }
+
+    for (name, weights) in [("ih", weight_ih), ("ho", weight_ho)] {
+        let histogram = Histogram::new(weights, bins);
+        let path = output_dir.join(format!("histogram_{}.png", name));
+        histogram.render(400, 200).save(&path)?;
+        println!("saved {} (weights from {:.4} to {:.4})", path.display(), histogram.min, histogram.max);
+    }
+
+    if let Some(input) = input {
+        let samples = read_mnist_csv(&input)?;
+        let sample = samples.get(index).ok_or_else(|| format!("{} has only {} samples", input, samples.len()))?;
+        let (hidden, output) = network_activations(&nn, &sample.input, 16);
+        for (name, image) in [("hidden", hidden), ("output", output)] {
+            let path = output_dir.join(format!("activations_{}_{}.png", name, index));
+            image.save(&path)?;
+            println!("saved {}", path.display());
+        }
+        println!("sample {} has label {}", index, sample.label);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,259 @@
+use eframe::egui::{self, load::SizedTexture, ColorImage, TextureHandle, TextureOptions};
+use nn::browser::DatasetBrowser;
+use nn::evaluation::argmax;
+use nn::explain::{occlusion_map, saliency_map, Occlusion};
+use nn::visualize::{contact_sheet, overlay, sample_image};
+use nn::model::{AnyModel, Model};
+use std::collections::HashMap;
+
+const THUMBNAIL: f32 = 56.0;
+// the sample textures are dropped when there are more of them, e.g. after paging through a big dataset
+const MAX_TEXTURES: usize = 2000;
+
+// the prediction of a model for the selected row and why, see `nn::explain`
+struct Explanation {
+    row: usize,
+    output_list: Vec<f32>,
+    // only a network has the input gradient
+    saliency: Option<TextureHandle>,
+    occlusion: TextureHandle,
+}
+
+/// pages through a dataset like `nn-image`, with a label filter, a row jump and contact sheets of the page.
+/// With a model of any kind the selected sample is explained by its occlusion map, and by its saliency map for a network
+pub struct DatasetPanel {
+    path: String,
+    browser: Option<DatasetBrowser>,
+    columns: usize,
+    rows: usize,
+    row_input: String,
+    sheet_path: String,
+    selected: Option<usize>,
+    textures: HashMap<usize, TextureHandle>,
+    model_path: String,
+    model: Option<AnyModel>,
+    explanation: Option<Explanation>,
+    status: String,
+}
+
+impl Default for DatasetPanel {
+    fn default() -> Self {
+        DatasetPanel {
+            path: "./dataset/mnist_test.csv".to_string(),
+            browser: None,
+            columns: 10,
+            rows: 6,
+            row_input: String::new(),
+            sheet_path: "./images/contact_sheet.png".to_string(),
+            selected: None,
+            textures: HashMap::new(),
+            model_path: "./mnist.model".to_string(),
+            model: None,
+            explanation: None,
+            status: String::new(),
+        }
+    }
+}
+
+impl DatasetPanel {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("csv file or shard directory");
+            ui.text_edit_singleline(&mut self.path);
+            if ui.button("Open").clicked() {
+                self.open();
+            }
+            ui.separator();
+            ui.label("model");
+            ui.text_edit_singleline(&mut self.model_path);
+            if ui.button("Load").clicked() {
+                match AnyModel::load(&self.model_path) {
+                    Ok(model) => {
+                        self.status = format!("loaded {}: {}", self.model_path, model.metadata());
+                        self.model = Some(model);
+                    }
+                    Err(err) => self.status = err.to_string(),
+                }
+                self.explanation = None;
+            }
+        });
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+        if self.browser.is_none() {
+            return;
+        }
+
+        self.navigation_ui(ui);
+        ui.separator();
+        ui.horizontal_top(|ui| {
+            self.page_ui(ui);
+            ui.separator();
+            self.selected_ui(ui);
+        });
+    }
+
+    /// predict and explain the samples with a model, e.g. the one of a finished training run
+    pub fn set_model(&mut self, model: impl Into<AnyModel>, path: String) {
+        self.model = Some(model.into());
+        self.model_path = path;
+        self.explanation = None;
+    }
+
+    fn open(&mut self) {
+        match DatasetBrowser::open(&self.path, self.columns * self.rows) {
+            Ok(browser) => {
+                self.status = format!("{} samples, per label: {:?}", browser.samples().len(), browser.label_counts());
+                self.browser = Some(browser);
+                self.selected = None;
+                self.textures.clear();
+                self.explanation = None;
+            }
+            Err(err) => self.status = err.to_string(),
+        }
+    }
+
+    fn navigation_ui(&mut self, ui: &mut egui::Ui) {
+        let browser = self.browser.as_mut().unwrap();
+        ui.horizontal(|ui| {
+            let mut label = browser.label_filter();
+            egui::ComboBox::from_label("label")
+                .selected_text(label.map_or("all".to_string(), |label| label.to_string()))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut label, None, "all");
+                    for digit in 0..10 {
+                        ui.selectable_value(&mut label, Some(digit), digit.to_string());
+                    }
+                });
+            if label != browser.label_filter() {
+                browser.set_label_filter(label);
+            }
+
+            if ui.button("◀").clicked() {
+                browser.previous_page();
+            }
+            ui.label(format!("page {}/{} ({} samples)", browser.page() + 1, browser.page_count(), browser.len()));
+            if ui.button("▶").clicked() {
+                browser.next_page();
+            }
+
+            ui.separator();
+            ui.label("row");
+            ui.add(egui::TextEdit::singleline(&mut self.row_input).desired_width(60.0));
+            if ui.button("Go").clicked() {
+                match self.row_input.trim().parse().ok().filter(|&row| browser.jump_to(row).is_some()) {
+                    Some(row) => self.selected = Some(row),
+                    None => self.status = format!("there is no row {}", self.row_input.trim()),
+                }
+            }
+        });
+
+        ui.horizontal(|ui| {
+            let columns = ui.add(egui::DragValue::new(&mut self.columns).clamp_range(1..=20).prefix("columns "));
+            let rows = ui.add(egui::DragValue::new(&mut self.rows).clamp_range(1..=20).prefix("rows "));
+            if columns.changed() || rows.changed() {
+                browser.set_page_size(self.columns * self.rows);
+            }
+
+            ui.separator();
+            ui.text_edit_singleline(&mut self.sheet_path);
+            if ui.button("Save contact sheet").clicked() {
+                let tiles = browser.page_rows().iter().map(|&row| (&browser.samples()[row], format!("{}:{}", browser.samples()[row].label, row)));
+                self.status = match contact_sheet(tiles, self.columns as u32, self.rows as u32, 1).save(&self.sheet_path) {
+                    Ok(()) => format!("saved {}", self.sheet_path),
+                    Err(err) => format!("can not save {}: {}", self.sheet_path, err),
+                };
+            }
+        });
+    }
+
+    fn page_ui(&mut self, ui: &mut egui::Ui) {
+        let rows: Vec<usize> = self.browser.as_ref().unwrap().page_rows().to_vec();
+        egui::Grid::new("page").spacing([4.0, 4.0]).show(ui, |ui| {
+            for (i, &row) in rows.iter().enumerate() {
+                let texture = SizedTexture::from_handle(self.texture(ui.ctx(), row));
+                let label = self.browser.as_ref().unwrap().samples()[row].label;
+                ui.vertical(|ui| {
+                    let image = egui::Image::from_texture(texture).fit_to_exact_size(egui::vec2(THUMBNAIL, THUMBNAIL));
+                    if ui.add(egui::ImageButton::new(image).selected(self.selected == Some(row))).clicked() {
+                        self.selected = Some(row);
+                    }
+                    ui.small(format!("{}:{}", label, row));
+                });
+                if (i + 1) % self.columns == 0 {
+                    ui.end_row();
+                }
+            }
+        });
+    }
+
+    fn selected_ui(&mut self, ui: &mut egui::Ui) {
+        let Some(row) = self.selected else {
+            ui.label("click a sample to enlarge it");
+            return;
+        };
+        let texture = SizedTexture::from_handle(self.texture(ui.ctx(), row));
+        let label = self.browser.as_ref().unwrap().samples()[row].label;
+        ui.vertical(|ui| {
+            ui.add(egui::Image::from_texture(texture).fit_to_exact_size(egui::vec2(280.0, 280.0)));
+            ui.label(format!("row {}, label {}", row, label));
+        });
+
+        let Some(explanation) = self.explain(ui.ctx(), row) else {
+            return;
+        };
+        let predicted = argmax(&explanation.output_list);
+        ui.vertical(|ui| {
+            ui.label(format!("predicted {} ({:.3})", predicted, explanation.output_list[predicted]));
+            if let Some(saliency) = &explanation.saliency {
+                ui.add(egui::Image::from_texture(SizedTexture::from_handle(saliency)).fit_to_exact_size(egui::vec2(168.0, 168.0)));
+                ui.small("saliency (input gradient)");
+            }
+            ui.add(egui::Image::from_texture(SizedTexture::from_handle(&explanation.occlusion)).fit_to_exact_size(egui::vec2(168.0, 168.0)));
+            ui.small("occlusion sensitivity");
+        });
+    }
+
+    // the explanation of the selected row, computed once per row
+    fn explain(&mut self, ctx: &egui::Context, row: usize) -> Option<&Explanation> {
+        let model = self.model.as_ref()?;
+        let input_list = &self.browser.as_ref()?.samples()[row].input;
+        if model.input_shape().iter().product::<usize>() != input_list.len() {
+            return None;
+        }
+        if self.explanation.as_ref().is_none_or(|explanation| explanation.row != row) {
+            let output_list = model.predict(input_list);
+            let predicted = argmax(&output_list);
+            let side = (input_list.len() as f32).sqrt() as usize;
+            let occlusion = occlusion_map(|input| model.predict(input), input_list, (side, side), predicted, Occlusion::default());
+            let load = |name: &str, map: &[f32]| {
+                let image = overlay(input_list, map, 1);
+                let size = [image.width() as usize, image.height() as usize];
+                ctx.load_texture(name, ColorImage::from_rgb(size, image.as_raw()), TextureOptions::NEAREST)
+            };
+            self.explanation = Some(Explanation {
+                row,
+                saliency: match model {
+                    AnyModel::Network(network) => Some(load("saliency", &saliency_map(network, input_list, predicted))),
+                    _ => None,
+                },
+                occlusion: load("occlusion", &occlusion),
+                output_list,
+            });
+        }
+        self.explanation.as_ref()
+    }
+
+    // the cached 28*28 texture of a row
+    fn texture(&mut self, ctx: &egui::Context, row: usize) -> &TextureHandle {
+        if self.textures.len() > MAX_TEXTURES {
+            self.textures.clear();
+        }
+        let sample = &self.browser.as_ref().unwrap().samples()[row];
+        self.textures.entry(row).or_insert_with(|| {
+            let image = sample_image(sample, 1);
+            let size = [image.width() as usize, image.height() as usize];
+            ctx.load_texture(format!("sample{}", row), ColorImage::from_rgb(size, image.as_raw()), TextureOptions::NEAREST)
+        })
+    }
+}
@@ -0,0 +1,48 @@
+use eframe::egui;
+
+mod dataset_panel;
+mod training_panel;
+
+use dataset_panel::DatasetPanel;
+use training_panel::TrainingPanel;
+
+fn main() -> eframe::Result<()> {
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([1024.0, 720.0]),
+        ..Default::default()
+    };
+    eframe::run_native("NN-GUI-rs", options, Box::new(|_cc| Box::<App>::default()))
+}
+
+// the panels of the window, one tab each
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+enum Tab {
+    #[default]
+    Dataset,
+    Training,
+}
+
+#[derive(Default)]
+struct App {
+    tab: Tab,
+    dataset: DatasetPanel,
+    training: TrainingPanel,
+}
+
+impl eframe::App for App {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.selectable_value(&mut self.tab, Tab::Dataset, "Dataset");
+                ui.selectable_value(&mut self.tab, Tab::Training, "Training");
+            });
+        });
+        egui::CentralPanel::default().show(ctx, |ui| match self.tab {
+            Tab::Dataset => self.dataset.ui(ui),
+            Tab::Training => self.training.ui(ui),
+        });
+        if let Some((model, path)) = self.training.take_model() {
+            self.dataset.set_model(model, path);
+        }
+    }
+}
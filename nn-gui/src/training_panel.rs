@@ -0,0 +1,283 @@
+use eframe::egui;
+use egui_plot::{Legend, Line, Plot, PlotPoints, Points};
+use nn::dataset::{csv_files, read_mnist_csv, MNIST_CLASSES, MNIST_PIXELS};
+use nn::evaluation::{evaluate, Evaluation};
+use nn::metrics::{Metrics, MetricsLogger};
+use nn::trainer::{Trainer, TrainingData, TrainingReport};
+use nn::{NeuralNetwork, Optimizer};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+// the options of a run, like the ones of nn-train
+#[derive(Debug, Clone)]
+struct Config {
+    train_path: String,
+    test_path: String,
+    hidden_nodes: i32,
+    learning_rate: f32,
+    epochs: usize,
+    batch_size: usize,
+    threads: usize,
+    validation_split: f32,
+    patience: usize,
+    seed: u64,
+    momentum: f32,
+    log_every: u64,
+    save_path: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            train_path: "./dataset/mnist_train".to_string(),
+            test_path: "./dataset/mnist_test.csv".to_string(),
+            hidden_nodes: 200,
+            learning_rate: 0.1,
+            epochs: 5,
+            batch_size: 1,
+            threads: 1,
+            validation_split: 0.1,
+            patience: 2,
+            seed: 0,
+            momentum: 0.0,
+            log_every: 100,
+            save_path: "./mnist.model".to_string(),
+        }
+    }
+}
+
+// the result of a run which was not cancelled
+struct Finished {
+    network: NeuralNetwork,
+    report: TrainingReport,
+    // a model which could not be saved or tested is still handed to the dataset panel
+    test: Result<Evaluation, String>,
+    saved: Result<(), String>,
+}
+
+// a `Trainer` running on a background thread, steered through its flags
+struct Run {
+    stop: Arc<AtomicBool>,
+    pause: Arc<AtomicBool>,
+    metrics: Receiver<Metrics>,
+    handle: JoinHandle<Result<Option<Finished>, String>>,
+    shards: usize,
+    epochs: usize,
+}
+
+/// trains a network on a background thread like `nn-train`, with live loss and accuracy curves.
+/// The finished model is saved and handed to the dataset panel, see `take_model`
+#[derive(Default)]
+pub struct TrainingPanel {
+    config: Config,
+    run: Option<Run>,
+    history: Vec<Metrics>,
+    model: Option<(NeuralNetwork, String)>,
+    status: String,
+}
+
+impl TrainingPanel {
+    pub fn ui(&mut self, ui: &mut egui::Ui) {
+        self.poll();
+        ui.add_enabled_ui(self.run.is_none(), |ui| self.config_ui(ui));
+        ui.horizontal(|ui| self.control_ui(ui));
+        if !self.status.is_empty() {
+            ui.label(&self.status);
+        }
+        self.progress_ui(ui);
+        ui.separator();
+        self.plots_ui(ui);
+        if self.run.is_some() {
+            ui.ctx().request_repaint_after(Duration::from_millis(100));
+        }
+    }
+
+    /// the model of the last finished run and the path it was saved to, once
+    pub fn take_model(&mut self) -> Option<(NeuralNetwork, String)> {
+        self.model.take()
+    }
+
+    fn config_ui(&mut self, ui: &mut egui::Ui) {
+        let config = &mut self.config;
+        egui::Grid::new("config").num_columns(4).spacing([12.0, 4.0]).show(ui, |ui| {
+            ui.label("train csv file or shard directory");
+            ui.text_edit_singleline(&mut config.train_path);
+            ui.label("test csv file");
+            ui.text_edit_singleline(&mut config.test_path);
+            ui.end_row();
+
+            ui.label("hidden nodes");
+            ui.add(egui::DragValue::new(&mut config.hidden_nodes).clamp_range(1..=2000));
+            ui.label("learning rate");
+            ui.add(egui::DragValue::new(&mut config.learning_rate).clamp_range(0.0001..=10.0).speed(0.001));
+            ui.end_row();
+
+            ui.label("epochs");
+            ui.add(egui::DragValue::new(&mut config.epochs).clamp_range(1..=1000));
+            ui.label("batch size");
+            ui.add(egui::DragValue::new(&mut config.batch_size).clamp_range(1..=1024));
+            ui.end_row();
+
+            ui.label("threads");
+            ui.add(egui::DragValue::new(&mut config.threads).clamp_range(1..=64));
+            ui.label("momentum (0 is plain SGD)");
+            ui.add(egui::DragValue::new(&mut config.momentum).clamp_range(0.0..=0.999).speed(0.01));
+            ui.end_row();
+
+            ui.label("validation split");
+            ui.add(egui::DragValue::new(&mut config.validation_split).clamp_range(0.0..=0.9).speed(0.01));
+            ui.label("patience");
+            ui.add(egui::DragValue::new(&mut config.patience).clamp_range(1..=100));
+            ui.end_row();
+
+            ui.label("seed");
+            ui.add(egui::DragValue::new(&mut config.seed));
+            ui.label("updates between two records");
+            ui.add(egui::DragValue::new(&mut config.log_every).clamp_range(1..=100000));
+            ui.end_row();
+
+            ui.label("save the model to");
+            ui.text_edit_singleline(&mut config.save_path);
+            ui.end_row();
+        });
+    }
+
+    fn control_ui(&mut self, ui: &mut egui::Ui) {
+        match &self.run {
+            None => {
+                if ui.button("Start").clicked() {
+                    self.start();
+                }
+            }
+            Some(run) => {
+                let paused = run.pause.load(Ordering::SeqCst);
+                if ui.button(if paused { "Resume" } else { "Pause" }).clicked() {
+                    run.pause.store(!paused, Ordering::SeqCst);
+                }
+                if ui.button("Cancel").clicked() {
+                    run.stop.store(true, Ordering::SeqCst);
+                    self.status = "cancelling after the current batch".to_string();
+                }
+                if paused {
+                    ui.label("paused");
+                }
+            }
+        }
+    }
+
+    fn progress_ui(&self, ui: &mut egui::Ui) {
+        let Some(run) = &self.run else {
+            return;
+        };
+        let (epoch, shard, step) = self.history.last().map_or((0, 0, 0), |m| (m.epoch, m.shard + 1, m.step));
+        let epoch_progress = shard as f32 / run.shards as f32;
+        ui.add(egui::ProgressBar::new((epoch as f32 + epoch_progress) / run.epochs as f32)
+            .text(format!("epoch {}/{}, {} updates", epoch + 1, run.epochs, step)));
+        ui.add(egui::ProgressBar::new(epoch_progress).text(format!("shard {}/{}", shard, run.shards)));
+    }
+
+    fn plots_ui(&self, ui: &mut egui::Ui) {
+        let points = |value: fn(&Metrics) -> Option<f32>| -> Vec<[f64; 2]> {
+            self.history.iter().filter_map(|m| value(m).map(|v| [m.step as f64, v as f64])).collect()
+        };
+        let train_loss = points(|m| Some(m.loss));
+        let validation_loss = points(|m| m.validation_loss);
+        let accuracy = points(|m| m.accuracy);
+        let height = (ui.available_height() / 2.0 - 10.0).max(120.0);
+
+        Plot::new("loss").height(height).legend(Legend::default()).x_axis_label("updates").show(ui, |plot| {
+            plot.line(Line::new(PlotPoints::new(train_loss)).name("train loss"));
+            plot.line(Line::new(PlotPoints::new(validation_loss.clone())).name("validation loss"));
+            plot.points(Points::new(PlotPoints::new(validation_loss)).radius(3.0).name("validation loss"));
+        });
+        Plot::new("accuracy").height(height).legend(Legend::default()).include_y(0.0).include_y(1.0).x_axis_label("updates").show(ui, |plot| {
+            plot.line(Line::new(PlotPoints::new(accuracy.clone())).name("validation accuracy"));
+            plot.points(Points::new(PlotPoints::new(accuracy)).radius(3.0).name("validation accuracy"));
+        });
+    }
+
+    fn start(&mut self) {
+        let config = self.config.clone();
+        let shards: Vec<PathBuf> = match csv_files(&config.train_path) {
+            Ok(shards) if !shards.is_empty() => shards,
+            Ok(_) => {
+                self.status = format!("there are no csv files in {}", config.train_path);
+                return;
+            }
+            Err(err) => {
+                self.status = err.to_string();
+                return;
+            }
+        };
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let pause = Arc::new(AtomicBool::new(false));
+        let (sender, receiver) = mpsc::channel();
+        let trainer = Trainer::new()
+            .epochs(config.epochs)
+            .batch_size(config.batch_size)
+            .threads(config.threads)
+            .validation_split(config.validation_split)
+            .patience(config.patience)
+            .seed(config.seed)
+            .stop_flag(stop.clone())
+            .pause_flag(pause.clone())
+            .metrics(MetricsLogger::new(config.log_every).sink(sender));
+        let run = Run { stop, pause, metrics: receiver, shards: shards.len(), epochs: config.epochs, handle: thread::spawn(move || {
+            let mut network = NeuralNetwork::new_with_seed(MNIST_PIXELS as i32, config.hidden_nodes, MNIST_CLASSES as i32, config.learning_rate, config.seed);
+            if config.momentum > 0.0 {
+                network.set_optimizer(Optimizer::Momentum { momentum: config.momentum });
+            }
+            let report = trainer.fit(&mut network, &TrainingData::Shards(shards)).map_err(|err| err.to_string())?;
+            if report.interrupted {
+                return Ok(None);
+            }
+            let saved = network.save(&config.save_path).map_err(|err| format!("can not save {}: {}", config.save_path, err));
+            let test = read_mnist_csv(&config.test_path)
+                .map(|test_samples| evaluate(&network, &test_samples))
+                .map_err(|err| format!("can not test with {}: {}", config.test_path, err));
+            Ok(Some(Finished { network, report, test, saved }))
+        })};
+
+        self.run = Some(run);
+        self.history.clear();
+        self.status = format!("training {} shards", self.run.as_ref().unwrap().shards);
+    }
+
+    // collect the new records and the result of a finished run
+    fn poll(&mut self) {
+        let Some(run) = &self.run else {
+            return;
+        };
+        self.history.extend(run.metrics.try_iter());
+        if !run.handle.is_finished() {
+            return;
+        }
+        let run = self.run.take().unwrap();
+        self.history.extend(run.metrics.try_iter());
+        self.status = match run.handle.join() {
+            Ok(Ok(Some(finished))) => {
+                let best = finished.report.best_epoch.map_or(String::new(), |epoch| format!(", restored the weights of epoch {}", epoch + 1));
+                let saved = match finished.saved {
+                    Ok(()) => format!("saved to {}", self.config.save_path),
+                    Err(err) => err,
+                };
+                let test = match finished.test {
+                    Ok(test) => format!("test accuracy {:.4}", test.accuracy),
+                    Err(err) => err,
+                };
+                let status = format!("trained {} epochs{}, {}, {} and loaded into the dataset panel",
+                    finished.report.history.len(), best, test, saved);
+                self.model = Some((finished.network, self.config.save_path.clone()));
+                status
+            }
+            Ok(Ok(None)) => "cancelled".to_string(),
+            Ok(Err(err)) => err,
+            Err(_) => "the training thread panicked".to_string(),
+        };
+    }
+}
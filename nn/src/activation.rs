@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Float;
+
+/// activation functions of the layers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Activation {
+    /// 1 / (1 + e^(-x)), the activation function of the book
+    #[default]
+    Sigmoid,
+    /// max(0, x)
+    Relu,
+    /// tanh(x)
+    Tanh,
+    /// e^(x_i) / sum(e^(x_j)) over the values of one sample
+    Softmax,
+}
+
+impl Activation {
+    /// the activation of one value, e.g. of a hidden node of a `NeuralNetwork`. Softmax needs all values of a sample
+    pub(crate) fn value<T: Float>(&self, x: T) -> T {
+        match self {
+            Activation::Sigmoid => T::one() / (T::one() + (-x).exp()),
+            Activation::Relu => x.max(T::zero()),
+            Activation::Tanh => x.tanh(),
+            Activation::Softmax => panic!("softmax is not an activation of single values"),
+        }
+    }
+
+    /// the derivative of `value` calculated from its output
+    pub(crate) fn derivative<T: Float>(&self, output: T) -> T {
+        match self {
+            Activation::Sigmoid => output * (T::one() - output),
+            Activation::Relu => if output > T::zero() { T::one() } else { T::zero() },
+            Activation::Tanh => T::one() - output * output,
+            Activation::Softmax => panic!("softmax is not an activation of single values"),
+        }
+    }
+}
+
+impl std::fmt::Display for Activation {
+    /// the name `from_str` parses
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Activation::Sigmoid => "sigmoid",
+            Activation::Relu => "relu",
+            Activation::Tanh => "tanh",
+            Activation::Softmax => "softmax",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::str::FromStr for Activation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sigmoid" => Ok(Activation::Sigmoid),
+            "relu" => Ok(Activation::Relu),
+            "tanh" => Ok(Activation::Tanh),
+            "softmax" => Ok(Activation::Softmax),
+            _ => Err(format!("unknown activation {}, expected sigmoid, relu, tanh or softmax", s)),
+        }
+    }
+}
@@ -0,0 +1,335 @@
+//! data augmentation for handwritten digit images
+//!
+//! our own handwriting is thinner and less centred than the MNIST digits, so the training samples
+//! can be distorted randomly before they are fed into the network.
+
+use ndarray::Array;
+use ndarray_rand::rand::{Rng, SeedableRng};
+use ndarray_rand::rand_distr::{Distribution, Normal};
+use rand_chacha::ChaCha8Rng;
+
+use crate::dataset::Sample;
+use crate::Matrix;
+
+/// smallest scale factor of `Augmentation::Affine`, a factor of 0 has no inverse transform
+pub const MIN_SCALE: f32 = 0.01;
+
+/// one random distortion of an image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Augmentation {
+    /// random affine transform around the image center
+    Affine {
+        /// maximum rotation in degrees, the angle is uniform in [-max_rotation, max_rotation]
+        max_rotation: f32,
+        /// maximum shift in pixels for each axis
+        max_shift: f32,
+        /// minimum and maximum scale factor, factors below `MIN_SCALE` are used as `MIN_SCALE`
+        scale: (f32, f32),
+        /// maximum horizontal shear factor
+        max_shear: f32,
+    },
+    /// elastic distortion (Simard et al. 2003), a random displacement field smoothed by a gaussian kernel
+    Elastic {
+        /// intensity of the displacement in pixels
+        alpha: f32,
+        /// standard deviation of the gaussian smoothing kernel
+        sigma: f32,
+    },
+    /// make the strokes thicker with a grayscale dilation
+    Thicken {
+        /// radius of the square structuring element
+        radius: usize,
+    },
+    /// make the strokes thinner with a grayscale erosion
+    Thin {
+        /// radius of the square structuring element
+        radius: usize,
+    },
+    /// additive gaussian noise
+    GaussianNoise {
+        /// standard deviation of the noise, no noise is added for 0, a negative or a non-finite value
+        std_dev: f32,
+    },
+}
+
+/// a seeded pipeline of augmentations which are applied one after another
+#[derive(Debug, Clone)]
+pub struct Augmenter {
+    steps: Vec<(Augmentation, f32)>,
+    width: usize,
+    height: usize,
+    // the background value and the maximum ink value of the images
+    range: (f32, f32),
+    rng: ChaCha8Rng,
+}
+
+impl Augmenter {
+    /// create an empty pipeline for the scaled 28 * 28 MNIST images (values in range 0.01 to 1.00)
+    pub fn new(seed: u64) -> Augmenter {
+        Augmenter {
+            steps: vec![],
+            width: 28,
+            height: 28,
+            range: (0.01, 1.0),
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// set the size of the images
+    pub fn size(mut self, width: usize, height: usize) -> Augmenter {
+        self.width = width;
+        self.height = height;
+        self
+    }
+
+    /// set the background value and the maximum value of the images, results are clamped to this range
+    pub fn range(mut self, background: f32, max: f32) -> Augmenter {
+        self.range = (background, max);
+        self
+    }
+
+    /// append an augmentation which is always applied
+    pub fn then(self, augmentation: Augmentation) -> Augmenter {
+        self.then_with_probability(augmentation, 1.0)
+    }
+
+    /// append an augmentation which is applied with the given probability
+    pub fn then_with_probability(mut self, augmentation: Augmentation, probability: f32) -> Augmenter {
+        self.steps.push((augmentation, probability));
+        self
+    }
+
+    /// apply the pipeline to one image
+    pub fn apply(&mut self, image: &Matrix) -> Matrix {
+        let mut image = image.clone();
+        for (augmentation, probability) in self.steps.clone() {
+            if probability < 1.0 && self.rng.gen::<f32>() >= probability {
+                continue;
+            }
+            image = match augmentation {
+                Augmentation::Affine { max_rotation, max_shift, scale, max_shear } => {
+                    let angle = uniform(&mut self.rng, max_rotation).to_radians();
+                    let shift = (uniform(&mut self.rng, max_shift), uniform(&mut self.rng, max_shift));
+                    let scale = (valid_scale(scale.0), valid_scale(scale.1));
+                    let factor = if scale.0 < scale.1 { self.rng.gen_range(scale.0..scale.1) } else { scale.0 };
+                    let shear = uniform(&mut self.rng, max_shear);
+                    self.affine(&image, angle, shift, factor, shear)
+                }
+                Augmentation::Elastic { alpha, sigma } => self.elastic(&image, alpha, sigma),
+                Augmentation::Thicken { radius } => morphology(&image, radius, f32::max),
+                Augmentation::Thin { radius } => morphology(&image, radius, f32::min),
+                Augmentation::GaussianNoise { std_dev } => match Normal::new(0.0, std_dev) {
+                    Ok(normal) if std_dev > 0.0 => image.mapv(|x| x + normal.sample(&mut self.rng)),
+                    _ => image,
+                },
+            };
+            image.mapv_inplace(|x| x.clamp(self.range.0, self.range.1));
+        }
+        image
+    }
+
+    /// apply the pipeline to the input of a sample, the target and the label are kept
+    pub fn apply_sample(&mut self, sample: &Sample) -> Sample {
+        let image = Array::from_shape_vec((self.height, self.width), sample.input.clone())
+            .expect("sample input does not match the augmenter image size");
+        Sample {
+            input: self.apply(&image).into_iter().collect(),
+            target: sample.target.clone(),
+            label: sample.label,
+        }
+    }
+
+    // rotate, shear and scale around the image center and then shift.
+    // every output pixel is mapped back into the source image and bilinear interpolated.
+    fn affine(&self, image: &Matrix, angle: f32, shift: (f32, f32), scale: f32, shear: f32) -> Matrix {
+        // forward matrix M = rotation * shear * scale
+        let (sin, cos) = angle.sin_cos();
+        let m = [
+            [cos * scale, (cos * shear - sin) * scale],
+            [sin * scale, (sin * shear + cos) * scale],
+        ];
+        let det = m[0][0] * m[1][1] - m[0][1] * m[1][0];
+        let inverse = [
+            [m[1][1] / det, -m[0][1] / det],
+            [-m[1][0] / det, m[0][0] / det],
+        ];
+        let center_x = (self.width as f32 - 1.0) / 2.0;
+        let center_y = (self.height as f32 - 1.0) / 2.0;
+        Array::from_shape_fn((self.height, self.width), |(y, x)| {
+            let dx = x as f32 - center_x - shift.0;
+            let dy = y as f32 - center_y - shift.1;
+            let source_x = inverse[0][0] * dx + inverse[0][1] * dy + center_x;
+            let source_y = inverse[1][0] * dx + inverse[1][1] * dy + center_y;
+            self.bilinear(image, source_x, source_y)
+        })
+    }
+
+    fn elastic(&mut self, image: &Matrix, alpha: f32, sigma: f32) -> Matrix {
+        let shape = (self.height, self.width);
+        let field_x: Matrix = Array::from_shape_fn(shape, |_| self.rng.gen_range(-1.0..1.0));
+        let field_y: Matrix = Array::from_shape_fn(shape, |_| self.rng.gen_range(-1.0..1.0));
+        let field_x = gaussian_blur(&field_x, sigma) * alpha;
+        let field_y = gaussian_blur(&field_y, sigma) * alpha;
+        Array::from_shape_fn(shape, |(y, x)| {
+            self.bilinear(image, x as f32 + field_x[[y, x]], y as f32 + field_y[[y, x]])
+        })
+    }
+
+    // pixels outside of the image are background
+    fn bilinear(&self, image: &Matrix, x: f32, y: f32) -> f32 {
+        let pixel = |px: i64, py: i64| {
+            if px < 0 || py < 0 || px >= self.width as i64 || py >= self.height as i64 {
+                self.range.0
+            } else {
+                image[[py as usize, px as usize]]
+            }
+        };
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let top = pixel(x0, y0) * (1.0 - fx) + pixel(x0 + 1, y0) * fx;
+        let bottom = pixel(x0, y0 + 1) * (1.0 - fx) + pixel(x0 + 1, y0 + 1) * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+/// adapter which augments every sample of a dataset stream
+#[derive(Debug)]
+pub struct Augmented<I> {
+    samples: I,
+    augmenter: Augmenter,
+}
+
+impl<I: Iterator<Item = Sample>> Iterator for Augmented<I> {
+    type Item = Sample;
+
+    fn next(&mut self) -> Option<Sample> {
+        let sample = self.samples.next()?;
+        Some(self.augmenter.apply_sample(&sample))
+    }
+}
+
+/// extension trait to compose an `Augmenter` into any stream of samples
+pub trait AugmentExt: Iterator<Item = Sample> + Sized {
+    /// augment every sample of the stream with `augmenter`
+    fn augment(self, augmenter: Augmenter) -> Augmented<Self> {
+        Augmented { samples: self, augmenter }
+    }
+}
+
+impl<I: Iterator<Item = Sample>> AugmentExt for I {}
+
+fn uniform(rng: &mut ChaCha8Rng, max: f32) -> f32 {
+    if max > 0.0 {
+        rng.gen_range(-max..max)
+    } else {
+        0.0
+    }
+}
+
+// a non-finite scale factor is used as 1
+fn valid_scale(scale: f32) -> f32 {
+    if scale.is_finite() {
+        scale.max(MIN_SCALE)
+    } else {
+        1.0
+    }
+}
+
+// grayscale dilation (max) or erosion (min) with a square structuring element
+fn morphology(image: &Matrix, radius: usize, op: fn(f32, f32) -> f32) -> Matrix {
+    let (height, width) = image.dim();
+    Array::from_shape_fn((height, width), |(y, x)| {
+        let mut value = image[[y, x]];
+        for ny in y.saturating_sub(radius)..(y + radius + 1).min(height) {
+            for nx in x.saturating_sub(radius)..(x + radius + 1).min(width) {
+                value = op(value, image[[ny, nx]]);
+            }
+        }
+        value
+    })
+}
+
+// separable gaussian blur, the borders are clamped to the edge values
+fn gaussian_blur(image: &Matrix, sigma: f32) -> Matrix {
+    if sigma <= 0.0 {
+        return image.clone();
+    }
+    let radius = (3.0 * sigma).ceil() as i64;
+    let kernel: Vec<f32> = (-radius..=radius).map(|i| (-(i * i) as f32 / (2.0 * sigma * sigma)).exp()).collect();
+    let total: f32 = kernel.iter().sum();
+    let (height, width) = image.dim();
+    let blur = |image: &Matrix, horizontal: bool| -> Matrix {
+        Array::from_shape_fn((height, width), |(y, x)| {
+            kernel.iter().enumerate().map(|(k, weight)| {
+                let offset = k as i64 - radius;
+                let value = if horizontal {
+                    image[[y, (x as i64 + offset).clamp(0, width as i64 - 1) as usize]]
+                } else {
+                    image[[(y as i64 + offset).clamp(0, height as i64 - 1) as usize, x]]
+                };
+                weight * value
+            }).sum::<f32>() / total
+        })
+    };
+    blur(&blur(image, true), false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digit() -> Matrix {
+        // a vertical stroke in the middle of the image
+        Array::from_shape_fn((28, 28), |(y, x)| if x == 14 && (6..22).contains(&y) { 1.0 } else { 0.01 })
+    }
+
+    #[test]
+    fn same_seed_same_augmentation() {
+        let pipeline = |seed| Augmenter::new(seed)
+            .then(Augmentation::Affine { max_rotation: 15.0, max_shift: 2.0, scale: (0.9, 1.1), max_shear: 0.2 })
+            .then(Augmentation::Elastic { alpha: 8.0, sigma: 3.0 })
+            .then(Augmentation::GaussianNoise { std_dev: 0.05 });
+        let image = digit();
+        assert_eq!(pipeline(7).apply(&image), pipeline(7).apply(&image));
+        assert_ne!(pipeline(7).apply(&image), pipeline(8).apply(&image));
+    }
+
+    #[test]
+    fn identity_affine_keeps_image() {
+        let mut augmenter = Augmenter::new(0)
+            .then(Augmentation::Affine { max_rotation: 0.0, max_shift: 0.0, scale: (1.0, 1.0), max_shear: 0.0 });
+        let image = digit();
+        let result = augmenter.apply(&image);
+        assert!(result.iter().zip(image.iter()).all(|(a, b)| (a - b).abs() < 1e-5));
+    }
+
+    #[test]
+    fn invalid_arguments_do_not_break_the_image() {
+        let image = digit();
+        for std_dev in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert_eq!(Augmenter::new(0).then(Augmentation::GaussianNoise { std_dev }).apply(&image), image);
+        }
+        for scale in [(0.0, 0.0), (-1.0, 0.0), (f32::NAN, f32::INFINITY)] {
+            let affine = Augmentation::Affine { max_rotation: 15.0, max_shift: 2.0, scale, max_shear: 0.2 };
+            let result = Augmenter::new(0).then(affine).apply(&image);
+            assert!(result.iter().all(|x| (0.01..=1.0).contains(x)), "{:?}", scale);
+        }
+    }
+
+    #[test]
+    fn stroke_width_and_noise() {
+        let image = digit();
+        let thick = Augmenter::new(0).then(Augmentation::Thicken { radius: 1 }).apply(&image);
+        let thin = Augmenter::new(0).then(Augmentation::Thin { radius: 1 }).apply(&image);
+        assert!(thick.sum() > image.sum());
+        assert!(thin.sum() < image.sum());
+
+        let samples = vec![Sample { input: image.iter().cloned().collect(), target: vec![0.99], label: 1 }];
+        let noisy: Vec<Sample> = samples.into_iter()
+            .augment(Augmenter::new(0).then(Augmentation::GaussianNoise { std_dev: 1.0 }))
+            .collect();
+        assert_eq!(noisy[0].label, 1);
+        assert!(noisy[0].input.iter().all(|x| (0.01..=1.0).contains(x)));
+    }
+}
@@ -0,0 +1,472 @@
+//! tape based reverse mode automatic differentiation over ndarray tensors
+//!
+//! every operation on a `Var` records its result and a backward function on a `Tape`. `Var::backward`
+//! walks the tape from the end and sums up the gradient of every recorded value, so the backward pass of a
+//! layer or a loss follows from its forward pass, like the ones of `Dense`, `Conv2d`, `ActivationLayer` and `Loss`:
+//!
+//! ```
+//! use ndarray::array;
+//! use nn::autograd::Tape;
+//!
+//! let tape = Tape::new();
+//! let x = tape.var(array![[1.0, 2.0]].into_dyn());
+//! let w = tape.var(array![[0.5], [-0.5]].into_dyn());
+//! let loss = x.matmul(w).sigmoid().square().sum();
+//! let gradients = loss.backward();
+//! assert_eq!(gradients.wrt(w).shape(), &[2, 1]);
+//! ```
+
+use ndarray::{s, Array2, Array4, Axis, Ix2, Ix4, IxDyn};
+use std::cell::RefCell;
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+use crate::activation::Activation;
+use crate::layers::{col2im, im2col, output_size, Tensor};
+
+type BackwardFn = Box<dyn Fn(&Tensor) -> Vec<Tensor>>;
+
+// a recorded value, the backward function maps its gradient to the gradients of its parents
+struct Node {
+    value: Tensor,
+    parents: Vec<usize>,
+    backward: Option<BackwardFn>,
+}
+
+/// records the operations of one forward pass
+#[derive(Default)]
+pub struct Tape {
+    nodes: RefCell<Vec<Node>>,
+}
+
+impl fmt::Debug for Tape {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Tape").field("nodes", &self.nodes.borrow().len()).finish()
+    }
+}
+
+impl Tape {
+    pub fn new() -> Tape {
+        Tape::default()
+    }
+
+    /// add an input or a parameter to the tape
+    pub fn var(&self, value: Tensor) -> Var<'_> {
+        self.push(value, vec![], None)
+    }
+
+    fn push(&self, value: Tensor, parents: Vec<usize>, backward: Option<BackwardFn>) -> Var<'_> {
+        let mut nodes = self.nodes.borrow_mut();
+        nodes.push(Node { value, parents, backward });
+        Var { tape: self, index: nodes.len() - 1 }
+    }
+}
+
+/// a value recorded on a tape
+#[derive(Debug, Clone, Copy)]
+pub struct Var<'t> {
+    tape: &'t Tape,
+    index: usize,
+}
+
+impl<'t> Var<'t> {
+    /// copy of the value
+    pub fn value(&self) -> Tensor {
+        self.tape.nodes.borrow()[self.index].value.clone()
+    }
+
+    pub fn shape(&self) -> Vec<usize> {
+        self.tape.nodes.borrow()[self.index].value.shape().to_vec()
+    }
+
+    fn unary(self, value: Tensor, backward: impl Fn(&Tensor) -> Tensor + 'static) -> Var<'t> {
+        self.tape.push(value, vec![self.index], Some(Box::new(move |g| vec![backward(g)])))
+    }
+
+    fn binary(self, other: Var<'t>, value: Tensor, backward: impl Fn(&Tensor) -> (Tensor, Tensor) + 'static) -> Var<'t> {
+        assert!(std::ptr::eq(self.tape, other.tape), "variables of different tapes");
+        self.tape.push(value, vec![self.index, other.index], Some(Box::new(move |g| {
+            let (a, b) = backward(g);
+            vec![a, b]
+        })))
+    }
+
+    /// multiply every value by a constant
+    pub fn scale(self, factor: f32) -> Var<'t> {
+        let value = self.value() * factor;
+        self.unary(value, move |g| g * factor)
+    }
+
+    /// matrix product of two matrices
+    pub fn matmul(self, other: Var<'t>) -> Var<'t> {
+        let a = self.value().into_dimensionality::<Ix2>().expect("matmul needs a matrix");
+        let b = other.value().into_dimensionality::<Ix2>().expect("matmul needs a matrix");
+        let value = a.dot(&b).into_dyn();
+        // dA = g * B^T, dB = A^T * g
+        self.binary(other, value, move |g| {
+            let g = g.view().into_dimensionality::<Ix2>().unwrap();
+            (g.dot(&b.t()).into_dyn(), a.t().dot(&g).into_dyn())
+        })
+    }
+
+    /// transpose of a matrix
+    pub fn t(self) -> Var<'t> {
+        let value = self.value().into_dimensionality::<Ix2>().expect("t needs a matrix").t().to_owned().into_dyn();
+        self.unary(value, |g| g.t().to_owned())
+    }
+
+    /// broadcast to a larger shape
+    pub fn broadcast(self, shape: &[usize]) -> Var<'t> {
+        let a = self.value();
+        let value = a.broadcast(IxDyn(shape)).expect("can not broadcast to the shape").to_owned();
+        let a_shape = a.shape().to_vec();
+        self.unary(value, move |g| unbroadcast(g, &a_shape))
+    }
+
+    /// change the shape, the number of values stays the same
+    pub fn reshape(self, shape: &[usize]) -> Var<'t> {
+        let a = self.value();
+        let a_shape = a.shape().to_vec();
+        let value = a.as_standard_layout().into_owned().into_shape(shape).expect("reshape does not match the tensor size");
+        self.unary(value, move |g| g.as_standard_layout().into_owned().into_shape(a_shape.clone()).unwrap())
+    }
+
+    /// apply an activation function to the last axis, e.g. softmax over the outputs of every sample
+    pub fn activation(self, activation: Activation) -> Var<'t> {
+        match activation {
+            Activation::Sigmoid => self.sigmoid(),
+            Activation::Relu => self.relu(),
+            Activation::Tanh => self.tanh(),
+            Activation::Softmax => self.softmax(),
+        }
+    }
+
+    /// 1 / (1 + e^(-x)), calculated as 0.5 * tanh(0.5 * x) + 0.5 which does not overflow
+    pub fn sigmoid(self) -> Var<'t> {
+        self.scale(0.5).tanh().scale(0.5).add_scalar(0.5)
+    }
+
+    pub fn relu(self) -> Var<'t> {
+        let a = self.value();
+        self.unary(a.mapv(|v| v.max(0.0)), move |g| g * &a.mapv(|v| if v > 0.0 { 1.0 } else { 0.0 }))
+    }
+
+    pub fn tanh(self) -> Var<'t> {
+        let value = self.value().mapv(f32::tanh);
+        let output = value.clone();
+        self.unary(value, move |g| g * &output.mapv(|y| 1.0 - y * y))
+    }
+
+    /// e^(x_i) / sum(e^(x_j)) over the last axis
+    pub fn softmax(self) -> Var<'t> {
+        let shape = self.shape();
+        let last = shape.len() - 1;
+        // subtract the max value so exp can not overflow, the result does not depend on it
+        let max = self.value().map_axis(Axis(last), |row| row.fold(f32::NEG_INFINITY, |a, b| a.max(*b)));
+        let exp = (self - self.tape.var(max.insert_axis(Axis(last)))).exp();
+        let mut sum_shape = shape;
+        sum_shape[last] = 1;
+        exp / exp.sum_axis(last).reshape(&sum_shape)
+    }
+
+    /// add a constant to every value
+    pub fn add_scalar(self, offset: f32) -> Var<'t> {
+        let value = self.value() + offset;
+        self.unary(value, |g| g.clone())
+    }
+
+    /// raise the values below `min` to it, the gradient is passed through as if no value was raised
+    pub fn clamp_min(self, min: f32) -> Var<'t> {
+        let value = self.value().mapv(|v| v.max(min));
+        self.unary(value, |g| g.clone())
+    }
+
+    pub fn exp(self) -> Var<'t> {
+        let value = self.value().mapv(f32::exp);
+        let output = value.clone();
+        self.unary(value, move |g| g * &output)
+    }
+
+    /// natural logarithm
+    pub fn ln(self) -> Var<'t> {
+        let a = self.value();
+        self.unary(a.mapv(f32::ln), move |g| g / &a)
+    }
+
+    pub fn square(self) -> Var<'t> {
+        let a = self.value();
+        self.unary(a.mapv(|v| v * v), move |g| g * &a * 2.0)
+    }
+
+    /// sum of all values as a scalar tensor
+    pub fn sum(self) -> Var<'t> {
+        let a = self.value();
+        let value = Tensor::from_elem(IxDyn(&[]), a.sum());
+        let a_shape = a.shape().to_vec();
+        self.unary(value, move |g| Tensor::from_elem(IxDyn(&a_shape), g.sum()))
+    }
+
+    /// mean of all values as a scalar tensor
+    pub fn mean(self) -> Var<'t> {
+        let count = self.tape.nodes.borrow()[self.index].value.len().max(1);
+        self.sum().scale(1.0 / count as f32)
+    }
+
+    /// sum over one axis, the axis is removed
+    pub fn sum_axis(self, axis: usize) -> Var<'t> {
+        let a = self.value();
+        let a_shape = a.shape().to_vec();
+        self.unary(a.sum_axis(Axis(axis)), move |g| {
+            g.view().insert_axis(Axis(axis)).broadcast(IxDyn(&a_shape)).unwrap().to_owned()
+        })
+    }
+
+    /// 2d convolution of a `[batch, channels, height, width]` input with `[out_channels, channels, kernel, kernel]` weights
+    pub fn conv2d(self, weights: Var<'t>, stride: usize, padding: usize) -> Var<'t> {
+        let x = self.value().into_dimensionality::<Ix4>().expect("conv2d input must be [batch, channels, height, width]");
+        let w = weights.value().into_dimensionality::<Ix4>().expect("conv2d weights must be [out, in, kernel, kernel]");
+        let (batch, channels, height, width) = x.dim();
+        let (out_channels, in_channels, kernel, _) = w.dim();
+        assert_eq!(channels, in_channels, "conv2d input channels do not match");
+        let (out_height, out_width) = (output_size("conv2d", height, kernel, stride, padding), output_size("conv2d", width, kernel, stride, padding));
+        let w = w.into_shape((out_channels, in_channels * kernel * kernel)).unwrap();
+
+        let mut output = Array4::zeros((batch, out_channels, out_height, out_width));
+        for n in 0..batch {
+            let columns = im2col(x.slice(s![n, .., .., ..]), kernel, stride, padding);
+            output.slice_mut(s![n, .., .., ..])
+                .assign(&w.dot(&columns).into_shape((out_channels, out_height, out_width)).unwrap());
+        }
+
+        self.binary(weights, output.into_dyn(), move |g| {
+            let mut grad_x = Array4::zeros(x.raw_dim());
+            let mut grad_w = Array2::zeros(w.raw_dim());
+            for n in 0..batch {
+                let columns = im2col(x.slice(s![n, .., .., ..]), kernel, stride, padding);
+                let g_n = g.slice(s![n, .., .., ..]).to_owned().into_shape((out_channels, out_height * out_width)).unwrap();
+                grad_w += &g_n.dot(&columns.t());
+                let image = col2im(&w.t().dot(&g_n), (channels, height, width), kernel, stride, padding);
+                grad_x.slice_mut(s![n, .., .., ..]).assign(&image);
+            }
+            (grad_x.into_dyn(), grad_w.into_shape((out_channels, in_channels, kernel, kernel)).unwrap().into_dyn())
+        })
+    }
+
+    /// gradients of every value of the tape with respect to this value, the gradient of this value is all ones
+    pub fn backward(self) -> Gradients {
+        let ones = Tensor::ones(IxDyn(&self.shape()));
+        self.backward_with(ones)
+    }
+
+    /// gradients of every value of the tape when the gradient of this value is `gradient`, e.g. the
+    /// gradient of the loss with respect to the output of a layer
+    pub fn backward_with(self, gradient: Tensor) -> Gradients {
+        assert_eq!(gradient.shape(), &self.shape()[..], "the gradient does not match the shape of the value");
+        let nodes = self.tape.nodes.borrow();
+        let mut gradients: Vec<Option<Tensor>> = vec![None; self.index + 1];
+        gradients[self.index] = Some(gradient);
+        for index in (0..=self.index).rev() {
+            let (node, gradient) = match (&nodes[index], &gradients[index]) {
+                (node @ Node { backward: Some(_), .. }, Some(gradient)) => (node, gradient),
+                _ => continue,
+            };
+            let parent_gradients = (node.backward.as_ref().unwrap())(gradient);
+            for (&parent, parent_gradient) in node.parents.iter().zip(parent_gradients) {
+                match &mut gradients[parent] {
+                    Some(sum) => *sum += &parent_gradient,
+                    empty => *empty = Some(parent_gradient),
+                }
+            }
+        }
+        Gradients { gradients }
+    }
+}
+
+/// elementwise sum, the shapes are broadcast like ndarray's arithmetic operators
+impl<'t> Add for Var<'t> {
+    type Output = Var<'t>;
+
+    fn add(self, other: Var<'t>) -> Var<'t> {
+        let (a, b) = (self.value(), other.value());
+        let (a_shape, b_shape) = (a.shape().to_vec(), b.shape().to_vec());
+        self.binary(other, &a + &b, move |g| (unbroadcast(g, &a_shape), unbroadcast(g, &b_shape)))
+    }
+}
+
+/// elementwise difference with broadcasting
+impl<'t> Sub for Var<'t> {
+    type Output = Var<'t>;
+
+    fn sub(self, other: Var<'t>) -> Var<'t> {
+        let (a, b) = (self.value(), other.value());
+        let (a_shape, b_shape) = (a.shape().to_vec(), b.shape().to_vec());
+        self.binary(other, &a - &b, move |g| (unbroadcast(g, &a_shape), -unbroadcast(g, &b_shape)))
+    }
+}
+
+/// elementwise product with broadcasting
+impl<'t> Mul for Var<'t> {
+    type Output = Var<'t>;
+
+    fn mul(self, other: Var<'t>) -> Var<'t> {
+        let (a, b) = (self.value(), other.value());
+        let value = &a * &b;
+        self.binary(other, value, move |g| (unbroadcast(&(g * &b), a.shape()), unbroadcast(&(g * &a), b.shape())))
+    }
+}
+
+/// elementwise quotient with broadcasting
+impl<'t> Div for Var<'t> {
+    type Output = Var<'t>;
+
+    // the gradient of a quotient needs products
+    #[allow(clippy::suspicious_arithmetic_impl)]
+    fn div(self, other: Var<'t>) -> Var<'t> {
+        let (a, b) = (self.value(), other.value());
+        let value = &a / &b;
+        self.binary(other, value, move |g| {
+            let grad_b = -(g * &a) / &b.mapv(|v| v * v);
+            (unbroadcast(&(g / &b), a.shape()), unbroadcast(&grad_b, b.shape()))
+        })
+    }
+}
+
+impl<'t> Neg for Var<'t> {
+    type Output = Var<'t>;
+
+    fn neg(self) -> Var<'t> {
+        self.scale(-1.0)
+    }
+}
+
+/// the gradients calculated by `Var::backward`
+#[derive(Debug, Clone)]
+pub struct Gradients {
+    gradients: Vec<Option<Tensor>>,
+}
+
+impl Gradients {
+    /// gradient with respect to `var`, `None` when the result does not depend on it
+    pub fn get(&self, var: Var) -> Option<&Tensor> {
+        self.gradients.get(var.index).and_then(Option::as_ref)
+    }
+
+    /// gradient with respect to `var`, zeros when the result does not depend on it
+    pub fn wrt(&self, var: Var) -> Tensor {
+        self.get(var).cloned().unwrap_or_else(|| Tensor::zeros(IxDyn(&var.shape())))
+    }
+}
+
+/// central difference gradients of `f` with respect to every value of the inputs
+pub fn numerical_gradient<F: Fn(&[Tensor]) -> f32>(f: F, inputs: &[Tensor], epsilon: f32) -> Vec<Tensor> {
+    let mut shifted: Vec<Tensor> = inputs.iter().map(|input| input.as_standard_layout().into_owned()).collect();
+    let mut gradients = vec![];
+    for i in 0..shifted.len() {
+        let mut gradient = Tensor::zeros(shifted[i].raw_dim());
+        for j in 0..shifted[i].len() {
+            let original = shifted[i].as_slice().unwrap()[j];
+            shifted[i].as_slice_mut().unwrap()[j] = original + epsilon;
+            let plus = f(&shifted);
+            shifted[i].as_slice_mut().unwrap()[j] = original - epsilon;
+            let minus = f(&shifted);
+            shifted[i].as_slice_mut().unwrap()[j] = original;
+            gradient.as_slice_mut().unwrap()[j] = (plus - minus) / (2.0 * epsilon);
+        }
+        gradients.push(gradient);
+    }
+    gradients
+}
+
+/// largest difference between the tape gradients of the scalar function `f` and central differences
+///
+/// the difference of a value is relative to the larger gradient when that is greater than 1.
+pub fn check_gradients<F>(f: F, inputs: &[Tensor], epsilon: f32) -> f32
+where
+    F: for<'t> Fn(&'t Tape, &[Var<'t>]) -> Var<'t>,
+{
+    let tape = Tape::new();
+    let vars: Vec<Var> = inputs.iter().map(|input| tape.var(input.clone())).collect();
+    let output = f(&tape, &vars);
+    assert_eq!(output.value().len(), 1, "gradients can only be checked for a scalar function");
+    let gradients = output.backward();
+
+    let numerical = numerical_gradient(|inputs| {
+        let tape = Tape::new();
+        let vars: Vec<Var> = inputs.iter().map(|input| tape.var(input.clone())).collect();
+        f(&tape, &vars).value().sum()
+    }, inputs, epsilon);
+
+    let mut max_error: f32 = 0.0;
+    for (var, numerical) in vars.iter().zip(&numerical) {
+        for (a, n) in gradients.wrt(*var).iter().zip(numerical.iter()) {
+            max_error = max_error.max((a - n).abs() / a.abs().max(n.abs()).max(1.0));
+        }
+    }
+    max_error
+}
+
+// sum the gradient of a broadcast value back to the shape of the value
+fn unbroadcast(gradient: &Tensor, shape: &[usize]) -> Tensor {
+    let mut gradient = gradient.clone();
+    while gradient.ndim() > shape.len() {
+        gradient = gradient.sum_axis(Axis(0));
+    }
+    for (axis, &size) in shape.iter().enumerate() {
+        if size == 1 && gradient.shape()[axis] != 1 {
+            gradient = gradient.sum_axis(Axis(axis)).insert_axis(Axis(axis));
+        }
+    }
+    gradient
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::{Conv2d, Layer};
+    use crate::Mode;
+    use ndarray_rand::rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn assert_close(a: &Tensor, b: &Tensor) {
+        assert_eq!(a.shape(), b.shape());
+        assert!(a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-5), "{} != {}", a, b);
+    }
+
+    fn tensor(shape: &[usize], offset: f32) -> Tensor {
+        let len = shape.iter().product();
+        Tensor::from_shape_vec(shape, (0..len).map(|i| (i as f32 * 0.7 + offset).sin()).collect()).unwrap()
+    }
+
+    #[test]
+    fn operations_match_numerical_gradients() {
+        let inputs = [tensor(&[3, 4], 0.0), tensor(&[4, 2], 1.0), tensor(&[1, 2], 2.0)];
+        let error = check_gradients(|_, v| {
+            let hidden = (v[0].matmul(v[1]) + v[2]).tanh();
+            let probabilities = (hidden * v[2].broadcast(&[3, 2])).softmax();
+            (probabilities.ln().sum_axis(1) - hidden.relu().mean()).square().sum()
+        }, &inputs, 1e-2);
+        assert!(error < 1e-2, "gradient error {}", error);
+
+        let positive = [tensor(&[2, 3], 0.0).mapv(f32::exp), tensor(&[3], 1.0).mapv(|v| v + 2.0)];
+        let error = check_gradients(|_, v| {
+            (v[0] / v[1]).exp().sigmoid().reshape(&[3, 2]).t().scale(0.5).sum() - (-v[1]).mean()
+        }, &positive, 1e-2);
+        assert!(error < 1e-2, "gradient error {}", error);
+    }
+
+    #[test]
+    fn conv2d_matches_the_layer() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let input = tensor(&[2, 2, 5, 5], 0.5);
+        let mut conv = Conv2d::new(2, 3, 3, &mut rng).stride(2).padding(1);
+        let output = conv.forward(&input, Mode::Train);
+        let grad_input = conv.backward(&input, &output, &Tensor::ones(output.raw_dim()));
+
+        let tape = Tape::new();
+        let x = tape.var(input.clone());
+        let w = tape.var(conv.weights().clone().into_shape(vec![3, 2, 3, 3]).unwrap());
+        let y = x.conv2d(w, 2, 1);
+        assert_close(&y.value(), &output);
+        assert_close(&y.sum().backward().wrt(x), &grad_input);
+    }
+}
@@ -0,0 +1,187 @@
+//! paging through a dataset, shared by the `nn-image` command line browser and the dataset panel of the GUI
+
+use std::path::Path;
+
+use crate::dataset::{csv_files, read_mnist_csv, DatasetError};
+use crate::Sample;
+
+/// the samples of a dataset split into pages, optionally only the samples of one label
+///
+/// rows are the positions of the samples in the whole dataset, they do not change with the filter.
+#[derive(Debug, Clone)]
+pub struct DatasetBrowser {
+    samples: Vec<Sample>,
+    label: Option<u8>,
+    selection: Option<Vec<usize>>,
+    rows: Vec<usize>,
+    page_size: usize,
+    page: usize,
+}
+
+impl DatasetBrowser {
+    pub fn new(samples: Vec<Sample>, page_size: usize) -> DatasetBrowser {
+        assert!(page_size > 0, "page size must be greater than 0");
+        let rows = (0..samples.len()).collect();
+        DatasetBrowser { samples, label: None, selection: None, rows, page_size, page: 0 }
+    }
+
+    /// open a MNIST csv file, or every csv file of a directory like the train shards in `file0.csv` order
+    pub fn open<P: AsRef<Path>>(path: P, page_size: usize) -> Result<DatasetBrowser, DatasetError> {
+        let path = path.as_ref();
+        let mut samples = vec![];
+        for file in csv_files(path)? {
+            samples.extend(read_mnist_csv(file)?);
+        }
+        Ok(DatasetBrowser::new(samples, page_size))
+    }
+
+    /// all samples of the dataset, whatever the filter is
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    /// the sample at a row of the whole dataset
+    pub fn sample(&self, row: usize) -> Option<&Sample> {
+        self.samples.get(row)
+    }
+
+    /// only show the samples of `label`, or every sample with `None`. Goes back to the first page
+    pub fn set_label_filter(&mut self, label: Option<u8>) {
+        self.label = label;
+        self.filter();
+    }
+
+    /// only show the given rows in the given order, e.g. the misclassified samples, or every row with `None`.
+    /// Rows which are not in the dataset are left out. Goes back to the first page
+    pub fn set_selection(&mut self, rows: Option<Vec<usize>>) {
+        self.selection = rows;
+        self.filter();
+    }
+
+    fn filter(&mut self) {
+        let rows: Box<dyn Iterator<Item = usize>> = match &self.selection {
+            Some(selection) => Box::new(selection.iter().copied().filter(|&row| row < self.samples.len())),
+            None => Box::new(0..self.samples.len()),
+        };
+        self.rows = rows.filter(|&row| self.label.is_none_or(|l| self.samples[row].label == l)).collect();
+        self.page = 0;
+    }
+
+    pub fn label_filter(&self) -> Option<u8> {
+        self.label
+    }
+
+    /// number of samples which match the filter
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// change the page size and stay on the page with the first sample of the current page
+    pub fn set_page_size(&mut self, page_size: usize) {
+        assert!(page_size > 0, "page size must be greater than 0");
+        let first = self.page * self.page_size;
+        self.page_size = page_size;
+        self.page = first / page_size;
+    }
+
+    /// number of pages, an empty dataset has one empty page
+    pub fn page_count(&self) -> usize {
+        self.rows.len().div_ceil(self.page_size).max(1)
+    }
+
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    /// go to a page, a page after the last one goes to the last page
+    pub fn set_page(&mut self, page: usize) {
+        self.page = page.min(self.page_count() - 1);
+    }
+
+    pub fn next_page(&mut self) {
+        self.set_page(self.page + 1);
+    }
+
+    pub fn previous_page(&mut self) {
+        self.page = self.page.saturating_sub(1);
+    }
+
+    /// the rows of the current page
+    pub fn page_rows(&self) -> &[usize] {
+        let start = (self.page * self.page_size).min(self.rows.len());
+        let end = (start + self.page_size).min(self.rows.len());
+        &self.rows[start..end]
+    }
+
+    /// go to the page which shows `row`, the label filter and the selection are cleared if they hide the row.
+    /// Returns `None` without changing the page if the dataset has no such row
+    pub fn jump_to(&mut self, row: usize) -> Option<&Sample> {
+        if row >= self.samples.len() {
+            return None;
+        }
+        if !self.rows.contains(&row) {
+            self.label = None;
+            self.set_selection(None);
+        }
+        let position = self.rows.iter().position(|&r| r == row).unwrap();
+        self.page = position / self.page_size;
+        self.samples.get(row)
+    }
+
+    /// number of samples of every label 0 to 9 in the whole dataset
+    pub fn label_counts(&self) -> [usize; 10] {
+        let mut counts = [0; 10];
+        for sample in &self.samples {
+            counts[sample.label as usize] += 1;
+        }
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pages_filter_and_jump() {
+        let samples: Vec<Sample> = (0..25).map(|i| Sample::from_mnist((i % 3) as u8, &[0; 784])).collect();
+        let mut browser = DatasetBrowser::new(samples, 4);
+        assert_eq!(browser.page_count(), 7);
+        browser.set_page(10);
+        assert_eq!(browser.page_rows(), &[24]);
+        browser.previous_page();
+        assert_eq!(browser.page_rows(), &[20, 21, 22, 23]);
+
+        // rows 2, 5, 8, ... have label 2
+        browser.set_label_filter(Some(2));
+        assert_eq!((browser.len(), browser.page_count(), browser.page()), (8, 2, 0));
+        browser.next_page();
+        assert_eq!(browser.page_rows(), &[14, 17, 20, 23]);
+        browser.next_page();
+        assert_eq!(browser.page(), 1);
+
+        // jumping to a row of another label clears the filter
+        assert_eq!(browser.jump_to(14).unwrap().label, 2);
+        assert_eq!((browser.label_filter(), browser.page()), (Some(2), 1));
+        assert_eq!(browser.jump_to(13).unwrap().label, 1);
+        assert_eq!((browser.label_filter(), browser.page()), (None, 3));
+        assert!(browser.jump_to(25).is_none());
+        assert_eq!(browser.label_counts()[..3], [9, 8, 8]);
+
+        // a selection keeps its order and is combined with the label filter
+        browser.set_selection(Some(vec![20, 3, 6, 99, 9]));
+        assert_eq!(browser.page_rows(), &[20, 3, 6, 9]);
+        browser.set_label_filter(Some(0));
+        assert_eq!(browser.page_rows(), &[3, 6, 9]);
+        browser.jump_to(7);
+        assert_eq!((browser.len(), browser.page()), (25, 1));
+    }
+}
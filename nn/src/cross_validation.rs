@@ -0,0 +1,153 @@
+//! stratified k-fold cross-validation, for datasets too small for a single noisy train/test split
+//!
+//! every fold is the test set once while a new network is trained on the other folds with a `Trainer`.
+
+use ndarray_rand::rand::seq::SliceRandom;
+use ndarray_rand::rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::evaluation::{evaluate, Evaluation};
+use crate::trainer::{TrainError, Trainer, TrainingData, TrainingReport};
+use crate::{NeuralNetwork, Sample};
+
+/// mean and sample standard deviation of a metric over the folds
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Summary {
+    pub mean: f32,
+    pub std: f32,
+}
+
+impl Summary {
+    pub fn new(values: &[f32]) -> Summary {
+        if values.is_empty() {
+            return Summary::default();
+        }
+        let mean = values.iter().sum::<f32>() / values.len() as f32;
+        let variance = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / (values.len().max(2) - 1) as f32;
+        Summary { mean, std: variance.sqrt() }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.4} ± {:.4}", self.mean, self.std)
+    }
+}
+
+/// the network trained without one fold and its evaluation on that fold
+#[derive(Debug, Clone)]
+pub struct FoldResult {
+    pub fold: usize,
+    /// rows of the samples of the fold
+    pub rows: Vec<usize>,
+    pub report: TrainingReport,
+    pub evaluation: Evaluation,
+    pub network: NeuralNetwork,
+}
+
+/// result of `cross_validate`
+#[derive(Debug, Clone)]
+pub struct CrossValidation {
+    pub folds: Vec<FoldResult>,
+    /// the test loss of the folds
+    pub loss: Summary,
+    /// the test accuracy of the folds
+    pub accuracy: Summary,
+}
+
+/// split the rows of the samples into `k` folds with the same share of every label, seeded
+///
+/// the rows of every label are shuffled and dealt to the folds in turn, so the fold sizes differ by one at most.
+pub fn stratified_folds(samples: &[Sample], k: usize, seed: u64) -> Result<Vec<Vec<usize>>, TrainError> {
+    if k < 2 {
+        return Err(TrainError::Invalid(format!("cross-validation needs at least 2 folds, not {}", k)));
+    }
+    let mut by_label: BTreeMap<u8, Vec<usize>> = BTreeMap::new();
+    for (row, sample) in samples.iter().enumerate() {
+        by_label.entry(sample.label).or_default().push(row);
+    }
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    let mut folds = vec![vec![]; k];
+    let mut next = 0;
+    for rows in by_label.values_mut() {
+        rows.shuffle(&mut rng);
+        for &row in rows.iter() {
+            folds[next % k].push(row);
+            next += 1;
+        }
+    }
+    for fold in &mut folds {
+        fold.sort_unstable();
+    }
+    Ok(folds)
+}
+
+/// k-fold cross-validation: for every fold a network from `build` is trained by `trainer` on the other folds and
+/// evaluated on the fold. The folds are stratified by label with `seed`, see `stratified_folds`
+pub fn cross_validate<F>(trainer: &Trainer, build: F, samples: &[Sample], k: usize, seed: u64) -> Result<CrossValidation, TrainError>
+where
+    F: Fn() -> NeuralNetwork,
+{
+    if k > samples.len() {
+        return Err(TrainError::Invalid(format!("there are {} samples, fewer than {} folds", samples.len(), k)));
+    }
+    let folds = stratified_folds(samples, k, seed)?;
+    let mut results = Vec::with_capacity(k);
+    for (fold, rows) in folds.into_iter().enumerate() {
+        let mut in_fold = vec![false; samples.len()];
+        for &row in &rows {
+            in_fold[row] = true;
+        }
+        let train: Vec<Sample> = samples.iter().zip(&in_fold).filter(|(_, &test)| !test).map(|(sample, _)| sample.clone()).collect();
+        let test: Vec<Sample> = rows.iter().map(|&row| samples[row].clone()).collect();
+
+        let mut network = build();
+        let report = trainer.fit(&mut network, &TrainingData::Samples(train))?;
+        let evaluation = evaluate(&network, &test);
+        results.push(FoldResult { fold, rows, report, evaluation, network });
+    }
+
+    let loss: Vec<f32> = results.iter().map(|result| result.evaluation.loss).collect();
+    let accuracy: Vec<f32> = results.iter().map(|result| result.evaluation.accuracy).collect();
+    Ok(CrossValidation { loss: Summary::new(&loss), accuracy: Summary::new(&accuracy), folds: results })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dataset::one_hot_target;
+
+    #[test]
+    fn folds_are_stratified_and_every_sample_is_tested_once() {
+        // 30 samples of label 0, 10 of label 1 and 5 of label 2
+        let samples: Vec<Sample> = (0..45).map(|i| {
+            let label = if i < 30 { 0 } else if i < 40 { 1 } else { 2 };
+            let x = [0.9, 0.5, 0.1][label as usize];
+            Sample { input: vec![x, 1.0 - x, 0.5], target: one_hot_target(label, 3), label }
+        }).collect();
+
+        let folds = stratified_folds(&samples, 5, 1).unwrap();
+        assert_eq!(folds, stratified_folds(&samples, 5, 1).unwrap());
+        let mut rows: Vec<usize> = folds.concat();
+        rows.sort_unstable();
+        assert_eq!(rows, (0..45).collect::<Vec<_>>());
+        for fold in &folds {
+            let count = |label| fold.iter().filter(|&&row| samples[row].label == label).count();
+            assert_eq!((fold.len(), count(0), count(1), count(2)), (9, 6, 2, 1));
+        }
+
+        let trainer = Trainer::new().epochs(30);
+        let result = cross_validate(&trainer, || NeuralNetwork::new_with_seed(3, 6, 3, 0.5, 2), &samples, 5, 1).unwrap();
+        assert_eq!(result.folds.len(), 5);
+        assert_eq!(result.folds.iter().map(|fold| fold.evaluation.count).sum::<usize>(), 45);
+        assert!(result.accuracy.mean > 0.9, "{}", result.accuracy);
+        for k in [1, 46] {
+            assert!(matches!(cross_validate(&trainer, || NeuralNetwork::new(3, 6, 3, 0.5), &samples, k, 1), Err(TrainError::Invalid(_))));
+        }
+        assert_eq!(Summary::new(&[1.0, 3.0]), Summary { mean: 2.0, std: 2f32.sqrt() });
+        assert_eq!(Summary::new(&[0.5, 0.5]).to_string(), "0.5000 ± 0.0000");
+    }
+}
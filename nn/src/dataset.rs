@@ -0,0 +1,140 @@
+use csv::{ReaderBuilder, StringRecord};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+
+/// number of pixels in a MNIST image, 28 * 28
+pub const MNIST_PIXELS: usize = 28 * 28;
+
+/// number of classes (digits 0 to 9) in the MNIST dataset
+pub const MNIST_CLASSES: usize = 10;
+
+/// one training or test sample of a dataset
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sample {
+    /// the scaled input values fed into the input layer
+    pub input: Vec<f32>,
+    /// the expected output values of the output layer
+    pub target: Vec<f32>,
+    /// the class (digit) of the sample
+    pub label: u8,
+}
+
+impl Sample {
+    /// create a sample from a MNIST label and its 28 * 28 gray values (0 to 255)
+    pub fn from_mnist(label: u8, pixels: &[u8]) -> Sample {
+        Sample {
+            input: scale_pixels(pixels),
+            target: one_hot_target(label, MNIST_CLASSES),
+            label,
+        }
+    }
+}
+
+/// scale the gray values (0 to 255) to range 0.01 to 1.00
+///
+/// zero is avoided because a zero input would kill the weight updates of its links.
+pub fn scale_pixels(pixels: &[u8]) -> Vec<f32> {
+    pixels.iter().map(|x| *x as f32 / 255.0 * 0.99 + 0.01).collect()
+}
+
+/// gray values (0 to 255) of scaled inputs, the inverse of `scale_pixels` to display a sample
+pub fn unscale_pixels(input: &[f32]) -> Vec<u8> {
+    input.iter().map(|x| ((x - 0.01) / 0.99 * 255.0).round().clamp(0.0, 255.0) as u8).collect()
+}
+
+/// target vector for `label`, 0.99 for the label node and 0.01 for the others
+///
+/// the sigmoid can never output 0 or 1, so these targets would saturate the network.
+pub fn one_hot_target(label: u8, classes: usize) -> Vec<f32> {
+    let mut target_list: Vec<f32> = vec![0.01; classes];
+    target_list[label as usize] = 0.99;
+    target_list
+}
+
+/// error while reading a dataset
+#[derive(Debug)]
+pub enum DatasetError {
+    /// the dataset file could not be opened
+    Io(PathBuf, io::Error),
+    /// the csv file is malformed
+    Csv(csv::Error),
+    /// a record could not be converted into a sample
+    InvalidRecord { row: usize, message: String },
+}
+
+impl fmt::Display for DatasetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DatasetError::Io(path, err) => write!(f, "can not read dataset file {:?}: {}", path, err),
+            DatasetError::Csv(err) => write!(f, "malformed csv: {}", err),
+            DatasetError::InvalidRecord { row, message } => write!(f, "invalid record at row {}: {}", row, message),
+        }
+    }
+}
+
+impl Error for DatasetError {}
+
+impl From<csv::Error> for DatasetError {
+    fn from(err: csv::Error) -> Self {
+        DatasetError::Csv(err)
+    }
+}
+
+/// convert a MNIST csv record (label, pix-11, pix-12, ... , pix-nn) into a sample
+pub fn parse_mnist_record(record: &StringRecord, row: usize) -> Result<Sample, DatasetError> {
+    let invalid = |message: String| DatasetError::InvalidRecord { row, message };
+    if record.len() != MNIST_PIXELS + 1 {
+        return Err(invalid(format!("expected {} values, found {}", MNIST_PIXELS + 1, record.len())));
+    }
+    let label = record[0].trim().parse::<u8>().map_err(|err| invalid(format!("label: {}", err)))?;
+    if label as usize >= MNIST_CLASSES {
+        return Err(invalid(format!("label {} is not a digit", label)));
+    }
+    let pixels = record.iter().skip(1)
+        .map(|value| value.trim().parse::<u8>())
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|err| invalid(format!("pixel: {}", err)))?;
+    Ok(Sample::from_mnist(label, &pixels))
+}
+
+/// open a MNIST csv file as a stream of samples, the file is read record by record
+pub fn mnist_csv_samples<P: AsRef<Path>>(path: P) -> Result<impl Iterator<Item = Result<Sample, DatasetError>>, DatasetError> {
+    let path = path.as_ref();
+    let file = File::open(path).map_err(|err| DatasetError::Io(path.to_path_buf(), err))?;
+    let reader = ReaderBuilder::new()
+        .has_headers(false)
+        .delimiter(b',')
+        .from_reader(BufReader::new(file));
+    Ok(reader.into_records().enumerate().map(|(row, result)| parse_mnist_record(&result?, row)))
+}
+
+/// read a whole MNIST csv file into memory
+pub fn read_mnist_csv<P: AsRef<Path>>(path: P) -> Result<Vec<Sample>, DatasetError> {
+    mnist_csv_samples(path)?.collect()
+}
+
+/// paths of the MNIST train shards `<dir>/file0.csv` to `<dir>/file<count - 1>.csv`
+pub fn mnist_shards<P: AsRef<Path>>(dir: P, count: usize) -> Vec<PathBuf> {
+    (0..count).map(|i| dir.as_ref().join(format!("file{}.csv", i))).collect()
+}
+
+/// the csv file itself, or the csv files of a directory sorted like file0.csv, file1.csv, ..., file10.csv
+pub fn csv_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>, DatasetError> {
+    let path = path.as_ref();
+    if !path.is_dir() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let entries = fs::read_dir(path).map_err(|err| DatasetError::Io(path.to_path_buf(), err))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| file.extension().is_some_and(|extension| extension == "csv"))
+        .collect();
+    files.sort_by_key(|file| {
+        let name = file.file_name().unwrap_or_default().to_string_lossy().into_owned();
+        (name.len(), name)
+    });
+    Ok(files)
+}
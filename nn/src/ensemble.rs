@@ -0,0 +1,234 @@
+//! ensembles of trained networks and weight averaging of compatible networks
+//!
+//! an `Ensemble` predicts like a single `NeuralNetwork`, so it can be evaluated and explained by any code
+//! which takes a predict function, e.g. `evaluation::evaluate_with`.
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+
+use crate::evaluation::argmax;
+use crate::persist::{self, PersistError};
+use crate::trainer::Checkpoint;
+use crate::NeuralNetwork;
+
+/// error while building an ensemble or averaging networks
+#[derive(Debug)]
+pub enum EnsembleError {
+    /// the networks do not have the same shape, or the weights do not fit the members
+    Incompatible(String),
+    /// a member could not be loaded
+    Persist(PersistError),
+}
+
+impl fmt::Display for EnsembleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EnsembleError::Incompatible(message) => write!(f, "incompatible networks: {}", message),
+            EnsembleError::Persist(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl Error for EnsembleError {}
+
+impl From<PersistError> for EnsembleError {
+    fn from(err: PersistError) -> Self {
+        EnsembleError::Persist(err)
+    }
+}
+
+/// how the outputs of the members are combined
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum Combination {
+    /// the mean of the outputs
+    #[default]
+    Average,
+    /// the share of the members whose highest output is the class, ties go to the lower class like `argmax`
+    MajorityVote,
+    /// the weighted mean of the outputs, one weight per member, e.g. their validation accuracy
+    Weighted(Vec<f32>),
+}
+
+impl std::str::FromStr for Combination {
+    type Err = String;
+
+    /// `average` or `vote`, a weighted combination needs the weights and can not be parsed
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "average" => Ok(Combination::Average),
+            "vote" => Ok(Combination::MajorityVote),
+            _ => Err(format!("unknown combination {}, expected average or vote", s)),
+        }
+    }
+}
+
+/// several networks with the same inputs and outputs, e.g. trained with different seeds or hidden sizes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(try_from = "SavedEnsemble")]
+pub struct Ensemble {
+    members: Vec<NeuralNetwork>,
+    combination: Combination,
+}
+
+// a saved ensemble before it is checked by `Ensemble::new` and `set_combination`
+#[derive(Deserialize)]
+struct SavedEnsemble {
+    members: Vec<NeuralNetwork>,
+    combination: Combination,
+}
+
+impl TryFrom<SavedEnsemble> for Ensemble {
+    type Error = EnsembleError;
+
+    fn try_from(saved: SavedEnsemble) -> Result<Self, Self::Error> {
+        let mut ensemble = Ensemble::new(saved.members)?;
+        ensemble.set_combination(saved.combination)?;
+        Ok(ensemble)
+    }
+}
+
+impl Ensemble {
+    /// an ensemble which averages the outputs of the members
+    pub fn new(members: Vec<NeuralNetwork>) -> Result<Ensemble, EnsembleError> {
+        let first = members.first().ok_or_else(|| EnsembleError::Incompatible("an ensemble needs members".to_string()))?;
+        if members.iter().any(|member| member.input_nodes != first.input_nodes || member.output_nodes != first.output_nodes) {
+            return Err(EnsembleError::Incompatible("the members do not have the same inputs and outputs".to_string()));
+        }
+        Ok(Ensemble { members, combination: Combination::Average })
+    }
+
+    /// load the members from model files written by `NeuralNetwork::save`
+    pub fn open<P: AsRef<Path>>(paths: &[P]) -> Result<Ensemble, EnsembleError> {
+        let members = paths.iter().map(NeuralNetwork::load).collect::<Result<Vec<_>, _>>()?;
+        Ensemble::new(members)
+    }
+
+    /// set how the outputs are combined, the weights of `Combination::Weighted` must fit the members
+    pub fn set_combination(&mut self, combination: Combination) -> Result<(), EnsembleError> {
+        if let Combination::Weighted(weights) = &combination {
+            let sum: f32 = weights.iter().sum();
+            if weights.len() != self.members.len() || weights.iter().any(|&w| w.is_nan() || w < 0.0) || !sum.is_finite() || sum <= 0.0 {
+                return Err(EnsembleError::Incompatible(format!("{} members need {} non negative weights", self.members.len(), self.members.len())));
+            }
+        }
+        self.combination = combination;
+        Ok(())
+    }
+
+    pub fn combination(&self) -> &Combination {
+        &self.combination
+    }
+
+    pub fn members(&self) -> &[NeuralNetwork] {
+        &self.members
+    }
+
+    /// query every member and combine their outputs
+    pub fn predict(&self, input_list: &[f32]) -> Vec<f32> {
+        let outputs: Vec<Vec<f32>> = self.members.iter().map(|member| member.predict(input_list)).collect();
+        let mut combined = vec![0.0; outputs[0].len()];
+        match &self.combination {
+            Combination::Average => {
+                for output_list in &outputs {
+                    for (c, o) in combined.iter_mut().zip(output_list) {
+                        *c += o / outputs.len() as f32;
+                    }
+                }
+            }
+            Combination::MajorityVote => {
+                for output_list in &outputs {
+                    combined[argmax(output_list)] += 1.0 / outputs.len() as f32;
+                }
+            }
+            Combination::Weighted(weights) => {
+                let sum: f32 = weights.iter().sum();
+                for (output_list, weight) in outputs.iter().zip(weights) {
+                    for (c, o) in combined.iter_mut().zip(output_list) {
+                        *c += o * weight / sum;
+                    }
+                }
+            }
+        }
+        combined
+    }
+
+    /// save the ensemble, the members and the combination, to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PersistError> {
+        persist::save_json(self, path)
+    }
+
+    /// load an ensemble written by `save`, the members and the combination are checked like by `new` and
+    /// `set_combination`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Ensemble, PersistError> {
+        persist::load_json(path)
+    }
+}
+
+/// a network whose weights are the mean of the weights of the networks, e.g. of the checkpoints of one run
+///
+/// the networks must have the same shape and activation. The result has the settings of the first network and
+/// no optimizer state.
+pub fn average_weights(networks: &[NeuralNetwork]) -> Result<NeuralNetwork, EnsembleError> {
+    let first = networks.first().ok_or_else(|| EnsembleError::Incompatible("there are no networks to average".to_string()))?;
+    if networks.iter().any(|network| network.weight_ih.dim() != first.weight_ih.dim()
+        || network.weight_ho.dim() != first.weight_ho.dim() || network.activation != first.activation)
+    {
+        return Err(EnsembleError::Incompatible("the networks do not have the same shape and activation".to_string()));
+    }
+    let mut average = first.clone();
+    let count = networks.len() as f32;
+    average.weight_ih = networks.iter().fold(first.weight_ih.mapv(|_| 0.0), |sum, network| sum + &network.weight_ih) / count;
+    average.weight_ho = networks.iter().fold(first.weight_ho.mapv(|_| 0.0), |sum, network| sum + &network.weight_ho) / count;
+    average.optimizer_state = None;
+    Ok(average)
+}
+
+/// load a model file, or the network of a checkpoint written by a `Trainer`
+pub fn load_network_or_checkpoint<P: AsRef<Path>>(path: P) -> Result<NeuralNetwork, PersistError> {
+    NeuralNetwork::load(&path).or_else(|err| Checkpoint::load(&path).map(|checkpoint| checkpoint.network).map_err(|_| err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_are_combined() {
+        let members: Vec<NeuralNetwork> = (0..3).map(|seed| NeuralNetwork::new_with_seed(4, 5, 3, 0.1, seed)).collect();
+        let input_list = [0.9, 0.1, 0.5, 0.3];
+        let outputs: Vec<Vec<f32>> = members.iter().map(|member| member.predict(&input_list)).collect();
+
+        let mut ensemble = Ensemble::new(members.clone()).unwrap();
+        let average = ensemble.predict(&input_list);
+        assert!((average[1] - (outputs[0][1] + outputs[1][1] + outputs[2][1]) / 3.0).abs() < 1e-6);
+
+        ensemble.set_combination(Combination::Weighted(vec![0.0, 2.0, 0.0])).unwrap();
+        assert_eq!(ensemble.predict(&input_list), outputs[1]);
+        assert!(ensemble.set_combination(Combination::Weighted(vec![1.0])).is_err());
+        assert!(ensemble.set_combination(Combination::Weighted(vec![f32::NAN, 1.0, 1.0])).is_err());
+        assert!(ensemble.set_combination(Combination::Weighted(vec![f32::NAN; 3])).is_err());
+
+        ensemble.set_combination(Combination::MajorityVote).unwrap();
+        let votes = ensemble.predict(&input_list);
+        assert!((votes.iter().sum::<f32>() - 1.0).abs() < 1e-6);
+        assert!((votes[argmax(&outputs[0])] * 3.0).round() >= 1.0);
+
+        assert!(Ensemble::new(vec![members[0].clone(), NeuralNetwork::new(4, 5, 2, 0.1)]).is_err());
+
+        let average = average_weights(&members).unwrap();
+        let expected = (&members[0].weight_ih + &members[1].weight_ih + &members[2].weight_ih) / 3.0;
+        assert!(average.weight_ih.iter().zip(&expected).all(|(a, e)| (a - e).abs() < 1e-6));
+        assert!(average_weights(&[members[0].clone(), NeuralNetwork::new(4, 6, 3, 0.1)]).is_err());
+
+        // a saved ensemble is checked like a new one
+        let mut saved = serde_json::to_value(&ensemble).unwrap();
+        assert!(serde_json::from_value::<Ensemble>(saved.clone()).is_ok());
+        saved["combination"] = serde_json::json!({"Weighted": [1.0, 2.0]});
+        assert!(serde_json::from_value::<Ensemble>(saved.clone()).is_err());
+        saved["members"] = serde_json::json!([]);
+        saved["combination"] = serde_json::json!("Average");
+        assert!(serde_json::from_value::<Ensemble>(saved).is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use serde::{Deserialize, Serialize};
+use std::io;
+
+use crate::dataset::Sample;
+use crate::model::Model;
+
+/// result of evaluating a neural network on a set of samples
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Evaluation {
+    /// mean squared error between the targets and the outputs
+    pub loss: f32,
+    /// ratio of the samples whose highest output is their label
+    pub accuracy: f32,
+    /// number of evaluated samples
+    pub count: usize,
+}
+
+/// a sample whose highest output is not its label
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Misclassification {
+    /// position of the sample in the evaluated samples, the row of the dataset browser
+    pub row: usize,
+    /// the true label of the sample
+    pub label: u8,
+    /// the class with the highest output
+    pub predicted: u8,
+    /// the highest output, close to 1 for a confident mistake
+    pub confidence: f32,
+}
+
+/// order of a list of misclassifications
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorOrder {
+    /// the most confident mistakes first, these are the worst errors
+    #[default]
+    Confidence,
+    Row,
+    /// by true label, then by predicted label
+    Label,
+    /// by predicted label, then by true label
+    Predicted,
+}
+
+impl std::str::FromStr for ErrorOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "confidence" => Ok(ErrorOrder::Confidence),
+            "row" => Ok(ErrorOrder::Row),
+            "label" => Ok(ErrorOrder::Label),
+            "predicted" => Ok(ErrorOrder::Predicted),
+            _ => Err(format!("unknown order {}, expected confidence, row, label or predicted", s)),
+        }
+    }
+}
+
+/// sort misclassifications, ties are in row order
+pub fn sort_misclassifications(errors: &mut [Misclassification], order: ErrorOrder) {
+    errors.sort_by(|a, b| {
+        let ordering = match order {
+            ErrorOrder::Confidence => b.confidence.total_cmp(&a.confidence),
+            ErrorOrder::Row => a.row.cmp(&b.row),
+            ErrorOrder::Label => (a.label, a.predicted).cmp(&(b.label, b.predicted)),
+            ErrorOrder::Predicted => (a.predicted, a.label).cmp(&(b.predicted, b.label)),
+        };
+        ordering.then(a.row.cmp(&b.row))
+    });
+}
+
+/// write misclassifications as a csv file with a `row,label,predicted,confidence` header
+pub fn write_misclassifications<W: io::Write>(writer: W, errors: &[Misclassification]) -> Result<(), csv::Error> {
+    let mut writer = csv::Writer::from_writer(writer);
+    for error in errors {
+        writer.serialize(error)?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// read misclassifications written by `write_misclassifications`, e.g. to review them in the dataset browser
+pub fn read_misclassifications<R: io::Read>(reader: R) -> Result<Vec<Misclassification>, csv::Error> {
+    csv::Reader::from_reader(reader).deserialize().collect()
+}
+
+/// index of the max value in the output list, which is the predicted class
+pub fn argmax(output_list: &[f32]) -> usize {
+    let mut max_value = f32::NEG_INFINITY;
+    let mut max_index = 0;
+    for (index, value) in output_list.iter().enumerate() {
+        if *value > max_value {
+            max_value = *value;
+            max_index = index;
+        }
+    }
+    max_index
+}
+
+/// mean squared error of one output list, the loss minimised by `NeuralNetwork::train`
+pub fn squared_error(output_list: &[f32], target_list: &[f32]) -> f32 {
+    let sum: f32 = output_list.iter().zip(target_list).map(|(o, t)| (t - o) * (t - o)).sum();
+    sum / output_list.len() as f32
+}
+
+/// evaluate the loss and the accuracy of a model on the samples, e.g. a network, an ensemble or an `AnyModel`
+pub fn evaluate<M: Model + ?Sized>(model: &M, samples: &[Sample]) -> Evaluation {
+    evaluate_with(|input_list| model.predict(input_list), samples)
+}
+
+/// evaluate any model given by its predict function, e.g. a quantised network
+pub fn evaluate_with<F: Fn(&[f32]) -> Vec<f32>>(predict: F, samples: &[Sample]) -> Evaluation {
+    evaluate_with_errors(predict, samples).0
+}
+
+/// evaluate like `evaluate_with` and collect every misclassified sample in row order
+pub fn evaluate_with_errors<F: Fn(&[f32]) -> Vec<f32>>(predict: F, samples: &[Sample]) -> (Evaluation, Vec<Misclassification>) {
+    if samples.is_empty() {
+        return (Evaluation::default(), vec![]);
+    }
+    let mut loss = 0.0;
+    let mut errors = vec![];
+    for (row, sample) in samples.iter().enumerate() {
+        let output_list = predict(&sample.input);
+        loss += squared_error(&output_list, &sample.target);
+        let predicted = argmax(&output_list);
+        if predicted != sample.label as usize {
+            errors.push(Misclassification { row, label: sample.label, predicted: predicted as u8, confidence: output_list[predicted] });
+        }
+    }
+    let evaluation = Evaluation {
+        loss: loss / samples.len() as f32,
+        accuracy: (samples.len() - errors.len()) as f32 / samples.len() as f32,
+        count: samples.len(),
+    };
+    (evaluation, errors)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn misclassifications_are_collected_sorted_and_exported() {
+        let samples: Vec<Sample> = [0, 1, 2, 1].iter().map(|&label| Sample::from_mnist(label, &[0; 784])).collect();
+        // predicts class 1 for every sample, the samples are evaluated in row order
+        let outputs = [vec![0.1, 0.6, 0.0], vec![0.1, 0.7, 0.0], vec![0.1, 0.9, 0.0], vec![0.2, 0.8, 0.0]];
+        let calls = Cell::new(0);
+        let (evaluation, mut errors) = evaluate_with_errors(|_| {
+            calls.set(calls.get() + 1);
+            outputs[calls.get() - 1].clone()
+        }, &samples);
+        assert_eq!(evaluation.accuracy, 0.5);
+        assert_eq!(errors, vec![
+            Misclassification { row: 0, label: 0, predicted: 1, confidence: 0.6 },
+            Misclassification { row: 2, label: 2, predicted: 1, confidence: 0.9 },
+        ]);
+
+        sort_misclassifications(&mut errors, ErrorOrder::Confidence);
+        assert_eq!(errors[0].row, 2);
+        sort_misclassifications(&mut errors, "label".parse().unwrap());
+        assert_eq!(errors[0].row, 0);
+
+        let mut csv = vec![];
+        write_misclassifications(&mut csv, &errors).unwrap();
+        assert!(csv.starts_with(b"row,label,predicted,confidence\n0,0,1,0.6\n"));
+        assert_eq!(read_misclassifications(&csv[..]).unwrap(), errors);
+    }
+}
@@ -0,0 +1,95 @@
+//! explanation maps of a prediction, which input pixels made the network choose a class
+//!
+//! both maps have one non negative value per input pixel, `visualize::overlay` draws them over the input image.
+
+use crate::NeuralNetwork;
+
+/// absolute input gradient of the output of `class`, see `NeuralNetwork::input_gradient`
+pub fn saliency_map(network: &NeuralNetwork, input_list: &[f32], class: usize) -> Vec<f32> {
+    network.input_gradient(input_list, class).iter().map(|g| g.abs()).collect()
+}
+
+/// settings of `occlusion_map`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Occlusion {
+    /// width and height of the square patch
+    pub patch: usize,
+    /// distance between two patch positions
+    pub stride: usize,
+    /// the value of the occluded inputs, 0.01 is a black pixel of a scaled MNIST image
+    pub fill: f32,
+}
+
+impl Default for Occlusion {
+    fn default() -> Self {
+        Occlusion { patch: 4, stride: 2, fill: 0.01 }
+    }
+}
+
+/// occlusion sensitivity of a `width`*`height` image: a patch slides over the input and every pixel gets the mean
+/// drop of the output of `class` over the patches which cover it. Pixels whose occlusion raises the output get 0
+///
+/// any model can be explained through its predict function, like in `evaluation::evaluate_with`.
+pub fn occlusion_map<F>(predict: F, input_list: &[f32], (width, height): (usize, usize), class: usize, occlusion: Occlusion) -> Vec<f32>
+where
+    F: Fn(&[f32]) -> Vec<f32>,
+{
+    assert_eq!(input_list.len(), width * height, "input list length does not match the image size");
+    assert!(occlusion.patch > 0 && occlusion.stride > 0, "patch and stride must be greater than 0");
+    let patch = occlusion.patch.min(width).min(height);
+    let output_list = predict(input_list);
+    assert!(class < output_list.len(), "class {} is not one of the {} outputs", class, output_list.len());
+    let base = output_list[class];
+    let mut drops = vec![0.0; input_list.len()];
+    let mut counts = vec![0usize; input_list.len()];
+    let mut occluded = input_list.to_vec();
+    for y0 in positions(height, patch, occlusion.stride) {
+        for x0 in positions(width, patch, occlusion.stride) {
+            let pixels: Vec<usize> = (y0..y0 + patch).flat_map(|y| (x0..x0 + patch).map(move |x| y * width + x)).collect();
+            for &i in &pixels {
+                occluded[i] = occlusion.fill;
+            }
+            let drop = base - predict(&occluded)[class];
+            for &i in &pixels {
+                drops[i] += drop;
+                counts[i] += 1;
+                occluded[i] = input_list[i];
+            }
+        }
+    }
+    drops.iter().zip(&counts).map(|(&drop, &count)| if count > 0 { (drop / count as f32).max(0.0) } else { 0.0 }).collect()
+}
+
+// the patch positions along one axis, the last one always touches the edge so every pixel is covered
+fn positions(size: usize, patch: usize, stride: usize) -> Vec<usize> {
+    let mut positions: Vec<usize> = (0..=size - patch).step_by(stride).collect();
+    if positions.last() != Some(&(size - patch)) {
+        positions.push(size - patch);
+    }
+    positions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn occlusion_finds_the_deciding_pixels() {
+        // class 0 is the mean of the pixels (1, 1) and (2, 1) of a 5*4 image
+        let predict = |input: &[f32]| vec![(input[6] + input[7]) / 2.0];
+        let map = occlusion_map(predict, &[1.0; 20], (5, 4), 0, Occlusion { patch: 2, stride: 2, fill: 0.0 });
+        // columns 0, 2 and 3 are patch positions, rows 0 and 2
+        assert_eq!(positions(5, 2, 2), vec![0, 2, 3]);
+        assert_eq!(map[6], 0.5);
+        assert_eq!(map[7], 0.5);
+        assert_eq!(map[8], 0.25);
+        assert_eq!(map[0], 0.5);
+        assert!(map[10..].iter().all(|&v| v == 0.0));
+
+        let nn = NeuralNetwork::new_with_seed(20, 6, 3, 0.1, 1);
+        let input_list = [0.5; 20];
+        assert_eq!(saliency_map(&nn, &input_list, 2).len(), 20);
+        let map = occlusion_map(|input| nn.predict(input), &input_list, (5, 4), 1, Occlusion::default());
+        assert!(map.iter().all(|&v| v >= 0.0));
+    }
+}
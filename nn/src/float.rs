@@ -0,0 +1,40 @@
+//! the floating point types a `NeuralNetwork` can be built with
+
+use ndarray::{LinalgScalar, ScalarOperand};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::{Debug, Display};
+use std::iter::Sum;
+use std::ops::{AddAssign, MulAssign, SubAssign};
+
+/// a float type of the weights, the inputs and the outputs of a network, implemented for `f32` and `f64`
+pub trait Float:
+    num_traits::Float + LinalgScalar + ScalarOperand + AddAssign + SubAssign + MulAssign + Sum
+    + Debug + Display + Default + Send + Sync + Serialize + DeserializeOwned + 'static
+{
+    /// convert a hyperparameter or an `f32` input, e.g. the pixels of a `Sample`
+    fn from_f32(value: f32) -> Self;
+
+    /// convert to `f32`, e.g. for the `Evaluation` of a network
+    fn as_f32(self) -> f32;
+}
+
+impl Float for f32 {
+    fn from_f32(value: f32) -> Self {
+        value
+    }
+
+    fn as_f32(self) -> f32 {
+        self
+    }
+}
+
+impl Float for f64 {
+    fn from_f32(value: f32) -> Self {
+        value as f64
+    }
+
+    fn as_f32(self) -> f32 {
+        self as f32
+    }
+}
@@ -0,0 +1,214 @@
+//! finite difference checks of the backward passes, recorded by the autograd tape or hand written, and property
+//! tests of training
+
+use ndarray::{Array2, Axis};
+use ndarray_rand::rand::SeedableRng;
+use proptest::prelude::*;
+use rand_chacha::ChaCha8Rng;
+
+use crate::autograd::{check_gradients, numerical_gradient};
+use crate::layers::{ActivationLayer, AnyLayer, AvgPool2d, BatchNorm, Conv2d, Dense, Flatten, LayerNorm, Layer, MaxPool2d, Reshape, Tensor};
+use crate::sequential::{Loss, Sequential};
+use crate::{Activation, Mode, NeuralNetwork, Sample};
+
+const EPSILON: f32 = 1e-2;
+const TOLERANCE: f32 = 1e-2;
+
+// deterministic test values which are not too close to each other or to zero
+fn tensor(shape: &[usize], offset: f32) -> Tensor {
+    let len = shape.iter().product();
+    Tensor::from_shape_vec(shape, (0..len).map(|i| (i as f32 * 0.77 + offset).sin() + 0.05).collect()).unwrap()
+}
+
+fn assert_close(analytic: &Tensor, numerical: &Tensor, what: &str) {
+    assert_eq!(analytic.shape(), numerical.shape(), "{}", what);
+    for (a, n) in analytic.iter().zip(numerical) {
+        assert!((a - n).abs() / a.abs().max(n.abs()).max(1.0) < TOLERANCE, "{}: {} != {}\n{}\n{}", what, a, n, analytic, numerical);
+    }
+}
+
+// check the input and the parameter gradients of the loss sum(output * weights)
+fn check_layer(layer: impl Into<AnyLayer>, input: Tensor) {
+    let mut layer = layer.into();
+    let what = format!("{:?}", layer).chars().take(40).collect::<String>();
+    let output = layer.forward(&input, Mode::Train);
+    let weights = tensor(output.shape(), 1.0);
+    let loss = |layer: &AnyLayer, input: &Tensor| (layer.forward(input, Mode::Train) * &weights).sum();
+
+    let grad_input = layer.backward(&input, &output, &weights);
+    let numerical = numerical_gradient(|x| loss(&layer, &x[0]), std::slice::from_ref(&input), EPSILON);
+    assert_close(&grad_input, &numerical[0], &what);
+
+    let count = layer.parameters().len();
+    for p in 0..count {
+        let analytic = layer.parameters()[p].1.clone();
+        let parameter = layer.parameters()[p].0.clone();
+        let numerical = numerical_gradient(|values| {
+            let mut shifted = layer.clone();
+            shifted.parameters()[p].0.assign(&values[0]);
+            loss(&shifted, &input)
+        }, &[parameter], EPSILON);
+        assert_close(&analytic, &numerical[0], &format!("{} parameter {}", what, p));
+    }
+}
+
+#[test]
+fn train_matches_finite_differences() {
+    for activation in [Activation::Sigmoid, Activation::Relu, Activation::Tanh] {
+        let mut nn = NeuralNetwork::new_with_seed(4, 5, 3, 0.1, 7);
+        nn.set_activation(activation);
+        let input = [0.9, -0.3, 0.5, 0.1];
+        let target = [0.99, 0.01, 0.5];
+        // `train` moves the weights along the negative gradient of 0.5 * sum((target - output)^2)
+        let loss = |nn: &NeuralNetwork| nn.predict(&input).iter().zip(&target).map(|(o, t)| 0.5 * (t - o) * (t - o)).sum::<f32>();
+
+        let gradients = nn.gradients(&input, &target, None);
+        let weights = [nn.weight_ih.clone().into_dyn(), nn.weight_ho.clone().into_dyn()];
+        let numerical = numerical_gradient(|weights| {
+            let mut shifted = nn.clone();
+            shifted.weight_ih = weights[0].clone().into_dimensionality().unwrap();
+            shifted.weight_ho = weights[1].clone().into_dimensionality().unwrap();
+            loss(&shifted)
+        }, &weights, 1e-3);
+        assert_close(&(-gradients.ih).into_dyn(), &numerical[0], &format!("{:?} weight_ih", activation));
+        assert_close(&(-gradients.ho).into_dyn(), &numerical[1], &format!("{:?} weight_ho", activation));
+    }
+}
+
+#[test]
+fn input_gradient_matches_finite_differences() {
+    for activation in [Activation::Sigmoid, Activation::Relu, Activation::Tanh] {
+        let mut nn = NeuralNetwork::new_with_seed(4, 5, 3, 0.1, 8);
+        nn.set_activation(activation);
+        let input = tensor(&[4], 0.2);
+        for class in 0..3 {
+            let analytic = Tensor::from_shape_vec(vec![4], nn.input_gradient(input.as_slice().unwrap(), class)).unwrap();
+            let numerical = numerical_gradient(|x| nn.predict(x[0].as_slice().unwrap())[class], std::slice::from_ref(&input), 1e-3);
+            assert_close(&analytic, &numerical[0], &format!("{:?} class {}", activation, class));
+        }
+    }
+}
+
+#[test]
+fn activations_match_finite_differences() {
+    let weights = tensor(&[3, 5], 1.0);
+    for activation in [Activation::Sigmoid, Activation::Relu, Activation::Tanh, Activation::Softmax] {
+        let error = check_gradients(|tape, v| (v[0].activation(activation) * tape.var(weights.clone())).sum(), &[tensor(&[3, 5], 0.3)], EPSILON);
+        assert!(error < TOLERANCE, "{:?}: gradient error {}", activation, error);
+        check_layer(ActivationLayer::new(activation), tensor(&[3, 5], 0.3));
+    }
+}
+
+#[test]
+fn losses_match_finite_differences() {
+    let target = Array2::from_shape_fn((3, 4), |(i, j)| if j == i { 1.0 } else { 0.0 });
+    let output = tensor(&[3, 4], 0.0).mapv(|v| 0.5 + 0.4 * v).into_dimensionality().unwrap();
+    for loss in [Loss::MeanSquaredError, Loss::CrossEntropy] {
+        let error = check_gradients(|tape, v| loss.graph(v[0], tape.var(target.clone().into_dyn())), &[output.clone().into_dyn()], 1e-3);
+        assert!(error < TOLERANCE, "{:?}: gradient error {}", loss, error);
+        let numerical = numerical_gradient(|x| {
+            loss.loss(&x[0].clone().into_dimensionality().unwrap(), &target)
+        }, &[Tensor::from(output.clone().into_dyn())], 1e-3);
+        assert_close(&loss.gradient(&output, &target).into_dyn(), &numerical[0], &format!("{:?}", loss));
+    }
+}
+
+#[test]
+fn layers_match_finite_differences() {
+    let mut rng = ChaCha8Rng::seed_from_u64(5);
+    let image = tensor(&[2, 2, 6, 6], 0.0);
+    check_layer(Dense::new(4, 3, &mut rng), tensor(&[2, 4], 0.0));
+    check_layer(Conv2d::new(2, 3, 3, &mut rng), image.clone());
+    check_layer(Conv2d::new(2, 2, 3, &mut rng).stride(2).padding(1), image.clone());
+    check_layer(MaxPool2d::new(2), image.clone());
+    check_layer(AvgPool2d::new(2), image.clone());
+    check_layer(Flatten::new(), image.clone());
+    check_layer(Reshape::new(&[6, 12]), image.clone());
+    check_layer(BatchNorm::new(2), image.clone());
+    check_layer(BatchNorm::new(4), tensor(&[3, 4], 0.0));
+    check_layer(LayerNorm::new(72), image);
+}
+
+#[test]
+fn sequential_matches_finite_differences() {
+    let mut rng = ChaCha8Rng::seed_from_u64(6);
+    let model = Sequential::new(&[16], Loss::CrossEntropy, 0.0)
+        .push(Reshape::new(&[1, 4, 4]))
+        .push(Conv2d::new(1, 2, 3, &mut rng).padding(1))
+        .push(ActivationLayer::new(Activation::Tanh))
+        .push(Flatten::new())
+        .push(Dense::new(32, 3, &mut rng))
+        .push(ActivationLayer::new(Activation::Softmax));
+    let samples: Vec<Sample> = (0..2)
+        .map(|label| {
+            let mut target = vec![0.0; 3];
+            target[label] = 1.0;
+            Sample { input: tensor(&[16], label as f32).into_raw_vec(), target, label: label as u8 }
+        })
+        .collect();
+
+    // with a learning rate of 1 the change of the first dense weights is their negative gradient
+    let dense_weights = |model: &Sequential| match &model.layers()[4] {
+        AnyLayer::Dense(dense) => dense.weights().clone(),
+        _ => unreachable!(),
+    };
+    let mut trained = model.clone();
+    trained.set_learning_rate(1.0);
+    trained.train_batch(&samples);
+    let analytic = dense_weights(&model) - dense_weights(&trained);
+
+    let numerical = numerical_gradient(|weights| {
+        let mut shifted = model.clone();
+        if let AnyLayer::Dense(dense) = &mut shifted.layers_mut()[4] {
+            dense.parameters()[0].0.assign(&weights[0]);
+        }
+        let input = Tensor::from_shape_vec(vec![2, 16], samples.iter().flat_map(|s| s.input.clone()).collect()).unwrap();
+        let output = shifted.forward(&input).into_dimensionality().unwrap();
+        let target = Array2::from_shape_fn((2, 3), |(i, j)| samples[i].target[j]);
+        Loss::CrossEntropy.loss(&output, &target)
+    }, &[dense_weights(&model)], 1e-3);
+    assert_close(&analytic, &numerical[0], "sequential");
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(32))]
+
+    #[test]
+    fn one_step_decreases_the_loss(
+        inputs in 1usize..8, hidden in 1usize..8, outputs in 1usize..5, seed in any::<u64>(),
+        values in proptest::collection::vec(-1.0f32..1.0, 16),
+    ) {
+        let mut nn = NeuralNetwork::new_with_seed(inputs as i32, hidden as i32, outputs as i32, 0.01, seed);
+        let input: Vec<f32> = values.iter().cycle().take(inputs).copied().collect();
+        let target: Vec<f32> = values.iter().rev().map(|v| 0.5 + 0.49 * v).cycle().take(outputs).collect();
+        let before = nn.train(&input, &target);
+        let after = nn.gradients(&input, &target, None).loss;
+        prop_assert!(after <= before + 1e-7, "{} > {}", after, before);
+    }
+
+    #[test]
+    fn forward_shapes_hold(
+        batch in 1usize..4, channels in 1usize..3, size in 4usize..9, filters in 1usize..4,
+        kernel in 1usize..4, padding in 0usize..2, hidden in 1usize..6, seed in any::<u64>(),
+    ) {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let features = channels * size * size;
+        let model = Sequential::new(&[features], Loss::MeanSquaredError, 0.1)
+            .push(Reshape::new(&[channels, size, size]))
+            .push(Conv2d::new(channels, filters, kernel, &mut rng).padding(padding))
+            .push(BatchNorm::new(filters))
+            .push(MaxPool2d::new(2))
+            .push(Flatten::new());
+        let flat = model.output_shape()[0];
+        let model = model
+            .push(Dense::new(flat, hidden, &mut rng))
+            .push(LayerNorm::new(hidden))
+            .push(ActivationLayer::new(Activation::Relu));
+
+        let output = model.forward(&tensor(&[batch, features], 0.0));
+        let mut expected = vec![batch];
+        expected.extend(model.output_shape());
+        prop_assert_eq!(output.shape(), &expected[..]);
+        prop_assert_eq!(output.len_of(Axis(0)), batch);
+    }
+}
@@ -0,0 +1,59 @@
+use ndarray::Axis;
+use serde::{Deserialize, Serialize};
+
+use super::{Layer, Tensor};
+use crate::autograd::{Tape, Var};
+use crate::{Activation, Mode};
+
+/// applies an activation function to every sample of a batch
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ActivationLayer {
+    activation: Activation,
+}
+
+impl ActivationLayer {
+    pub fn new(activation: Activation) -> ActivationLayer {
+        ActivationLayer { activation }
+    }
+
+    pub fn activation(&self) -> Activation {
+        self.activation
+    }
+
+    // the forward pass recorded on a tape for the backward pass, `forward` calculates the same without a tape
+    fn graph<'t>(&self, x: Var<'t>) -> Var<'t> {
+        // softmax works on all values of a sample, so every sample is flattened into one row
+        let shape = x.shape();
+        let features = shape[1..].iter().product();
+        x.reshape(&[shape[0], features]).activation(self.activation).reshape(&shape)
+    }
+}
+
+impl Layer for ActivationLayer {
+    fn forward(&self, input: &Tensor, _mode: Mode) -> Tensor {
+        let activation = self.activation;
+        if activation != Activation::Softmax {
+            return input.mapv(|x| activation.value(x));
+        }
+        let batch = input.len_of(Axis(0));
+        let mut rows = input.as_standard_layout().into_owned().into_shape((batch, input.len() / batch.max(1))).unwrap();
+        for mut row in rows.rows_mut() {
+            // subtract the max value so exp can not overflow
+            let max = row.fold(f32::NEG_INFINITY, |a, &b| a.max(b));
+            row.mapv_inplace(|x| (x - max).exp());
+            let sum = row.sum();
+            row.mapv_inplace(|x| x / sum);
+        }
+        rows.into_shape(input.shape()).unwrap()
+    }
+
+    fn backward(&mut self, input: &Tensor, _output: &Tensor, grad_output: &Tensor) -> Tensor {
+        let tape = Tape::new();
+        let x = tape.var(input.clone());
+        self.graph(x).backward_with(grad_output.clone()).wrt(x)
+    }
+
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        input_shape.to_vec()
+    }
+}
@@ -0,0 +1,296 @@
+use ndarray::{s, Array, Array2, Array3, Array4, ArrayView3, ArrayView4, Axis, Ix1, Ix2, Ix4};
+use ndarray_rand::rand::Rng;
+use ndarray_rand::rand_distr::Normal;
+use ndarray_rand::RandomExt;
+use serde::{Deserialize, Serialize};
+
+use super::{ensure_gradient, Layer, Tensor};
+use crate::autograd::{Tape, Var};
+use crate::Mode;
+
+/// 2d convolution over `[batch, channels, height, width]` images
+///
+/// the convolution is calculated as a matrix product of the weights and the image patches (im2col).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Conv2d {
+    in_channels: usize,
+    out_channels: usize,
+    kernel: usize,
+    stride: usize,
+    padding: usize,
+    /// out_channels * (in_channels * kernel * kernel) weights matrix
+    weights: Tensor,
+    bias: Tensor,
+    #[serde(skip)]
+    grad_weights: Tensor,
+    #[serde(skip)]
+    grad_bias: Tensor,
+}
+
+impl Conv2d {
+    /// create a convolution with a square kernel, stride 1 and no padding.
+    /// The weights are normal distributed with standard deviation sqrt(2 / fan_in) (He initialisation)
+    pub fn new<R: Rng>(in_channels: usize, out_channels: usize, kernel: usize, rng: &mut R) -> Conv2d {
+        let fan_in = in_channels * kernel * kernel;
+        let weights = Array::random_using((out_channels, fan_in), Normal::new(0.0, (2.0 / fan_in as f32).sqrt()).unwrap(), rng);
+        Conv2d {
+            in_channels,
+            out_channels,
+            kernel,
+            stride: 1,
+            padding: 0,
+            grad_weights: Array::zeros(weights.raw_dim()).into_dyn(),
+            weights: weights.into_dyn(),
+            bias: Array::zeros(out_channels).into_dyn(),
+            grad_bias: Array::zeros(out_channels).into_dyn(),
+        }
+    }
+
+    /// step between two kernel positions
+    pub fn stride(mut self, stride: usize) -> Conv2d {
+        assert!(stride > 0, "stride must be greater than 0");
+        self.stride = stride;
+        self
+    }
+
+    /// number of zero pixels added around the image
+    pub fn padding(mut self, padding: usize) -> Conv2d {
+        self.padding = padding;
+        self
+    }
+
+    /// the weights matrix, one row of in_channels * kernel * kernel values per output channel
+    pub fn weights(&self) -> &Tensor {
+        &self.weights
+    }
+
+    fn output_size(&self, height: usize, width: usize) -> (usize, usize) {
+        (
+            output_size("Conv2d", height, self.kernel, self.stride, self.padding),
+            output_size("Conv2d", width, self.kernel, self.stride, self.padding),
+        )
+    }
+
+    fn im2col(&self, image: ArrayView3<f32>) -> Array2<f32> {
+        im2col(image, self.kernel, self.stride, self.padding)
+    }
+
+    // the forward pass recorded on a tape for the backward pass, `forward` calculates the same without a tape
+    fn graph<'t>(&self, x: Var<'t>, weights: Var<'t>, bias: Var<'t>) -> Var<'t> {
+        let weights = weights.reshape(&[self.out_channels, self.in_channels, self.kernel, self.kernel]);
+        x.conv2d(weights, self.stride, self.padding) + bias.reshape(&[self.out_channels, 1, 1])
+    }
+}
+
+// number of kernel positions along one axis of `size` pixels, `layer` names the layer when the kernel does not fit
+pub(crate) fn output_size(layer: &str, size: usize, kernel: usize, stride: usize, padding: usize) -> usize {
+    assert!(kernel <= size + 2 * padding,
+        "{}: the kernel of {} pixels is larger than the input of {} pixels with a padding of {}", layer, kernel, size, padding);
+    (size + 2 * padding - kernel) / stride + 1
+}
+
+// (channels * kernel * kernel) * (out_height * out_width) matrix of the image patches
+pub(crate) fn im2col(image: ArrayView3<f32>, kernel: usize, stride: usize, padding: usize) -> Array2<f32> {
+    let (channels, height, width) = image.dim();
+    let (out_height, out_width) = (output_size("Conv2d", height, kernel, stride, padding), output_size("Conv2d", width, kernel, stride, padding));
+    let k = kernel;
+    let mut columns = Array2::zeros((channels * k * k, out_height * out_width));
+    for c in 0..channels {
+        for ky in 0..k {
+            for kx in 0..k {
+                let row = (c * k + ky) * k + kx;
+                for oy in 0..out_height {
+                    let y = (oy * stride + ky) as isize - padding as isize;
+                    if y < 0 || y >= height as isize {
+                        continue;
+                    }
+                    for ox in 0..out_width {
+                        let x = (ox * stride + kx) as isize - padding as isize;
+                        if x >= 0 && x < width as isize {
+                            columns[[row, oy * out_width + ox]] = image[[c, y as usize, x as usize]];
+                        }
+                    }
+                }
+            }
+        }
+    }
+    columns
+}
+
+// add the patch gradients back to the image positions they were taken from
+pub(crate) fn col2im(columns: &Array2<f32>, shape: (usize, usize, usize), kernel: usize, stride: usize, padding: usize) -> Array3<f32> {
+    let (channels, height, width) = shape;
+    let (out_height, out_width) = (output_size("Conv2d", height, kernel, stride, padding), output_size("Conv2d", width, kernel, stride, padding));
+    let k = kernel;
+    let mut image = Array3::zeros(shape);
+    for c in 0..channels {
+        for ky in 0..k {
+            for kx in 0..k {
+                let row = (c * k + ky) * k + kx;
+                for oy in 0..out_height {
+                    let y = (oy * stride + ky) as isize - padding as isize;
+                    if y < 0 || y >= height as isize {
+                        continue;
+                    }
+                    for ox in 0..out_width {
+                        let x = (ox * stride + kx) as isize - padding as isize;
+                        if x >= 0 && x < width as isize {
+                            image[[c, y as usize, x as usize]] += columns[[row, oy * out_width + ox]];
+                        }
+                    }
+                }
+            }
+        }
+    }
+    image
+}
+
+impl Layer for Conv2d {
+    fn forward(&self, input: &Tensor, _mode: Mode) -> Tensor {
+        let x = input.view().into_dimensionality::<Ix4>().expect("conv2d input must be [batch, channels, height, width]");
+        let (batch, channels, height, width) = x.dim();
+        assert_eq!(channels, self.in_channels, "conv2d input channels do not match");
+        let (out_height, out_width) = self.output_size(height, width);
+        let weights = self.weights.view().into_dimensionality::<Ix2>().unwrap();
+        let bias = self.bias.view().into_dimensionality::<Ix1>().unwrap().insert_axis(Axis(1));
+
+        let mut output = Array4::zeros((batch, self.out_channels, out_height, out_width));
+        for (n, image) in x.axis_iter(Axis(0)).enumerate() {
+            let result = weights.dot(&self.im2col(image)) + bias;
+            output.slice_mut(s![n, .., .., ..])
+                .assign(&result.into_shape((self.out_channels, out_height, out_width)).unwrap());
+        }
+        output.into_dyn()
+    }
+
+    fn backward(&mut self, input: &Tensor, _output: &Tensor, grad_output: &Tensor) -> Tensor {
+        let tape = Tape::new();
+        let (x, weights, bias) = (tape.var(input.clone()), tape.var(self.weights.clone()), tape.var(self.bias.clone()));
+        let gradients = self.graph(x, weights, bias).backward_with(grad_output.clone());
+        ensure_gradient(&mut self.grad_weights, &self.weights);
+        ensure_gradient(&mut self.grad_bias, &self.bias);
+        self.grad_weights += &gradients.wrt(weights);
+        self.grad_bias += &gradients.wrt(bias);
+        gradients.wrt(x)
+    }
+
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        let (out_height, out_width) = self.output_size(input_shape[1], input_shape[2]);
+        vec![self.out_channels, out_height, out_width]
+    }
+
+    fn parameters(&mut self) -> Vec<(&mut Tensor, &mut Tensor)> {
+        vec![(&mut self.weights, &mut self.grad_weights), (&mut self.bias, &mut self.grad_bias)]
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.weights.len() + self.bias.len()
+    }
+}
+
+// number of windows of a pooling layer along one axis, the pixels after the last whole window are dropped
+fn pooled_size(layer: &str, size: usize, window: usize) -> usize {
+    output_size(layer, size, window, window, 0)
+}
+
+/// max pooling over `size` * `size` windows with stride `size`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MaxPool2d {
+    size: usize,
+}
+
+impl MaxPool2d {
+    pub fn new(size: usize) -> MaxPool2d {
+        assert!(size > 0, "pool size must be greater than 0");
+        MaxPool2d { size }
+    }
+
+    // the max value of every window and its position in the input
+    fn pool(&self, x: ArrayView4<f32>) -> (Array4<f32>, Vec<[usize; 4]>) {
+        let (batch, channels, height, width) = x.dim();
+        let mut output = Array4::zeros((batch, channels, pooled_size("MaxPool2d", height, self.size), pooled_size("MaxPool2d", width, self.size)));
+        let mut positions = Vec::with_capacity(output.len());
+        for ((n, c, oy, ox), value) in output.indexed_iter_mut() {
+            let mut max = f32::NEG_INFINITY;
+            let mut position = [n, c, oy * self.size, ox * self.size];
+            for y in oy * self.size..(oy + 1) * self.size {
+                for x_ in ox * self.size..(ox + 1) * self.size {
+                    if x[[n, c, y, x_]] > max {
+                        max = x[[n, c, y, x_]];
+                        position = [n, c, y, x_];
+                    }
+                }
+            }
+            *value = max;
+            positions.push(position);
+        }
+        (output, positions)
+    }
+}
+
+impl Layer for MaxPool2d {
+    fn forward(&self, input: &Tensor, _mode: Mode) -> Tensor {
+        let x = input.view().into_dimensionality::<Ix4>().expect("pool input must be [batch, channels, height, width]");
+        self.pool(x).0.into_dyn()
+    }
+
+    fn backward(&mut self, input: &Tensor, _output: &Tensor, grad_output: &Tensor) -> Tensor {
+        // only the max value of a window gets the gradient
+        let x = input.view().into_dimensionality::<Ix4>().unwrap();
+        let (_, positions) = self.pool(x);
+        let mut grad_input = Array4::zeros(x.raw_dim());
+        for (g, position) in grad_output.iter().zip(positions) {
+            grad_input[position] += g;
+        }
+        grad_input.into_dyn()
+    }
+
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        vec![input_shape[0], pooled_size("MaxPool2d", input_shape[1], self.size), pooled_size("MaxPool2d", input_shape[2], self.size)]
+    }
+}
+
+/// average pooling over `size` * `size` windows with stride `size`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AvgPool2d {
+    size: usize,
+}
+
+impl AvgPool2d {
+    pub fn new(size: usize) -> AvgPool2d {
+        assert!(size > 0, "pool size must be greater than 0");
+        AvgPool2d { size }
+    }
+}
+
+impl Layer for AvgPool2d {
+    fn forward(&self, input: &Tensor, _mode: Mode) -> Tensor {
+        let x = input.view().into_dimensionality::<Ix4>().expect("pool input must be [batch, channels, height, width]");
+        let (batch, channels, height, width) = x.dim();
+        let area = (self.size * self.size) as f32;
+        let shape = (batch, channels, pooled_size("AvgPool2d", height, self.size), pooled_size("AvgPool2d", width, self.size));
+        Array4::from_shape_fn(shape, |(n, c, oy, ox)| {
+            x.slice(s![n, c, oy * self.size..(oy + 1) * self.size, ox * self.size..(ox + 1) * self.size]).sum() / area
+        }).into_dyn()
+    }
+
+    fn backward(&mut self, input: &Tensor, _output: &Tensor, grad_output: &Tensor) -> Tensor {
+        // every value of a window gets the same share of the gradient
+        let g = grad_output.view().into_dimensionality::<Ix4>().unwrap();
+        let (_, _, out_height, out_width) = g.dim();
+        let area = (self.size * self.size) as f32;
+        let shape = input.view().into_dimensionality::<Ix4>().unwrap().dim();
+        Array4::from_shape_fn(shape, |(n, c, y, x)| {
+            let (oy, ox) = (y / self.size, x / self.size);
+            if oy < out_height && ox < out_width {
+                g[[n, c, oy, ox]] / area
+            } else {
+                0.0
+            }
+        }).into_dyn()
+    }
+
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        vec![input_shape[0], pooled_size("AvgPool2d", input_shape[1], self.size), pooled_size("AvgPool2d", input_shape[2], self.size)]
+    }
+}
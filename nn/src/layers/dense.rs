@@ -0,0 +1,88 @@
+use ndarray::{Array, Ix1, Ix2};
+use ndarray_rand::rand::Rng;
+use ndarray_rand::rand_distr::Normal;
+use ndarray_rand::RandomExt;
+use serde::{Deserialize, Serialize};
+
+use super::{ensure_gradient, Layer, Tensor};
+use crate::autograd::{Tape, Var};
+use crate::Mode;
+
+/// fully connected layer, output = W * input + b
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Dense {
+    /// outputs * inputs weights matrix, the same layout as the weights of `NeuralNetwork`
+    weights: Tensor,
+    bias: Tensor,
+    #[serde(skip)]
+    grad_weights: Tensor,
+    #[serde(skip)]
+    grad_bias: Tensor,
+}
+
+impl Dense {
+    /// create a dense layer, the weights are normal distributed with standard deviation inputs^(-0.5)
+    pub fn new<R: Rng>(inputs: usize, outputs: usize, rng: &mut R) -> Dense {
+        let weights = Array::random_using((outputs, inputs), Normal::new(0.0, (inputs as f32).powf(-0.5)).unwrap(), rng);
+        Dense::from_weights(weights.into_dyn(), Array::zeros(outputs).into_dyn())
+    }
+
+    /// create a dense layer from an outputs * inputs weights matrix and a bias vector
+    pub fn from_weights(weights: Tensor, bias: Tensor) -> Dense {
+        assert_eq!(weights.ndim(), 2, "dense weights must be a matrix");
+        assert_eq!(bias.shape(), &[weights.shape()[0]], "dense bias must have one value per output");
+        Dense {
+            grad_weights: Array::zeros(weights.raw_dim()),
+            grad_bias: Array::zeros(bias.raw_dim()),
+            weights,
+            bias,
+        }
+    }
+
+    /// the outputs * inputs weights matrix
+    pub fn weights(&self) -> &Tensor {
+        &self.weights
+    }
+
+    /// the bias vector
+    pub fn bias(&self) -> &Tensor {
+        &self.bias
+    }
+
+    // the forward pass recorded on a tape for the backward pass, `forward` calculates the same without a tape
+    fn graph<'t>(x: Var<'t>, weights: Var<'t>, bias: Var<'t>) -> Var<'t> {
+        x.matmul(weights.t()) + bias
+    }
+}
+
+impl Layer for Dense {
+    fn forward(&self, input: &Tensor, _mode: Mode) -> Tensor {
+        let x = input.view().into_dimensionality::<Ix2>().expect("dense input must be [batch, features]");
+        let weights = self.weights.view().into_dimensionality::<Ix2>().unwrap();
+        let bias = self.bias.view().into_dimensionality::<Ix1>().unwrap();
+        (x.dot(&weights.t()) + bias).into_dyn()
+    }
+
+    fn backward(&mut self, input: &Tensor, _output: &Tensor, grad_output: &Tensor) -> Tensor {
+        let tape = Tape::new();
+        let (x, weights, bias) = (tape.var(input.clone()), tape.var(self.weights.clone()), tape.var(self.bias.clone()));
+        let gradients = Dense::graph(x, weights, bias).backward_with(grad_output.clone());
+        ensure_gradient(&mut self.grad_weights, &self.weights);
+        ensure_gradient(&mut self.grad_bias, &self.bias);
+        self.grad_weights += &gradients.wrt(weights);
+        self.grad_bias += &gradients.wrt(bias);
+        gradients.wrt(x)
+    }
+
+    fn output_shape(&self, _input_shape: &[usize]) -> Vec<usize> {
+        vec![self.weights.shape()[0]]
+    }
+
+    fn parameters(&mut self) -> Vec<(&mut Tensor, &mut Tensor)> {
+        vec![(&mut self.weights, &mut self.grad_weights), (&mut self.bias, &mut self.grad_bias)]
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.weights.len() + self.bias.len()
+    }
+}
@@ -0,0 +1,162 @@
+//! layers which can be stacked into a `Sequential` model
+//!
+//! every layer works on a batch, the first axis of a tensor is the sample index:
+//! `[batch, features]` for dense layers and `[batch, channels, height, width]` for image layers.
+
+use ndarray::ArrayD;
+use serde::{Deserialize, Serialize};
+use std::fmt::Debug;
+
+use crate::Mode;
+
+mod activation;
+mod conv;
+mod dense;
+mod norm;
+mod shape;
+
+pub use activation::ActivationLayer;
+pub use conv::{AvgPool2d, Conv2d, MaxPool2d};
+pub(crate) use conv::{col2im, im2col, output_size};
+pub use dense::Dense;
+pub use norm::{BatchNorm, LayerNorm};
+pub use shape::{Flatten, Reshape};
+
+/// n-dimensional array of a batch
+pub type Tensor = ArrayD<f32>;
+
+/// a layer with a forward and a backward pass
+///
+/// the forward pass does not change the layer, `Sequential` keeps the input and the output of every layer
+/// of a training batch and passes them to the backward pass.
+pub trait Layer: Debug {
+    /// forward pass of a batch
+    fn forward(&self, input: &Tensor, mode: Mode) -> Tensor;
+
+    /// forward pass of a training batch, layers with state which is learned in the forward pass override it
+    fn train_forward(&mut self, input: &Tensor) -> Tensor {
+        self.forward(input, Mode::Train)
+    }
+
+    /// backward pass of a training batch, accumulates the parameter gradients
+    /// and returns the gradient with respect to the input
+    fn backward(&mut self, input: &Tensor, output: &Tensor, grad_output: &Tensor) -> Tensor;
+
+    /// apply the accumulated parameter gradients with gradient descent and reset them
+    fn update(&mut self, learning_rate: f32) {
+        sgd_update(self.parameters(), learning_rate);
+    }
+
+    /// shape of one output sample for the shape of one input sample (without the batch axis)
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize>;
+
+    /// the learnable parameters and their accumulated gradients
+    fn parameters(&mut self) -> Vec<(&mut Tensor, &mut Tensor)> {
+        vec![]
+    }
+
+    /// number of learnable values, e.g. to describe a model
+    fn parameter_count(&self) -> usize {
+        0
+    }
+}
+
+/// any of the layers of this module, so models can be stored in a model file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum AnyLayer {
+    Dense(Dense),
+    Activation(ActivationLayer),
+    Conv2d(Conv2d),
+    MaxPool2d(MaxPool2d),
+    AvgPool2d(AvgPool2d),
+    Flatten(Flatten),
+    Reshape(Reshape),
+    BatchNorm(BatchNorm),
+    LayerNorm(LayerNorm),
+}
+
+macro_rules! dispatch {
+    ($self:ident, $layer:ident => $call:expr) => {
+        match $self {
+            AnyLayer::Dense($layer) => $call,
+            AnyLayer::Activation($layer) => $call,
+            AnyLayer::Conv2d($layer) => $call,
+            AnyLayer::MaxPool2d($layer) => $call,
+            AnyLayer::AvgPool2d($layer) => $call,
+            AnyLayer::Flatten($layer) => $call,
+            AnyLayer::Reshape($layer) => $call,
+            AnyLayer::BatchNorm($layer) => $call,
+            AnyLayer::LayerNorm($layer) => $call,
+        }
+    };
+}
+
+impl Layer for AnyLayer {
+    fn forward(&self, input: &Tensor, mode: Mode) -> Tensor {
+        dispatch!(self, layer => layer.forward(input, mode))
+    }
+
+    fn train_forward(&mut self, input: &Tensor) -> Tensor {
+        dispatch!(self, layer => layer.train_forward(input))
+    }
+
+    fn backward(&mut self, input: &Tensor, output: &Tensor, grad_output: &Tensor) -> Tensor {
+        dispatch!(self, layer => layer.backward(input, output, grad_output))
+    }
+
+    fn update(&mut self, learning_rate: f32) {
+        dispatch!(self, layer => layer.update(learning_rate))
+    }
+
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        dispatch!(self, layer => layer.output_shape(input_shape))
+    }
+
+    fn parameters(&mut self) -> Vec<(&mut Tensor, &mut Tensor)> {
+        dispatch!(self, layer => layer.parameters())
+    }
+
+    fn parameter_count(&self) -> usize {
+        dispatch!(self, layer => layer.parameter_count())
+    }
+}
+
+macro_rules! impl_from {
+    ($($variant:ident),*) => {
+        $(impl From<$variant> for AnyLayer {
+            fn from(layer: $variant) -> Self {
+                AnyLayer::$variant(layer)
+            }
+        })*
+    };
+}
+
+impl_from!(Dense, Conv2d, MaxPool2d, AvgPool2d, Flatten, Reshape, BatchNorm, LayerNorm);
+
+impl From<ActivationLayer> for AnyLayer {
+    fn from(layer: ActivationLayer) -> Self {
+        AnyLayer::Activation(layer)
+    }
+}
+
+// gradient descent step on all parameters of a layer, the gradients are reset afterwards
+fn sgd_update(parameters: Vec<(&mut Tensor, &mut Tensor)>, learning_rate: f32) {
+    for (parameter, gradient) in parameters {
+        if gradient.shape() == parameter.shape() {
+            parameter.scaled_add(-learning_rate, gradient);
+        }
+        gradient.fill(0.0);
+    }
+}
+
+// the gradients are not stored in the model file, they are created again before the first backward pass
+fn ensure_gradient(gradient: &mut Tensor, parameter: &Tensor) {
+    if gradient.shape() != parameter.shape() {
+        *gradient = Tensor::zeros(parameter.raw_dim());
+    }
+}
+
+// reshape into a standard layout copy, the batch axis can be included in `shape`
+fn reshape(tensor: &Tensor, shape: &[usize]) -> Tensor {
+    tensor.as_standard_layout().into_owned().into_shape(shape).expect("reshape does not match the tensor size")
+}
@@ -0,0 +1,275 @@
+use ndarray::{Array, Array1, Array2, Array3, Axis, Ix1, Ix2, Ix3};
+use serde::{Deserialize, Serialize};
+
+use super::{ensure_gradient, reshape, Layer, Tensor};
+use crate::Mode;
+
+/// batch normalisation of every feature (dense layers) or every channel (image layers)
+///
+/// in train mode a batch is normalised with its own mean and variance, which also update the running
+/// mean and variance. In eval mode the running mean and variance are used, so a prediction does not
+/// depend on the other samples of the batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchNorm {
+    /// learnable scale
+    gamma: Tensor,
+    /// learnable shift
+    beta: Tensor,
+    running_mean: Array1<f32>,
+    running_var: Array1<f32>,
+    /// weight of a new batch in the running mean and variance
+    momentum: f32,
+    epsilon: f32,
+    #[serde(skip)]
+    grad_gamma: Tensor,
+    #[serde(skip)]
+    grad_beta: Tensor,
+}
+
+impl BatchNorm {
+    /// `features` is the number of features of a `[batch, features]` input
+    /// or the number of channels of a `[batch, channels, height, width]` input
+    pub fn new(features: usize) -> BatchNorm {
+        BatchNorm {
+            gamma: Array::ones(features).into_dyn(),
+            beta: Array::zeros(features).into_dyn(),
+            running_mean: Array::zeros(features),
+            running_var: Array::ones(features),
+            momentum: 0.1,
+            epsilon: 1e-5,
+            grad_gamma: Array::zeros(features).into_dyn(),
+            grad_beta: Array::zeros(features).into_dyn(),
+        }
+    }
+
+    /// weight of a new batch in the running mean and variance, 0.1 by default
+    pub fn momentum(mut self, momentum: f32) -> BatchNorm {
+        assert!((0.0..=1.0).contains(&momentum), "momentum must be in range [0, 1]");
+        self.momentum = momentum;
+        self
+    }
+
+    pub fn running_mean(&self) -> &Array1<f32> {
+        &self.running_mean
+    }
+
+    pub fn running_var(&self) -> &Array1<f32> {
+        &self.running_var
+    }
+
+    // mean and biased variance of every channel of a [batch, channels, positions] array
+    fn batch_statistics(x: &Array3<f32>) -> (Array1<f32>, Array1<f32>) {
+        let count = (x.len_of(Axis(0)) * x.len_of(Axis(2))).max(1) as f32;
+        let mean = x.sum_axis(Axis(2)).sum_axis(Axis(0)) / count;
+        let centered = x - &mean.view().insert_axis(Axis(1));
+        let var = centered.mapv(|v| v * v).sum_axis(Axis(2)).sum_axis(Axis(0)) / count;
+        (mean, var)
+    }
+
+    fn normalize(&self, x: &Array3<f32>, mean: &Array1<f32>, var: &Array1<f32>) -> Array3<f32> {
+        let gamma = self.gamma.view().into_shape(self.running_mean.len()).unwrap();
+        let scale = &gamma / &var.mapv(|v| (v + self.epsilon).sqrt());
+        let shift = &self.beta.view().into_shape(self.running_mean.len()).unwrap() - &(&scale * mean);
+        x * &scale.insert_axis(Axis(1)) + &shift.insert_axis(Axis(1))
+    }
+}
+
+impl Layer for BatchNorm {
+    fn forward(&self, input: &Tensor, mode: Mode) -> Tensor {
+        let x = as_channels(input);
+        let output = match mode {
+            Mode::Train => {
+                let (mean, var) = BatchNorm::batch_statistics(&x);
+                self.normalize(&x, &mean, &var)
+            }
+            Mode::Eval => self.normalize(&x, &self.running_mean, &self.running_var),
+        };
+        reshape(&output.into_dyn(), input.shape())
+    }
+
+    fn train_forward(&mut self, input: &Tensor) -> Tensor {
+        let x = as_channels(input);
+        let (mean, var) = BatchNorm::batch_statistics(&x);
+        // the running variance is the unbiased estimate like in other frameworks
+        let count = (x.len_of(Axis(0)) * x.len_of(Axis(2))) as f32;
+        let unbiased = if count > 1.0 { &var * (count / (count - 1.0)) } else { var.clone() };
+        self.running_mean = &self.running_mean * (1.0 - self.momentum) + &mean * self.momentum;
+        self.running_var = &self.running_var * (1.0 - self.momentum) + unbiased * self.momentum;
+        reshape(&self.normalize(&x, &mean, &var).into_dyn(), input.shape())
+    }
+
+    fn backward(&mut self, input: &Tensor, _output: &Tensor, grad_output: &Tensor) -> Tensor {
+        let x = as_channels(input);
+        let g = as_channels(grad_output);
+        let (mean, var) = BatchNorm::batch_statistics(&x);
+        let inv_std = var.mapv(|v| 1.0 / (v + self.epsilon).sqrt()).insert_axis(Axis(1));
+        let x_hat = (&x - &mean.insert_axis(Axis(1))) * &inv_std;
+        ensure_gradient(&mut self.grad_gamma, &self.gamma);
+        ensure_gradient(&mut self.grad_beta, &self.beta);
+        self.grad_gamma += &(&g * &x_hat).sum_axis(Axis(2)).sum_axis(Axis(0)).into_dyn();
+        self.grad_beta += &g.sum_axis(Axis(2)).sum_axis(Axis(0)).into_dyn();
+
+        // dx = gamma / std * (g - mean(g) - x_hat * mean(g * x_hat)) with the means over a channel
+        let count = (x.len_of(Axis(0)) * x.len_of(Axis(2))).max(1) as f32;
+        let mean_g = g.sum_axis(Axis(2)).sum_axis(Axis(0)) / count;
+        let mean_g_x_hat = (&g * &x_hat).sum_axis(Axis(2)).sum_axis(Axis(0)) / count;
+        let gamma = self.gamma.view().into_shape(self.running_mean.len()).unwrap().insert_axis(Axis(1));
+        let grad_input = (&g - &mean_g.insert_axis(Axis(1)) - &x_hat * &mean_g_x_hat.insert_axis(Axis(1)))
+            * &(&gamma * &inv_std);
+        reshape(&grad_input.into_dyn(), input.shape())
+    }
+
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        input_shape.to_vec()
+    }
+
+    fn parameters(&mut self) -> Vec<(&mut Tensor, &mut Tensor)> {
+        vec![(&mut self.gamma, &mut self.grad_gamma), (&mut self.beta, &mut self.grad_beta)]
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.gamma.len() + self.beta.len()
+    }
+}
+
+/// layer normalisation, every sample is normalised with the mean and variance of its own features
+///
+/// it does not depend on the batch, so train and eval mode give the same output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayerNorm {
+    /// learnable scale of every feature
+    gamma: Tensor,
+    /// learnable shift of every feature
+    beta: Tensor,
+    epsilon: f32,
+    #[serde(skip)]
+    grad_gamma: Tensor,
+    #[serde(skip)]
+    grad_beta: Tensor,
+}
+
+impl LayerNorm {
+    /// `features` is the number of values of one sample
+    pub fn new(features: usize) -> LayerNorm {
+        LayerNorm {
+            gamma: Array::ones(features).into_dyn(),
+            beta: Array::zeros(features).into_dyn(),
+            epsilon: 1e-5,
+            grad_gamma: Array::zeros(features).into_dyn(),
+            grad_beta: Array::zeros(features).into_dyn(),
+        }
+    }
+
+    // x_hat and 1 / std of every sample of a [batch, features] array
+    fn standardize(&self, x: &Array2<f32>) -> (Array2<f32>, Array2<f32>) {
+        let features = x.ncols().max(1) as f32;
+        let mean = (x.sum_axis(Axis(1)) / features).insert_axis(Axis(1));
+        let centered = x - &mean;
+        let var = centered.mapv(|v| v * v).sum_axis(Axis(1)) / features;
+        let inv_std = var.mapv(|v| 1.0 / (v + self.epsilon).sqrt()).insert_axis(Axis(1));
+        (centered * &inv_std, inv_std)
+    }
+}
+
+impl Layer for LayerNorm {
+    fn forward(&self, input: &Tensor, _mode: Mode) -> Tensor {
+        let x = as_rows(input);
+        let (x_hat, _) = self.standardize(&x);
+        let gamma = self.gamma.view().into_dimensionality::<Ix1>().unwrap();
+        let beta = self.beta.view().into_dimensionality::<Ix1>().unwrap();
+        reshape(&(x_hat * gamma + beta).into_dyn(), input.shape())
+    }
+
+    fn backward(&mut self, input: &Tensor, _output: &Tensor, grad_output: &Tensor) -> Tensor {
+        let x = as_rows(input);
+        let g = as_rows(grad_output);
+        let (x_hat, inv_std) = self.standardize(&x);
+        ensure_gradient(&mut self.grad_gamma, &self.gamma);
+        ensure_gradient(&mut self.grad_beta, &self.beta);
+        self.grad_gamma += &(&g * &x_hat).sum_axis(Axis(0)).into_dyn();
+        self.grad_beta += &g.sum_axis(Axis(0)).into_dyn();
+
+        // the same formula as batch normalisation with the means over the features of a sample
+        let gamma = self.gamma.view().into_dimensionality::<Ix1>().unwrap();
+        let g_hat = &g * &gamma;
+        let features = x.ncols().max(1) as f32;
+        let mean_g = (g_hat.sum_axis(Axis(1)) / features).insert_axis(Axis(1));
+        let mean_g_x_hat = ((&g_hat * &x_hat).sum_axis(Axis(1)) / features).insert_axis(Axis(1));
+        let grad_input = (&g_hat - &mean_g - &x_hat * &mean_g_x_hat) * &inv_std;
+        reshape(&grad_input.into_dyn(), input.shape())
+    }
+
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        input_shape.to_vec()
+    }
+
+    fn parameters(&mut self) -> Vec<(&mut Tensor, &mut Tensor)> {
+        vec![(&mut self.gamma, &mut self.grad_gamma), (&mut self.beta, &mut self.grad_beta)]
+    }
+
+    fn parameter_count(&self) -> usize {
+        self.gamma.len() + self.beta.len()
+    }
+}
+
+// [batch, channels, positions] copy, a [batch, features] input has one position per feature
+fn as_channels(tensor: &Tensor) -> Array3<f32> {
+    let shape = tensor.shape();
+    let positions = shape[2..].iter().product();
+    reshape(tensor, &[shape[0], shape[1], positions]).into_dimensionality::<Ix3>().unwrap()
+}
+
+// [batch, features] copy of a batch
+fn as_rows(tensor: &Tensor) -> Array2<f32> {
+    let batch = tensor.shape()[0];
+    reshape(tensor, &[batch, tensor.len() / batch.max(1)]).into_dimensionality::<Ix2>().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // compare the input gradient of the loss sum(output * weights) with central differences
+    fn check_input_gradient(layer: &mut dyn Layer, input: &Tensor) {
+        let weights = Tensor::from_shape_vec(input.raw_dim(), (0..input.len()).map(|i| (i as f32 * 0.7).cos()).collect()).unwrap();
+        let loss = |layer: &dyn Layer, x: &Tensor| (layer.forward(x, Mode::Train) * &weights).sum();
+        let output = layer.forward(input, Mode::Train);
+        let grad_input = layer.backward(input, &output, &weights);
+        let eps = 1e-2;
+        for i in [0, 3, input.len() - 1] {
+            let mut plus = input.clone();
+            plus.as_slice_mut().unwrap()[i] += eps;
+            let mut minus = input.clone();
+            minus.as_slice_mut().unwrap()[i] -= eps;
+            let numeric = (loss(layer, &plus) - loss(layer, &minus)) / (2.0 * eps);
+            let analytic = grad_input.as_slice().unwrap()[i];
+            assert!((numeric - analytic).abs() < 2e-2, "{} != {}", numeric, analytic);
+        }
+    }
+
+    #[test]
+    fn normalisation_gradients() {
+        let image = Tensor::from_shape_vec(vec![3, 2, 2, 2], (0..24).map(|i| (i as f32 * 1.3).sin()).collect()).unwrap();
+        check_input_gradient(&mut BatchNorm::new(2), &image);
+        check_input_gradient(&mut LayerNorm::new(8), &image);
+    }
+
+    #[test]
+    fn batch_norm_uses_running_statistics_in_eval_mode() {
+        let mut layer = BatchNorm::new(2).momentum(1.0);
+        let batch = Tensor::from_shape_vec(vec![2, 2], vec![1.0, 10.0, 3.0, 30.0]).unwrap();
+        let output = layer.train_forward(&batch);
+        assert!((output[[0, 0]] + 1.0).abs() < 1e-3 && (output[[1, 1]] - 1.0).abs() < 1e-3);
+        assert_eq!(layer.running_mean().to_vec(), vec![2.0, 20.0]);
+
+        // in eval mode a single sample is normalised with the running statistics, the unbiased variance is 2 and 200
+        let sample = Tensor::from_shape_vec(vec![1, 2], vec![2.0, 20.0]).unwrap();
+        assert!(layer.forward(&sample, Mode::Eval).iter().all(|v| v.abs() < 1e-6));
+        let sample = Tensor::from_shape_vec(vec![1, 2], vec![4.0, 20.0]).unwrap();
+        assert!((layer.forward(&sample, Mode::Eval)[[0, 0]] - 2.0f32.sqrt()).abs() < 1e-3);
+
+        // the running statistics are stored in the model file
+        let loaded: BatchNorm = serde_json::from_str(&serde_json::to_string(&layer).unwrap()).unwrap();
+        assert_eq!(loaded.forward(&sample, Mode::Eval), layer.forward(&sample, Mode::Eval));
+    }
+}
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+use super::{reshape, Layer, Tensor};
+use crate::Mode;
+
+/// flatten every sample of a batch into a vector, `[batch, ...]` becomes `[batch, features]`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Flatten;
+
+impl Flatten {
+    pub fn new() -> Flatten {
+        Flatten
+    }
+}
+
+impl Layer for Flatten {
+    fn forward(&self, input: &Tensor, _mode: Mode) -> Tensor {
+        let batch = input.shape()[0];
+        reshape(input, &[batch, input.len() / batch.max(1)])
+    }
+
+    fn backward(&mut self, input: &Tensor, _output: &Tensor, grad_output: &Tensor) -> Tensor {
+        reshape(grad_output, input.shape())
+    }
+
+    fn output_shape(&self, input_shape: &[usize]) -> Vec<usize> {
+        vec![input_shape.iter().product()]
+    }
+}
+
+/// reshape every sample of a batch, e.g. the 784 MNIST inputs into a `[1, 28, 28]` image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reshape {
+    shape: Vec<usize>,
+}
+
+impl Reshape {
+    /// `shape` is the shape of one sample without the batch axis
+    pub fn new(shape: &[usize]) -> Reshape {
+        Reshape { shape: shape.to_vec() }
+    }
+}
+
+impl Layer for Reshape {
+    fn forward(&self, input: &Tensor, _mode: Mode) -> Tensor {
+        let mut shape = vec![input.shape()[0]];
+        shape.extend(&self.shape);
+        reshape(input, &shape)
+    }
+
+    fn backward(&mut self, input: &Tensor, _output: &Tensor, grad_output: &Tensor) -> Tensor {
+        reshape(grad_output, input.shape())
+    }
+
+    fn output_shape(&self, _input_shape: &[usize]) -> Vec<usize> {
+        self.shape.clone()
+    }
+}
@@ -1,38 +1,98 @@
-use ndarray::{Array, Dim};
+use ndarray::{Array, Array1, ArrayView1, ArrayView2, ArrayViewMut1, Axis, Ix2};
 use ndarray_rand::RandomExt;
+use ndarray_rand::rand::SeedableRng;
 use ndarray_rand::rand_distr::Normal;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::thread;
+
+pub mod activation;
+pub mod augment;
+pub mod autograd;
+pub mod browser;
+pub mod cross_validation;
+pub mod dataset;
+pub mod ensemble;
+pub mod evaluation;
+pub mod explain;
+mod float;
+#[cfg(test)]
+mod gradient_check;
+pub mod layers;
+pub mod loader;
+pub mod metrics;
+pub mod model;
+pub mod npy;
+pub mod onnx;
+mod optimizer;
+pub mod persist;
+mod protobuf;
+pub mod quantize;
+mod regularization;
+pub mod sequential;
+pub mod sweep;
+pub mod trainer;
+pub mod visualize;
+
+pub use activation::Activation;
+pub use dataset::Sample;
+pub use float::Float;
+pub use optimizer::{Optimizer, Schedule};
+pub use regularization::{Mode, Regularization};
+
+use optimizer::OptimizerState;
+use persist::PersistError;
+
+/// 2d matrix used for the weights and the column vectors of the neural network
+pub type Matrix<T = f32> = Array<T, Ix2>;
 
 /// neural network  struct definition
-#[derive(Debug)]
-pub struct NeuralNetwork {
+///
+/// the weights, inputs and outputs are `f32` by default, `NeuralNetwork<f64>` is the same network in double
+/// precision. The hyperparameters (learning rate, regularisation, momentum) are always `f32`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(bound = "T: Float", try_from = "SavedNetwork<T>")]
+pub struct NeuralNetwork<T = f32> {
     input_nodes: i32,
     hidden_nodes: i32,
     output_nodes: i32,
     learning_rate: f32,
-    weight_ih: Array<f32, Dim<[usize; 2]>>, // weights matrix from input to hidden layer
-    weight_ho: Array<f32, Dim<[usize; 2]>>, // weights matrix from hidden to output layer
-    activation_function: fn(f32) -> f32,
+    weight_ih: Matrix<T>, // weights matrix from input to hidden layer
+    weight_ho: Matrix<T>, // weights matrix from hidden to output layer
+    // the activation of the hidden layer, the output layer is always the sigmoid of the book
+    activation: Activation,
+    regularization: Regularization,
+    optimizer: Optimizer,
+    optimizer_state: Option<OptimizerState<T>>,
+    mode: Mode,
+    rng: ChaCha8Rng, // random number generator for the dropout masks
 
 }
 
-impl NeuralNetwork {
+impl<T: Float> NeuralNetwork<T> {
     /// Create a new neural network from inputnodes, hiddennodes, outputnodes, learningrate
-    pub fn new(inputnodes: i32, hiddennodes: i32, outputnodes: i32, learningrate: f32) -> NeuralNetwork {
+    pub fn new(inputnodes: i32, hiddennodes: i32, outputnodes: i32, learningrate: f32) -> NeuralNetwork<T> {
+        NeuralNetwork::with_rng(inputnodes, hiddennodes, outputnodes, learningrate, ChaCha8Rng::from_entropy())
+    }
+
+    /// Create a new neural network like `new`, the initial weights and the dropout masks are reproducible from `seed`
+    pub fn new_with_seed(inputnodes: i32, hiddennodes: i32, outputnodes: i32, learningrate: f32, seed: u64) -> NeuralNetwork<T> {
+        NeuralNetwork::with_rng(inputnodes, hiddennodes, outputnodes, learningrate, ChaCha8Rng::seed_from_u64(seed))
+    }
+
+    fn with_rng(inputnodes: i32, hiddennodes: i32, outputnodes: i32, learningrate: f32, mut rng: ChaCha8Rng) -> NeuralNetwork<T> {
         // hiddennodes*inputnodes matrix array
         // mean 0.0 and standard deviation of 1 / sqrt(number of nodes of next layer) = inputnodes^(-0.5)
-        let wih = Array::random((hiddennodes as usize, inputnodes as usize),
-            Normal::new(0.0, (hiddennodes as f32).powf(-0.5)).unwrap());
-        
+        // the weights are drawn as f32 for every precision, so a seed gives the same network in f32 and f64
+        let wih = Array::random_using((hiddennodes as usize, inputnodes as usize),
+            Normal::new(0.0, (hiddennodes as f32).powf(-0.5)).unwrap(), &mut rng).mapv(T::from_f32);
+
         // outputnodes*hiddennodes matrix array
         // mean 0.0 and standard deviation of 1 / sqrt(number of nodes of next layer) = hiddennodes^(-0.5)
-        let who = Array::random((outputnodes as usize, hiddennodes as usize),
-            Normal::new(0.0, (outputnodes as f32).powf(-0.5)).unwrap());
+        let who = Array::random_using((outputnodes as usize, hiddennodes as usize),
+            Normal::new(0.0, (outputnodes as f32).powf(-0.5)).unwrap(), &mut rng).mapv(T::from_f32);
 
-        // 1 / (1 + e^(-x))
-        fn sigmoid(x: f32) -> f32 {
-            1.0 / (1.0 + (-x).exp())
-        }
-        
         NeuralNetwork {
             // set number of nodes in each input, hidden, output layer
             input_nodes: inputnodes,
@@ -43,42 +103,245 @@ impl NeuralNetwork {
             // learning rate
             learning_rate: learningrate,
             // activation function is the sigmoid function
-            activation_function: |x| sigmoid(x),
+            activation: Activation::Sigmoid,
+            // no regularisation by default
+            regularization: Regularization::default(),
+            // plain SGD like the book
+            optimizer: Optimizer::Sgd,
+            optimizer_state: None,
+            mode: Mode::Train,
+            rng,
         }
     }
 
-    /// train the neural network
-    pub fn train(&mut self, input_list: &Vec<f32>, target_list: &Vec<f32>) {
-        let (hidden_output_vec, final_output_vec) = self.forward(input_list);
-        let target_vec =  Array::from_shape_vec((target_list.len(), 1), target_list.clone()).unwrap();
+    /// Create a neural network from trained hidden*input and output*hidden weights matrices, e.g. imported `wih`/`who`
+    pub fn from_weights(weight_ih: Matrix<T>, weight_ho: Matrix<T>, learningrate: f32) -> NeuralNetwork<T> {
+        if weight_ho.ncols() != weight_ih.nrows() {
+            panic!("weights matrices do not fit together");
+        }
+        let (hidden, input) = weight_ih.dim();
+        let mut nn = NeuralNetwork::new(input as i32, hidden as i32, weight_ho.nrows() as i32, learningrate);
+        nn.weight_ih = weight_ih;
+        nn.weight_ho = weight_ho;
+        nn
+    }
+
+    /// set the L1/L2 penalties and the dropout rate used by `train`
+    pub fn set_regularization(&mut self, regularization: Regularization) {
+        assert!((0.0..1.0).contains(&regularization.dropout), "dropout must be in range [0, 1)");
+        self.regularization = regularization;
+    }
+
+    /// get the regularisation settings of the neural network
+    pub fn regularization(&self) -> Regularization {
+        self.regularization
+    }
+
+    /// set the activation function of the hidden layer, sigmoid by default. The outputs always use the sigmoid
+    /// so they stay in the range of the targets
+    pub fn set_activation(&mut self, activation: Activation) {
+        assert!(activation != Activation::Softmax, "softmax can not be the activation of the hidden layer");
+        self.activation = activation;
+    }
+
+    /// get the activation function of the hidden layer
+    pub fn activation(&self) -> Activation {
+        self.activation
+    }
+
+    /// get the learning rate
+    pub fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    /// set the learning rate, e.g. from a `Schedule`
+    pub fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    /// get the input to hidden and the hidden to output weights matrices
+    pub fn weights(&self) -> (&Matrix<T>, &Matrix<T>) {
+        (&self.weight_ih, &self.weight_ho)
+    }
+
+    /// set the optimizer, the state of the previous optimizer (e.g. the momentum velocities) is dropped
+    pub fn set_optimizer(&mut self, optimizer: Optimizer) {
+        self.optimizer = optimizer;
+        self.optimizer_state = None;
+    }
+
+    /// get the optimizer of the neural network
+    pub fn optimizer(&self) -> Optimizer {
+        self.optimizer
+    }
+
+    /// save the neural network, including its training state, to a JSON model file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PersistError> {
+        persist::save_json(self, path)
+    }
+
+    /// load a neural network from a model file written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<NeuralNetwork<T>, PersistError> {
+        persist::load_json(path)
+    }
+
+    /// switch between train and eval mode, dropout is only applied in `Mode::Train`
+    pub fn set_mode(&mut self, mode: Mode) {
+        self.mode = mode;
+    }
+
+    /// get the current mode of the neural network
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// train the neural network, returns the mean squared error of the outputs before the weights are updated
+    pub fn train(&mut self, input_list: &[T], target_list: &[T]) -> T {
+        self.train_step(input_list, target_list).loss
+    }
+
+    /// `train` which also returns the norms of the gradients, e.g. for a `metrics::MetricsLogger`
+    pub fn train_step(&mut self, input_list: &[T], target_list: &[T]) -> TrainStep<T> {
+        let dropout_mask = self.next_dropout_mask();
+        let gradients = self.gradients(input_list, target_list, dropout_mask.as_ref());
+        self.apply_gradients(&gradients);
+        gradients.step()
+    }
+
+    /// train the neural network with a mini batch, the weights are updated once with the mean of the samples' updates
+    ///
+    /// the batch is split into `threads` contiguous chunks whose updates are calculated in parallel and then summed
+    /// in chunk order. The dropout masks are drawn in sample order before the batch is split, so a seeded network
+    /// always gets the same weights for the same batches and the same number of threads.
+    /// Returns the mean loss of the batch like `train`.
+    pub fn train_batch(&mut self, batch: &[Sample], threads: usize) -> T {
+        self.train_batch_step(batch, threads).loss
+    }
+
+    /// `train_batch` which also returns the norms of the mean gradients of the batch
+    pub fn train_batch_step(&mut self, batch: &[Sample], threads: usize) -> TrainStep<T> {
+        if batch.is_empty() {
+            return TrainStep { loss: T::zero(), gradient_norm_ih: T::zero(), gradient_norm_ho: T::zero() };
+        }
+        let dropout_masks: Vec<Option<Matrix<T>>> = batch.iter().map(|_| self.next_dropout_mask()).collect();
+        let chunk_size = batch.len().div_ceil(threads.max(1));
+
+        let network = &*self;
+        let chunk_gradients: Vec<Gradients<T>> = thread::scope(|scope| {
+            let handles: Vec<_> = batch.chunks(chunk_size).zip(dropout_masks.chunks(chunk_size))
+                .map(|(samples, masks)| scope.spawn(move || {
+                    let mut sum = Gradients::zeros(network);
+                    for (sample, mask) in samples.iter().zip(masks) {
+                        let (input_list, target_list) = (to_float(&sample.input), to_float(&sample.target));
+                        sum.add(&network.gradients(&input_list, &target_list, mask.as_ref()));
+                    }
+                    sum
+                }))
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+
+        let mut gradients = Gradients::zeros(self);
+        for chunk in &chunk_gradients {
+            gradients.add(chunk);
+        }
+        gradients.scale(T::one() / T::from_f32(batch.len() as f32));
+        self.apply_gradients(&gradients);
+        gradients.step()
+    }
+
+    // inverted dropout mask for the hidden layer, only in train mode
+    fn next_dropout_mask(&mut self) -> Option<Matrix<T>> {
+        if self.mode == Mode::Train && self.regularization.dropout > 0.0 {
+            Some(self.regularization.dropout_mask(self.hidden_nodes as usize, &mut self.rng))
+        } else {
+            None
+        }
+    }
+
+    // calculate the weight updates for one sample, without the learning rate and the penalties
+    fn gradients(&self, input_list: &[T], target_list: &[T], dropout_mask: Option<&Matrix<T>>) -> Gradients<T> {
+        if input_list.len() != self.input_nodes as usize {
+            panic!("input list length does not match input nodes");
+        }
+        let input_vec = ArrayView2::from_shape((input_list.len(), 1), input_list).unwrap();
+        let target_vec = ArrayView2::from_shape((target_list.len(), 1), target_list).unwrap();
+
+        // calculate the signals emerging from hidden layer
+        let hidden_output_vec = self.weight_ih.dot(&input_vec).mapv(|x| self.activation.value(x));
+
+        // inverted dropout, the dropped hidden nodes do not send any signal to the output layer
+        let hidden_signal_vec = match dropout_mask {
+            Some(mask) => &hidden_output_vec * mask,
+            None => hidden_output_vec.clone(),
+        };
+
+        // calculate the signals emerging from final output layer
+        let final_output_vec = self.weight_ho.dot(&hidden_signal_vec).mapv(sigmoid);
 
         // calculate the error (target - actual_output)
-        let output_errors_vec = target_vec - &final_output_vec;
-        // calculate the hidden layer errors (errors_hidden = (weights_hidden_to_output^T) * output_errors_vec)
-        let hidden_errors_vec = self.weight_ho.t().dot(&output_errors_vec);
+        let output_errors_vec = &target_vec - &final_output_vec;
+        // the error at the input of the output sigmoid, E * O * (1 - O)
+        let output_deltas_vec = &output_errors_vec * &final_output_vec * final_output_vec.mapv(|o| T::one() - o);
+        // calculate the hidden layer errors (errors_hidden = (weights_hidden_to_output^T) * output_deltas_vec)
+        // the book uses the output errors here, which is not the gradient of the squared error
+        let mut hidden_errors_vec = self.weight_ho.t().dot(&output_deltas_vec);
+        // the dropped hidden nodes did not contribute to the output, so they do not receive any error either
+        if let Some(mask) = dropout_mask {
+            hidden_errors_vec *= mask;
+        }
+
+        Gradients {
+            // E * O * (1 - O) * hidden_output_vec^T where O is sigmoid(previous_output_vec) and E is next layer errors
+            ho: output_deltas_vec.dot(&hidden_signal_vec.t()),
+            // E * f'(H) * input_vec^T where H is f(previous_output_vec), f the hidden activation and E is next layer errors
+            ih: (&hidden_errors_vec * hidden_output_vec.mapv(|h| self.activation.derivative(h))).dot(&input_vec.t()),
+            loss: output_errors_vec.mapv(|e| e * e).sum() / T::from_f32(output_errors_vec.len() as f32),
+        }
+    }
+
+    fn apply_gradients(&mut self, gradients: &Gradients<T>) {
+        // the penalties are calculated from the weights before they are updated
+        // update = gradients - (l2 * W + l1 * sign(W))
+        let update_ho = &gradients.ho - self.regularization.penalty_gradient(&self.weight_ho);
+        let update_ih = &gradients.ih - self.regularization.penalty_gradient(&self.weight_ih);
 
-        // Update weights for the weight links between the hidden and output layers used With output_errors_vec
-        // Delta W = learning_rate * E * O * (1 - O) * hidden_output_vec^T where O is sigmoid(previous_output_vec) and E is next layer errors
-        self.weight_ho = &self.weight_ho + self.learning_rate * &output_errors_vec * &final_output_vec * (1.0 - &final_output_vec) * &hidden_output_vec.t();
+        let (update_ih, update_ho) = match self.optimizer {
+            Optimizer::Sgd => (update_ih, update_ho),
+            Optimizer::Momentum { momentum } => {
+                let momentum = T::from_f32(momentum);
+                let state = self.optimizer_state.get_or_insert_with(|| OptimizerState {
+                    velocity_ih: Array::zeros(update_ih.raw_dim()),
+                    velocity_ho: Array::zeros(update_ho.raw_dim()),
+                });
+                // V = momentum * V + update
+                state.velocity_ih = &state.velocity_ih * momentum + update_ih;
+                state.velocity_ho = &state.velocity_ho * momentum + update_ho;
+                (state.velocity_ih.clone(), state.velocity_ho.clone())
+            }
+        };
 
-        // Update weights for the weight links between the input and hidden layers used With hidden_errors_vec
-        // Delta W = learning_rate * E * O * (1 - O) * input_vec^T where O is sigmoid(previous_output_vec) and E is next layer errors
-        let input_vec = Array::from_shape_vec((input_list.len(), 1), input_list.clone()).unwrap();
-        self.weight_ih = &self.weight_ih + self.learning_rate * &hidden_errors_vec * &hidden_output_vec * (1.0 - &hidden_output_vec) * &input_vec.t();
+        // Update weights for the weight links between the hidden and output layers
+        // Delta W = learning_rate * update
+        let learning_rate = T::from_f32(self.learning_rate);
+        self.weight_ho = &self.weight_ho + update_ho * learning_rate;
 
+        // Update weights for the weight links between the input and hidden layers
+        // Delta W = learning_rate * update
+        self.weight_ih = &self.weight_ih + update_ih * learning_rate;
     }
 
     /// forward pass through the neural network
-    pub fn forward(&self, input_list: &Vec<f32>) -> (Array<f32, Dim<[usize; 2]>>, Array<f32, Dim<[usize; 2]>>){
+    pub fn forward(&self, input_list: &[T]) -> (Matrix<T>, Matrix<T>) {
         if input_list.len() != self.input_nodes as usize {
             panic!("input list length does not match input nodes");
         }
-        // convert input list to 2d array
+        // view the input list as a column vector without copying it
         // calculate signals into hidden layer
-        let input_vec = Array::from_shape_vec((input_list.len(), 1), input_list.clone()).unwrap();
+        let input_vec = ArrayView2::from_shape((input_list.len(), 1), input_list).unwrap();
         let hidden_input_vec = self.weight_ih.dot(&input_vec);
         // calculate the signals emerging from hidden layer
-        let hidden_output_vec = hidden_input_vec.mapv(|x| (self.activation_function)(x));
+        let hidden_output_vec = hidden_input_vec.mapv(|x| self.activation.value(x));
 
         if hidden_output_vec.shape()[0] != self.hidden_nodes as usize {
             panic!("hidden output vec length does not match hidden nodes");
@@ -87,25 +350,256 @@ impl NeuralNetwork {
         // calculate signals into final output layer
         let final_input_vec = self.weight_ho.dot(&hidden_output_vec);
         // calculate the signals emerging from final output layer
-        let final_output_vec = final_input_vec.mapv(|x| (self.activation_function)(x));
+        let final_output_vec = final_input_vec.mapv(sigmoid);
 
         if final_output_vec.shape()[0] != self.output_nodes as usize {
             panic!("final output vec length does not match output nodes");
         }
 
-        return (hidden_output_vec, final_output_vec);
+        (hidden_output_vec, final_output_vec)
+    }
+
+    /// query the neural network, dropout is never applied here whatever the mode is
+    pub fn predict(&self, input_list: &[T]) -> Vec<T> {
+        let mut output_list = vec![T::zero(); self.output_nodes as usize];
+        self.predict_into(input_list, &mut output_list);
+        output_list
+    }
+
+    /// query the neural network like `predict` with an input view, e.g. a row of a batch or a strided image
+    pub fn predict_view(&self, input: ArrayView1<T>) -> Array1<T> {
+        let mut output = Array1::zeros(self.output_nodes as usize);
+        self.predict_view_into(input, output.view_mut());
+        output
+    }
+
+    /// query the neural network like `predict` and write the outputs to `output_list`, without allocating,
+    /// so a buffer can be reused for every frame of a stream
+    pub fn predict_into(&self, input_list: &[T], output_list: &mut [T]) {
+        if output_list.len() != self.output_nodes as usize {
+            panic!("output list length does not match output nodes");
+        }
+        self.predict_view_into(ArrayView1::from(input_list), ArrayViewMut1::from(output_list));
+    }
+
+    // the outputs are accumulated one hidden node at a time, so the hidden layer needs no buffer
+    fn predict_view_into(&self, input: ArrayView1<T>, mut output: ArrayViewMut1<T>) {
+        if input.len() != self.input_nodes as usize {
+            panic!("input list length does not match input nodes");
+        }
+        output.fill(T::zero());
+        for (weights, output_weights) in self.weight_ih.outer_iter().zip(self.weight_ho.columns()) {
+            let hidden_output = self.activation.value(weights.dot(&input));
+            output.scaled_add(hidden_output, &output_weights);
+        }
+        output.mapv_inplace(sigmoid);
+    }
+
+    /// query the neural network with a `[batch, inputs]` batch, one row of outputs per sample
+    pub fn predict_batch(&self, inputs: ArrayView2<T>) -> Matrix<T> {
+        if inputs.ncols() != self.input_nodes as usize {
+            panic!("input list length does not match input nodes");
+        }
+        let hidden_outputs = inputs.dot(&self.weight_ih.t()).mapv(|x| self.activation.value(x));
+        hidden_outputs.dot(&self.weight_ho.t()).mapv(sigmoid)
     }
 
-    /// query the neural network
-    pub fn predict(&self, input_list: &Vec<f32>) -> Vec<f32> {
-        let (_, final_output_list)= self.forward(input_list);
-        let mut output_list = Vec::new();
-        for i in 0..final_output_list.len() {
-            output_list.push(final_output_list[[i, 0]]);
+    /// gradient of the output of `class` with respect to every input value, the saliency of the inputs
+    ///
+    /// it is backpropagated like in `train`, but from the output itself instead of from the error of the output.
+    pub fn input_gradient(&self, input_list: &[T], class: usize) -> Vec<T> {
+        if class >= self.output_nodes as usize {
+            panic!("class is not an output node");
         }
-        return output_list;
+        let (hidden_output_vec, final_output_vec) = self.forward(input_list);
+        let output = final_output_vec[[class, 0]];
+        // the gradient at the input of the output sigmoid, O * (1 - O)
+        let output_delta = output * (T::one() - output);
+        // the gradient at the input of the hidden activations, W_ho[class]^T * O * (1 - O) * f'(H)
+        let hidden_deltas_vec = self.weight_ho.row(class).mapv(|w| w * output_delta).insert_axis(Axis(1))
+            * hidden_output_vec.mapv(|h| self.activation.derivative(h));
+        self.weight_ih.t().dot(&hidden_deltas_vec).into_iter().collect()
     }
 
+    /// run a target backwards through the network with the inverse sigmoid, the input the network "thinks" of
+    ///
+    /// like the backquery of the book the targets are clamped to [0.01, 0.99] and the signals of every layer are
+    /// rescaled to [0.01, 0.99] before the logit, so it never sees 0 or 1. The returned inputs are in the same range.
+    /// A tanh hidden layer is inverted with atanh of the signal rescaled to [-0.98, 0.98], a relu layer passes the
+    /// rescaled signal unchanged.
+    pub fn backquery(&self, target_list: &[T]) -> Vec<T> {
+        if target_list.len() != self.output_nodes as usize {
+            panic!("target list length does not match output nodes");
+        }
+        let (low, high) = (T::from_f32(0.01), T::from_f32(0.99));
+        let final_output_vec = Matrix::from_shape_fn((target_list.len(), 1), |(i, _)| target_list[i].max(low).min(high));
+        // calculate the signal into the final output layer with the inverse of the activation function
+        let final_input_vec = final_output_vec.mapv(logit);
+        // calculate the signal out of the hidden layer and rescale it into the domain of the logit
+        let hidden_output_vec = rescale_for_logit(self.weight_ho.t().dot(&final_input_vec));
+        let hidden_input_vec = match self.activation {
+            Activation::Relu => hidden_output_vec,
+            Activation::Tanh => hidden_output_vec.mapv(|h| (h * T::from_f32(2.0) - T::one()).atanh()),
+            _ => hidden_output_vec.mapv(logit),
+        };
+        let input_vec = rescale_for_logit(self.weight_ih.t().dot(&hidden_input_vec));
+        input_vec.into_iter().collect()
+    }
+
+    /// the same network in another precision, e.g. `nn.cast::<f64>()` to compare a trained network in double precision
+    ///
+    /// the optimizer state is converted too, so training can continue in the new precision.
+    pub fn cast<U: Float>(&self) -> NeuralNetwork<U> {
+        let cast = |matrix: &Matrix<T>| matrix.mapv(|w| num_traits::cast::<T, U>(w).unwrap());
+        NeuralNetwork {
+            input_nodes: self.input_nodes,
+            hidden_nodes: self.hidden_nodes,
+            output_nodes: self.output_nodes,
+            learning_rate: self.learning_rate,
+            weight_ih: cast(&self.weight_ih),
+            weight_ho: cast(&self.weight_ho),
+            activation: self.activation,
+            regularization: self.regularization,
+            optimizer: self.optimizer,
+            optimizer_state: self.optimizer_state.as_ref().map(|state| OptimizerState {
+                velocity_ih: cast(&state.velocity_ih),
+                velocity_ho: cast(&state.velocity_ho),
+            }),
+            mode: self.mode,
+            rng: self.rng.clone(),
+        }
+    }
+}
+
+// 1 / (1 + e^(-x))
+fn sigmoid<T: Float>(x: T) -> T {
+    T::one() / (T::one() + (-x).exp())
+}
+
+// ln(x / (1 - x)), the inverse of the sigmoid function
+fn logit<T: Float>(x: T) -> T {
+    (x / (T::one() - x)).ln()
+}
+
+// scale the signals linearly into [0.01, 0.99], a constant signal becomes 0.5
+fn rescale_for_logit<T: Float>(signal: Matrix<T>) -> Matrix<T> {
+    let min = signal.iter().fold(T::infinity(), |min, &x| min.min(x));
+    let max = signal.iter().fold(T::neg_infinity(), |max, &x| max.max(x));
+    if !(max - min).is_normal() {
+        return signal.mapv(|_| T::from_f32(0.5));
+    }
+    signal.mapv(|x| (x - min) / (max - min) * T::from_f32(0.98) + T::from_f32(0.01))
+}
+
+// the f32 values of a `Sample` in the precision of the network
+fn to_float<T: Float>(values: &[f32]) -> Vec<T> {
+    values.iter().map(|&value| T::from_f32(value)).collect()
+}
+
+// the fields of a saved `NeuralNetwork`, which are checked before they become a network, so a hand edited or
+// truncated file is an error of `load` and not a panic in the first `predict`
+#[derive(Deserialize)]
+#[serde(bound = "T: Float")]
+struct SavedNetwork<T> {
+    input_nodes: i32,
+    hidden_nodes: i32,
+    output_nodes: i32,
+    learning_rate: f32,
+    weight_ih: Matrix<T>,
+    weight_ho: Matrix<T>,
+    // files saved before the activation could be chosen have a sigmoid hidden layer
+    #[serde(default)]
+    activation: Activation,
+    regularization: Regularization,
+    optimizer: Optimizer,
+    optimizer_state: Option<OptimizerState<T>>,
+    mode: Mode,
+    rng: ChaCha8Rng,
+}
+
+impl<T: Float> TryFrom<SavedNetwork<T>> for NeuralNetwork<T> {
+    type Error = String;
+
+    fn try_from(saved: SavedNetwork<T>) -> Result<Self, Self::Error> {
+        // softmax is rejected like by `set_activation`
+        if saved.activation == Activation::Softmax {
+            return Err("softmax can not be the activation of the hidden layer".to_string());
+        }
+        let (input, hidden, output) = (saved.input_nodes as usize, saved.hidden_nodes as usize, saved.output_nodes as usize);
+        let shapes = [
+            ("weight_ih", saved.weight_ih.dim(), (hidden, input)),
+            ("weight_ho", saved.weight_ho.dim(), (output, hidden)),
+        ];
+        let velocities = saved.optimizer_state.iter().flat_map(|state| [
+            ("velocity_ih", state.velocity_ih.dim(), (hidden, input)),
+            ("velocity_ho", state.velocity_ho.dim(), (output, hidden)),
+        ]);
+        for (name, shape, expected) in shapes.into_iter().chain(velocities) {
+            if shape != expected {
+                return Err(format!("{} has the shape {:?} instead of {:?} for {}-{}-{} nodes",
+                    name, shape, expected, saved.input_nodes, saved.hidden_nodes, saved.output_nodes));
+            }
+        }
+        Ok(NeuralNetwork {
+            input_nodes: saved.input_nodes,
+            hidden_nodes: saved.hidden_nodes,
+            output_nodes: saved.output_nodes,
+            learning_rate: saved.learning_rate,
+            weight_ih: saved.weight_ih,
+            weight_ho: saved.weight_ho,
+            activation: saved.activation,
+            regularization: saved.regularization,
+            optimizer: saved.optimizer,
+            optimizer_state: saved.optimizer_state,
+            mode: saved.mode,
+            rng: saved.rng,
+        })
+    }
+}
+
+/// the loss and the gradients of one weight update
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TrainStep<T = f32> {
+    /// mean squared error of the outputs before the update, the return value of `train`
+    pub loss: T,
+    /// L2 norm of the gradient of `weight_ih`, without the regularisation penalties
+    pub gradient_norm_ih: T,
+    /// L2 norm of the gradient of `weight_ho`, without the regularisation penalties
+    pub gradient_norm_ho: T,
+}
+
+// the weight updates (negative gradients of the squared error) of both weights matrices
+struct Gradients<T> {
+    ih: Matrix<T>,
+    ho: Matrix<T>,
+    loss: T,
+}
+
+impl<T: Float> Gradients<T> {
+    fn zeros(network: &NeuralNetwork<T>) -> Gradients<T> {
+        Gradients {
+            ih: Array::zeros(network.weight_ih.raw_dim()),
+            ho: Array::zeros(network.weight_ho.raw_dim()),
+            loss: T::zero(),
+        }
+    }
+
+    fn add(&mut self, other: &Gradients<T>) {
+        self.ih += &other.ih;
+        self.ho += &other.ho;
+        self.loss += other.loss;
+    }
+
+    fn scale(&mut self, factor: T) {
+        self.ih *= factor;
+        self.ho *= factor;
+        self.loss *= factor;
+    }
+
+    fn step(&self) -> TrainStep<T> {
+        let norm = |gradient: &Matrix<T>| gradient.iter().map(|&g| g * g).sum::<T>().sqrt();
+        TrainStep { loss: self.loss, gradient_norm_ih: norm(&self.ih), gradient_norm_ho: norm(&self.ho) }
+    }
 }
 
 #[cfg(test)]
@@ -117,8 +611,150 @@ mod tests {
         let mut nn = NeuralNetwork::new(3, 3, 3, 0.3);
         let input_list = vec![1.0, 0.5, -1.5];
         let target_list = vec![0.5, 1.0, 0.5];
-        nn.train(&input_list, &target_list);
+        let loss = nn.train(&input_list, &target_list);
+        let output_list = nn.predict(&input_list);
+        assert_eq!(output_list.len(), 3);
+        assert!(output_list.iter().all(|o| (0.0..=1.0).contains(o)));
+        // the loss after training is smaller than the loss of the outputs before the update
+        let error: f32 = output_list.iter().zip(&target_list).map(|(o, t)| (t - o) * (t - o)).sum::<f32>() / 3.0;
+        assert!(error < loss);
+    }
+
+    #[test]
+    fn weight_decay_shrinks_weights() {
+        let mut nn: NeuralNetwork = NeuralNetwork::new(3, 4, 2, 0.1);
+        nn.set_regularization(Regularization { l1: 0.0, l2: 1.0, dropout: 0.0 });
+        // a zero input produces no gradient for weight_ih, only the penalty changes it
+        let input_list = vec![0.0, 0.0, 0.0];
+        let norm_before = nn.weight_ih.mapv(|w| w * w).sum();
+        nn.train(&input_list, &[0.5, 0.5]);
+        let norm_after = nn.weight_ih.mapv(|w| w * w).sum();
+        assert!((norm_after - norm_before * 0.81).abs() < 1e-4);
+    }
+
+    #[test]
+    fn dropout_does_not_affect_predict() {
+        let mut nn = NeuralNetwork::new(3, 8, 2, 0.1);
+        nn.set_regularization(Regularization { l1: 0.0, l2: 0.0, dropout: 0.5 });
+        let input_list = vec![1.0, 0.5, -1.5];
         let output_list = nn.predict(&input_list);
-        println!("{:?}", output_list);
+        assert_eq!(output_list, nn.predict(&input_list));
+
+        // in eval mode the dropout is disabled, training is the same as training a network without dropout
+        nn.set_mode(Mode::Eval);
+        let mut plain = nn.clone();
+        plain.set_regularization(Regularization::default());
+        nn.train(&input_list, &[0.9, 0.1]);
+        plain.train(&input_list, &[0.9, 0.1]);
+        assert_eq!(nn.weight_ih, plain.weight_ih);
+        assert_eq!(nn.weight_ho, plain.weight_ho);
+        assert_eq!(nn.mode(), Mode::Eval);
+    }
+
+    #[test]
+    fn predict_variants_match_forward() {
+        let mut nn = NeuralNetwork::new_with_seed(4, 6, 3, 0.1, 4);
+        nn.set_activation(Activation::Tanh);
+        let inputs = Array::from_shape_fn((3, 4), |(i, j)| (i as f32 - j as f32) / 4.0);
+        let batch = nn.predict_batch(inputs.view());
+        let mut output_list = [0.0; 3];
+        for (row, input) in inputs.outer_iter().enumerate() {
+            let (_, expected) = nn.forward(input.as_slice().unwrap());
+            nn.predict_into(input.as_slice().unwrap(), &mut output_list);
+            let strided = inputs.t().to_owned();
+            let views = [nn.predict(input.as_slice().unwrap()), output_list.to_vec(),
+                nn.predict_view(strided.column(row)).to_vec(), batch.row(row).to_vec()];
+            for output in views {
+                assert!(output.iter().zip(expected.column(0)).all(|(o, e)| (o - e).abs() < 1e-6), "{:?} {}", output, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn softmax_hidden_activation_is_not_loaded() {
+        let mut saved = serde_json::to_value(NeuralNetwork::<f32>::new_with_seed(3, 2, 2, 0.1, 0)).unwrap();
+        saved["activation"] = serde_json::json!("Tanh");
+        assert_eq!(serde_json::from_value::<NeuralNetwork>(saved.clone()).unwrap().activation(), Activation::Tanh);
+        saved["activation"] = serde_json::json!("Softmax");
+        assert!(serde_json::from_value::<NeuralNetwork>(saved).unwrap_err().to_string().contains("softmax"));
+    }
+
+    #[test]
+    fn weights_which_do_not_fit_the_nodes_are_not_loaded() {
+        let mut nn = NeuralNetwork::<f32>::new_with_seed(3, 2, 2, 0.1, 0);
+        nn.set_optimizer(Optimizer::Momentum { momentum: 0.9 });
+        nn.train(&[0.1, 0.5, 0.9], &[0.99, 0.01]);
+        let saved = serde_json::to_value(&nn).unwrap();
+        assert!(serde_json::from_value::<NeuralNetwork>(saved.clone()).is_ok());
+        for (field, value) in [("input_nodes", 4), ("hidden_nodes", 3), ("output_nodes", 1)] {
+            let mut invalid = saved.clone();
+            invalid[field] = serde_json::json!(value);
+            assert!(serde_json::from_value::<NeuralNetwork>(invalid).unwrap_err().to_string().contains("shape"), "{}", field);
+        }
+        let mut invalid = saved;
+        invalid["optimizer_state"]["velocity_ho"] = serde_json::to_value(Matrix::<f32>::zeros((3, 2))).unwrap();
+        assert!(serde_json::from_value::<NeuralNetwork>(invalid).unwrap_err().to_string().contains("velocity_ho"));
+    }
+
+    #[test]
+    fn f64_network_matches_f32() {
+        let input_list = [0.9, 0.1, -0.5];
+        let target_list = [0.99, 0.01];
+        let mut single = NeuralNetwork::<f32>::new_with_seed(3, 4, 2, 0.3, 9);
+        let mut double = NeuralNetwork::<f64>::new_with_seed(3, 4, 2, 0.3, 9);
+        assert_eq!(single.cast::<f64>().weight_ih, double.weight_ih);
+        for _ in 0..10 {
+            single.train(&input_list, &target_list);
+            double.train(&input_list.map(f64::from), &target_list.map(f64::from));
+        }
+        let difference = single.cast::<f64>().weight_ho - &double.weight_ho;
+        assert!(difference.iter().all(|d| d.abs() < 1e-5));
+
+        // a round trip through f64 keeps the f32 network and its momentum state
+        single.set_optimizer(Optimizer::Momentum { momentum: 0.9 });
+        single.train(&input_list, &target_list);
+        let round_trip = single.cast::<f64>().cast::<f32>();
+        assert_eq!(round_trip.weight_ih, single.weight_ih);
+        assert_eq!(round_trip.optimizer_state, single.optimizer_state);
+        assert_eq!(round_trip.predict(&input_list), single.predict(&input_list));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn parallel_training_is_deterministic() {
+        let batch: Vec<Sample> = (0..10)
+            .map(|i| Sample { input: vec![i as f32 / 10.0, 0.5, -0.5], target: vec![0.99, 0.01], label: 0 })
+            .collect();
+        let train = |threads| {
+            let mut nn: NeuralNetwork = NeuralNetwork::new_with_seed(3, 8, 2, 0.3, 42);
+            nn.set_regularization(Regularization { l1: 0.0, l2: 0.0, dropout: 0.2 });
+            nn.train_batch(&batch, threads);
+            nn.train_batch(&batch, threads);
+            nn
+        };
+        let (a, b, single) = (train(3), train(3), train(1));
+        assert_eq!(a.weight_ih, b.weight_ih);
+        assert_eq!(a.weight_ho, b.weight_ho);
+        // the reduction order differs from a single thread only by rounding
+        assert!(a.weight_ho.iter().zip(single.weight_ho.iter()).all(|(x, y)| (x - y).abs() < 1e-5));
+    }
+
+    #[test]
+    fn backquery_shows_the_trained_pattern() {
+        // the first output is trained on the first input and the second output on the second input
+        let mut nn: NeuralNetwork = NeuralNetwork::new_with_seed(3, 4, 2, 0.3, 3);
+        for _ in 0..500 {
+            nn.train(&[0.99, 0.01, 0.5], &[0.99, 0.01]);
+            nn.train(&[0.01, 0.99, 0.5], &[0.01, 0.99]);
+        }
+        let first = nn.backquery(&[0.99, 0.01]);
+        let second = nn.backquery(&[0.0, 1.0]);
+        assert!(first[0] > first[1] && second[1] > second[0], "{:?} {:?}", first, second);
+        for inputs in [&first, &second] {
+            assert!(inputs.iter().all(|x| (0.01 - 1e-6..=0.99 + 1e-6).contains(x)));
+        }
+
+        // without any signal every input is in the middle of the range instead of NaN
+        let zero: NeuralNetwork = NeuralNetwork::from_weights(Matrix::zeros((4, 3)), Matrix::zeros((2, 4)), 0.1);
+        assert_eq!(zero.backquery(&[1.0, 0.0]), vec![0.5; 3]);
+    }
+}
@@ -0,0 +1,220 @@
+//! multi-threaded data loading
+//!
+//! the shards are parsed by worker threads and the batches are prefetched through bounded channels,
+//! so parsing the next batches overlaps with training on the current one. The batches are always
+//! returned in shard and row order, whatever the number of workers is.
+
+use std::path::PathBuf;
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::thread::{self, JoinHandle};
+
+use crate::dataset::{mnist_csv_samples, DatasetError, Sample};
+
+/// a batch of consecutive samples from one shard
+#[derive(Debug, Clone)]
+pub struct Batch {
+    /// index of the shard in the loader's shard list
+    pub shard: usize,
+    /// row of the first sample of the batch in its shard
+    pub row: usize,
+    pub samples: Vec<Sample>,
+}
+
+enum Message {
+    Batch(Batch),
+    Error(DatasetError),
+    EndOfShard,
+}
+
+/// builder of a `DataLoader`
+#[derive(Debug, Clone)]
+pub struct DataLoaderBuilder {
+    shards: Vec<PathBuf>,
+    batch_size: usize,
+    workers: usize,
+    prefetch: usize,
+}
+
+impl DataLoaderBuilder {
+    /// number of samples in a batch, the last batch of a shard can be smaller, 0 is used as 1
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size.max(1);
+        self
+    }
+
+    /// number of worker threads parsing the shards, 0 is used as 1
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// number of batches each worker can parse ahead before it blocks
+    pub fn prefetch(mut self, prefetch: usize) -> Self {
+        self.prefetch = prefetch;
+        self
+    }
+
+    /// start the worker threads
+    pub fn build(self) -> DataLoader {
+        let workers = self.workers.min(self.shards.len()).max(1);
+        let mut receivers = vec![];
+        let mut handles = vec![];
+        for worker in 0..workers {
+            let (sender, receiver) = sync_channel(self.prefetch);
+            // worker `w` parses the shards w, w + workers, w + 2 * workers, ...
+            let shards: Vec<(usize, PathBuf)> = self.shards.iter().cloned().enumerate()
+                .skip(worker)
+                .step_by(workers)
+                .collect();
+            let batch_size = self.batch_size;
+            handles.push(thread::spawn(move || load_shards(shards, batch_size, sender)));
+            receivers.push(receiver);
+        }
+        DataLoader {
+            receivers,
+            handles,
+            shard: 0,
+            shard_count: self.shards.len(),
+        }
+    }
+}
+
+/// iterator over the batches of a list of csv shards, parsed on worker threads
+#[derive(Debug)]
+pub struct DataLoader {
+    receivers: Vec<Receiver<Message>>,
+    handles: Vec<JoinHandle<()>>,
+    // the shard which is currently read
+    shard: usize,
+    shard_count: usize,
+}
+
+impl DataLoader {
+    /// create a loader builder for the MNIST csv shards,
+    /// by default 2 workers prefetch up to 4 batches of 100 samples each
+    pub fn builder(shards: Vec<PathBuf>) -> DataLoaderBuilder {
+        DataLoaderBuilder {
+            shards,
+            batch_size: 100,
+            workers: 2,
+            prefetch: 4,
+        }
+    }
+}
+
+impl Iterator for DataLoader {
+    type Item = Result<Batch, DatasetError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.shard < self.shard_count {
+            let receiver = &self.receivers[self.shard % self.receivers.len()];
+            match receiver.recv() {
+                Ok(Message::Batch(batch)) => return Some(Ok(batch)),
+                Ok(Message::Error(err)) => {
+                    // the worker gives up the shard after an error
+                    self.shard += 1;
+                    return Some(Err(err));
+                }
+                Ok(Message::EndOfShard) => self.shard += 1,
+                Err(_) => panic!("data loader worker stopped unexpectedly"),
+            }
+        }
+        None
+    }
+}
+
+impl Drop for DataLoader {
+    fn drop(&mut self) {
+        // disconnect the channels first, so the blocked workers return from `send`
+        self.receivers.clear();
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn load_shards(shards: Vec<(usize, PathBuf)>, batch_size: usize, sender: SyncSender<Message>) {
+    for (shard, path) in shards {
+        let samples = match mnist_csv_samples(&path) {
+            Ok(samples) => samples,
+            Err(err) => {
+                if sender.send(Message::Error(err)).is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+        let mut batch = Batch { shard, row: 0, samples: Vec::with_capacity(batch_size) };
+        let mut failed = false;
+        for (row, sample) in samples.enumerate() {
+            match sample {
+                Ok(sample) => batch.samples.push(sample),
+                Err(err) => {
+                    failed = true;
+                    if sender.send(Message::Error(err)).is_err() {
+                        return;
+                    }
+                    break;
+                }
+            }
+            if batch.samples.len() == batch_size {
+                let next = Batch { shard, row: row + 1, samples: Vec::with_capacity(batch_size) };
+                if sender.send(Message::Batch(std::mem::replace(&mut batch, next))).is_err() {
+                    return;
+                }
+            }
+        }
+        if failed {
+            continue;
+        }
+        if !batch.samples.is_empty() && sender.send(Message::Batch(batch)).is_err() {
+            return;
+        }
+        if sender.send(Message::EndOfShard).is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn batches_are_in_shard_order() {
+        let dir = std::env::temp_dir().join(format!("nn-loader-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut shards = vec![];
+        for shard in 0..5 {
+            let path = dir.join(format!("file{}.csv", shard));
+            let mut contents = String::new();
+            for row in 0..7 {
+                let label = (shard + row) % 10;
+                contents.push_str(&format!("{}{}\n", label, ",0".repeat(28 * 28)));
+            }
+            fs::write(&path, contents).unwrap();
+            shards.push(path);
+        }
+
+        let batches: Vec<Batch> = DataLoader::builder(shards.clone())
+            .batch_size(3)
+            .workers(3)
+            .prefetch(1)
+            .build()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        // 0 workers and a batch size of 0 are used as 1 instead of a panic
+        let single = DataLoader::builder(shards).batch_size(0).workers(0).build().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(single, 35);
+
+        // 7 rows per shard are split into batches of 3, 3 and 1 samples
+        assert_eq!(batches.len(), 15);
+        for (i, batch) in batches.iter().enumerate() {
+            assert_eq!(batch.shard, i / 3);
+            assert_eq!(batch.row, (i % 3) * 3);
+            assert_eq!(batch.samples[0].label as usize, (batch.shard + batch.row) % 10);
+        }
+    }
+}
@@ -0,0 +1,331 @@
+//! training metrics written to CSV, JSON Lines or TensorBoard event files while a `Trainer` runs
+//!
+//! a `MetricsLogger` sends every record to all of its sinks, so the runs can be compared and graphed offline.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::protobuf::Encoder;
+
+/// one record of a training run, written every few weight updates and at the end of every epoch
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Metrics {
+    /// number of weight updates so far
+    pub step: u64,
+    pub epoch: usize,
+    /// the shard of the last update, with `epoch` the position of the run in the training data
+    pub shard: usize,
+    /// mean training loss of the updates since the previous record
+    pub loss: f32,
+    /// accuracy of the validation split, only in the record at the end of an epoch with a validation split
+    pub accuracy: Option<f32>,
+    /// loss of the validation split, like `accuracy`
+    pub validation_loss: Option<f32>,
+    /// learning rate of the last update
+    pub learning_rate: f32,
+    /// mean gradient norm of `weight_ih` of the updates since the previous record, see `TrainStep`
+    pub gradient_norm_ih: f32,
+    /// mean gradient norm of `weight_ho`, like `gradient_norm_ih`
+    pub gradient_norm_ho: f32,
+}
+
+/// destination of the records of a `MetricsLogger`
+pub trait MetricsSink {
+    /// write one record, the record should be readable by other programs when this returns
+    fn write(&mut self, metrics: &Metrics) -> io::Result<()>;
+}
+
+/// sends the records of a training run to several sinks
+///
+/// the `Trainer` calls `log` every `every_steps` weight updates and at the end of every epoch.
+pub struct MetricsLogger {
+    every_steps: u64,
+    sinks: Vec<Box<dyn MetricsSink + Send>>,
+}
+
+impl fmt::Debug for MetricsLogger {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricsLogger").field("every_steps", &self.every_steps).field("sinks", &self.sinks.len()).finish()
+    }
+}
+
+impl MetricsLogger {
+    /// create a logger without sinks which records the metrics every `every_steps` weight updates
+    pub fn new(every_steps: u64) -> MetricsLogger {
+        MetricsLogger { every_steps: every_steps.max(1), sinks: vec![] }
+    }
+
+    /// add a sink
+    pub fn sink<S: MetricsSink + Send + 'static>(mut self, sink: S) -> Self {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// number of weight updates between two records
+    pub fn every_steps(&self) -> u64 {
+        self.every_steps
+    }
+
+    /// write a record to all sinks
+    pub fn log(&mut self, metrics: &Metrics) -> io::Result<()> {
+        for sink in &mut self.sinks {
+            sink.write(metrics)?;
+        }
+        Ok(())
+    }
+}
+
+/// sends the records to another thread, e.g. to show the progress of a training run while it trains
+impl MetricsSink for Sender<Metrics> {
+    fn write(&mut self, metrics: &Metrics) -> io::Result<()> {
+        self.send(*metrics).map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "the metrics receiver is gone"))
+    }
+}
+
+/// csv file with a header line, the validation columns are empty except at the end of an epoch
+pub struct CsvSink<W: Write> {
+    writer: csv::Writer<W>,
+}
+
+impl CsvSink<File> {
+    /// create or truncate the csv file
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<CsvSink<File>> {
+        Ok(CsvSink::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> CsvSink<W> {
+    pub fn new(writer: W) -> CsvSink<W> {
+        CsvSink { writer: csv::Writer::from_writer(writer) }
+    }
+
+    /// the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner().map_err(|err| err.into_error()).unwrap()
+    }
+}
+
+impl<W: Write> MetricsSink for CsvSink<W> {
+    fn write(&mut self, metrics: &Metrics) -> io::Result<()> {
+        self.writer.serialize(metrics)?;
+        self.writer.flush()
+    }
+}
+
+/// one JSON object per line, the validation fields are `null` except at the end of an epoch
+pub struct JsonlSink<W: Write> {
+    writer: W,
+}
+
+impl JsonlSink<BufWriter<File>> {
+    /// create or truncate the JSON Lines file
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<JsonlSink<BufWriter<File>>> {
+        Ok(JsonlSink::new(BufWriter::new(File::create(path)?)))
+    }
+}
+
+impl<W: Write> JsonlSink<W> {
+    pub fn new(writer: W) -> JsonlSink<W> {
+        JsonlSink { writer }
+    }
+
+    /// the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> MetricsSink for JsonlSink<W> {
+    fn write(&mut self, metrics: &Metrics) -> io::Result<()> {
+        serde_json::to_writer(&mut self.writer, metrics)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()
+    }
+}
+
+/// TensorBoard event file, `tensorboard --logdir <dir>` shows the scalars
+///
+/// every record is an `Event` protocol buffer with one `simple_value` per metric, tagged `train/loss`,
+/// `train/learning_rate`, `train/gradient_norm_ih`, `train/gradient_norm_ho`, `validation/loss` and
+/// `validation/accuracy`. The events are framed as TFRecords.
+pub struct TensorBoardSink<W: Write> {
+    writer: W,
+}
+
+impl TensorBoardSink<BufWriter<File>> {
+    /// create the log directory and a new event file in it, named like TensorFlow names them
+    pub fn create<P: AsRef<Path>>(log_dir: P) -> io::Result<TensorBoardSink<BufWriter<File>>> {
+        fs::create_dir_all(&log_dir)?;
+        let path = event_file_path(log_dir.as_ref(), wall_time() as u64, &hostname());
+        TensorBoardSink::new(BufWriter::new(File::create(path)?))
+    }
+}
+
+impl<W: Write> TensorBoardSink<W> {
+    /// write the file version event which starts every event file
+    pub fn new(writer: W) -> io::Result<TensorBoardSink<W>> {
+        let mut sink = TensorBoardSink { writer };
+        let mut event = Encoder::new();
+        event.double(1, wall_time()).varint(2, 0).string(3, "brain.Event:2");
+        sink.write_record(&event.into_bytes())?;
+        sink.writer.flush()?;
+        Ok(sink)
+    }
+
+    /// the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    // TFRecord framing: length, masked crc of the length, data, masked crc of the data
+    fn write_record(&mut self, data: &[u8]) -> io::Result<()> {
+        let length = (data.len() as u64).to_le_bytes();
+        self.writer.write_all(&length)?;
+        self.writer.write_all(&masked_crc32c(&length).to_le_bytes())?;
+        self.writer.write_all(data)?;
+        self.writer.write_all(&masked_crc32c(data).to_le_bytes())
+    }
+}
+
+impl<W: Write> MetricsSink for TensorBoardSink<W> {
+    fn write(&mut self, metrics: &Metrics) -> io::Result<()> {
+        let mut scalars = vec![
+            ("train/loss", metrics.loss),
+            ("train/learning_rate", metrics.learning_rate),
+            ("train/gradient_norm_ih", metrics.gradient_norm_ih),
+            ("train/gradient_norm_ho", metrics.gradient_norm_ho),
+        ];
+        if let Some(loss) = metrics.validation_loss {
+            scalars.push(("validation/loss", loss));
+        }
+        if let Some(accuracy) = metrics.accuracy {
+            scalars.push(("validation/accuracy", accuracy));
+        }
+
+        let mut event = Encoder::new();
+        event.double(1, wall_time()).varint(2, metrics.step as i64).message(5, |summary| {
+            for (tag, value) in scalars {
+                summary.message(1, |v| {
+                    v.string(1, tag).float(2, value);
+                });
+            }
+        });
+        self.write_record(&event.into_bytes())?;
+        self.writer.flush()
+    }
+}
+
+// events.out.tfevents.<seconds>.<host>, TensorBoard only reads files whose name contains "tfevents"
+fn event_file_path(log_dir: &Path, seconds: u64, host: &str) -> PathBuf {
+    log_dir.join(format!("events.out.tfevents.{}.{}", seconds, host))
+}
+
+fn wall_time() -> f64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0.0, |time| time.as_secs_f64())
+}
+
+fn hostname() -> String {
+    fs::read_to_string("/etc/hostname")
+        .ok()
+        .or_else(|| std::env::var("HOSTNAME").ok())
+        .or_else(|| std::env::var("COMPUTERNAME").ok())
+        .map(|host| host.trim().to_string())
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| "localhost".to_string())
+}
+
+// crc32c (Castagnoli) as TFRecords use it, masked so that the crc of data containing crcs stays well distributed
+fn masked_crc32c(data: &[u8]) -> u32 {
+    let crc = crc32c(data);
+    crc.rotate_right(15).wrapping_add(0xa282ead8)
+}
+
+fn crc32c(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0x82f63b78 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protobuf::{Decoder, Value};
+
+    fn metrics(step: u64, accuracy: Option<f32>) -> Metrics {
+        Metrics {
+            step,
+            epoch: 0,
+            shard: 1,
+            loss: 0.5,
+            accuracy,
+            validation_loss: accuracy.map(|_| 0.25),
+            learning_rate: 0.1,
+            gradient_norm_ih: 2.0,
+            gradient_norm_ho: 1.0,
+        }
+    }
+
+    #[test]
+    fn sinks_write_every_record() {
+        let mut csv = CsvSink::new(vec![]);
+        let mut jsonl = JsonlSink::new(vec![]);
+        for sink in [&mut csv as &mut dyn MetricsSink, &mut jsonl] {
+            sink.write(&metrics(10, None)).unwrap();
+            sink.write(&metrics(20, Some(0.75))).unwrap();
+        }
+        let csv = String::from_utf8(csv.into_inner()).unwrap();
+        assert_eq!(csv.lines().collect::<Vec<_>>(), vec![
+            "step,epoch,shard,loss,accuracy,validation_loss,learning_rate,gradient_norm_ih,gradient_norm_ho",
+            "10,0,1,0.5,,,0.1,2.0,1.0",
+            "20,0,1,0.5,0.75,0.25,0.1,2.0,1.0",
+        ]);
+        let jsonl = String::from_utf8(jsonl.into_inner()).unwrap();
+        let records: Vec<Metrics> = jsonl.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        assert_eq!(records, vec![metrics(10, None), metrics(20, Some(0.75))]);
+    }
+
+    #[test]
+    fn tensorboard_records_are_framed_events() {
+        // the crc32c check value of the iSCSI specification
+        assert_eq!(crc32c(b"123456789"), 0xe3069283);
+        assert!(event_file_path(Path::new("runs"), 1700000000, "host").ends_with("events.out.tfevents.1700000000.host"));
+
+        let mut sink = TensorBoardSink::new(vec![]).unwrap();
+        sink.write(&metrics(20, Some(0.75))).unwrap();
+        let mut bytes = &sink.into_inner()[..];
+        let mut events = vec![];
+        while !bytes.is_empty() {
+            let length = u64::from_le_bytes(bytes[..8].try_into().unwrap()) as usize;
+            assert_eq!(u32::from_le_bytes(bytes[8..12].try_into().unwrap()), masked_crc32c(&bytes[..8]));
+            let data = &bytes[12..12 + length];
+            assert_eq!(u32::from_le_bytes(bytes[12 + length..16 + length].try_into().unwrap()), masked_crc32c(data));
+            events.push(data);
+            bytes = &bytes[16 + length..];
+        }
+        assert_eq!(events.len(), 2);
+
+        let version: Vec<(u32, Value)> = Decoder::new(events[0]).collect::<Result<_, _>>().unwrap();
+        assert_eq!(version[2].1.as_string().unwrap(), "brain.Event:2");
+        let event: Vec<(u32, Value)> = Decoder::new(events[1]).collect::<Result<_, _>>().unwrap();
+        assert_eq!(event[1], (2, Value::Varint(20)));
+        let values: Vec<(String, f32)> = Decoder::new(event[2].1.as_bytes().unwrap())
+            .map(|value| {
+                let fields: Vec<(u32, Value)> = Decoder::new(value.unwrap().1.as_bytes().unwrap()).collect::<Result<_, _>>().unwrap();
+                (fields[0].1.as_string().unwrap(), fields[1].1.as_f32().unwrap())
+            })
+            .collect();
+        assert_eq!(values.len(), 6);
+        assert_eq!(values[0], ("train/loss".to_string(), 0.5));
+        assert_eq!(values[5], ("validation/accuracy".to_string(), 0.75));
+    }
+}
@@ -0,0 +1,302 @@
+//! the predict interface shared by every kind of trained model, so evaluation and the GUI accept any of them
+//!
+//! `AnyModel` holds one of the models of this crate and loads it from any model file.
+
+use ndarray::{Array2, ArrayView2, Axis};
+use serde::de::IgnoredAny;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use crate::ensemble::Ensemble;
+use crate::layers::{Layer, Tensor};
+use crate::onnx;
+use crate::persist::PersistError;
+use crate::quantize::QuantizedNetwork;
+use crate::sequential::{flatten_batch, Sequential};
+use crate::NeuralNetwork;
+
+/// what a model is, e.g. to show it next to its predictions
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct Metadata {
+    /// the kind of model, e.g. "mlp" or "ensemble"
+    pub kind: String,
+    /// the layers or the members, e.g. "784-200-10 sigmoid"
+    pub description: String,
+    /// number of weights
+    pub parameters: usize,
+}
+
+impl fmt::Display for Metadata {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}, {} parameters", self.kind, self.description, self.parameters)
+    }
+}
+
+/// a trained model which maps a flat input list to an output list with one value per class
+pub trait Model {
+    /// output of the model for one sample
+    fn predict(&self, input_list: &[f32]) -> Vec<f32>;
+
+    /// write the outputs for one sample to `output_list`, models which can predict without allocating override it
+    fn predict_into(&self, input_list: &[f32], output_list: &mut [f32]) {
+        output_list.copy_from_slice(&self.predict(input_list));
+    }
+
+    /// outputs of a `[batch, inputs]` batch, one row per sample, models with a faster batch pass override it
+    fn predict_batch(&self, inputs: ArrayView2<f32>) -> Array2<f32> {
+        let outputs: Vec<Vec<f32>> = inputs.outer_iter().map(|row| self.predict(&row.to_vec())).collect();
+        let cols = outputs.first().map_or(0, Vec::len);
+        Array2::from_shape_vec((outputs.len(), cols), outputs.concat()).unwrap()
+    }
+
+    /// shape of one input sample, e.g. `[784]`
+    fn input_shape(&self) -> Vec<usize>;
+
+    /// shape of one output sample, e.g. `[10]`
+    fn output_shape(&self) -> Vec<usize>;
+
+    fn metadata(&self) -> Metadata;
+}
+
+impl Model for NeuralNetwork {
+    fn predict(&self, input_list: &[f32]) -> Vec<f32> {
+        NeuralNetwork::predict(self, input_list)
+    }
+
+    fn predict_into(&self, input_list: &[f32], output_list: &mut [f32]) {
+        NeuralNetwork::predict_into(self, input_list, output_list)
+    }
+
+    fn predict_batch(&self, inputs: ArrayView2<f32>) -> Array2<f32> {
+        NeuralNetwork::predict_batch(self, inputs)
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        vec![self.input_nodes as usize]
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        vec![self.output_nodes as usize]
+    }
+
+    fn metadata(&self) -> Metadata {
+        let (weight_ih, weight_ho) = self.weights();
+        Metadata {
+            kind: "mlp".to_string(),
+            description: format!("{}-{}-{} {}", self.input_nodes, self.hidden_nodes, self.output_nodes, self.activation),
+            parameters: weight_ih.len() + weight_ho.len(),
+        }
+    }
+}
+
+impl Model for Ensemble {
+    fn predict(&self, input_list: &[f32]) -> Vec<f32> {
+        Ensemble::predict(self, input_list)
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        self.members()[0].input_shape()
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        self.members()[0].output_shape()
+    }
+
+    fn metadata(&self) -> Metadata {
+        let members: Vec<String> = self.members().iter().map(|member| member.metadata().description).collect();
+        Metadata {
+            kind: "ensemble".to_string(),
+            description: format!("{:?} of {}", self.combination(), members.join(", ")),
+            parameters: self.members().iter().map(|member| member.metadata().parameters).sum(),
+        }
+    }
+}
+
+impl Model for QuantizedNetwork {
+    fn predict(&self, input_list: &[f32]) -> Vec<f32> {
+        QuantizedNetwork::predict(self, input_list)
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        vec![self.weights().0.dim().1]
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        vec![self.weights().1.dim().0]
+    }
+
+    fn metadata(&self) -> Metadata {
+        let ((hidden, inputs), (outputs, _)) = (self.weights().0.dim(), self.weights().1.dim());
+        Metadata {
+            kind: "int8 mlp".to_string(),
+            description: format!("{}-{}-{} {}", inputs, hidden, outputs, self.activation()),
+            parameters: inputs * hidden + hidden * outputs,
+        }
+    }
+}
+
+impl Model for Sequential {
+    fn predict(&self, input_list: &[f32]) -> Vec<f32> {
+        Sequential::predict(self, input_list)
+    }
+
+    fn predict_batch(&self, inputs: ArrayView2<f32>) -> Array2<f32> {
+        let mut shape = vec![inputs.len_of(Axis(0))];
+        shape.extend(self.input_shape());
+        let batch = Tensor::from_shape_vec(shape, inputs.iter().copied().collect()).expect("inputs do not match the input shape");
+        flatten_batch(&self.forward(&batch))
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        Sequential::input_shape(self).to_vec()
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        Sequential::output_shape(self)
+    }
+
+    fn metadata(&self) -> Metadata {
+        Metadata {
+            kind: "sequential".to_string(),
+            description: format!("{:?} -> {:?}, {} layers", self.input_shape(), self.output_shape(), self.layers().len()),
+            parameters: self.layers().iter().map(Layer::parameter_count).sum(),
+        }
+    }
+}
+
+/// any of the models of this crate
+#[derive(Debug, Clone)]
+// there are only a few models alive at a time, boxing the network would not save anything
+#[allow(clippy::large_enum_variant)]
+pub enum AnyModel {
+    Network(NeuralNetwork),
+    Ensemble(Ensemble),
+    Quantized(QuantizedNetwork),
+    /// a `Sequential` model, also the one imported from an ONNX file
+    Sequential(Sequential),
+}
+
+macro_rules! dispatch {
+    ($self:ident, $model:ident => $call:expr) => {
+        match $self {
+            AnyModel::Network($model) => $call,
+            AnyModel::Ensemble($model) => $call,
+            AnyModel::Quantized($model) => $call,
+            AnyModel::Sequential($model) => $call,
+        }
+    };
+}
+
+impl AnyModel {
+    /// load a model file of any kind: an ONNX file by its `.onnx` extension, a quantised model by its header,
+    /// otherwise a JSON file written by `NeuralNetwork::save`, `Ensemble::save` or `Sequential::save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<AnyModel, PersistError> {
+        let path = path.as_ref();
+        if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("onnx")) {
+            return onnx::load(path).map(AnyModel::Sequential).map_err(|err| PersistError::Invalid(err.to_string()));
+        }
+        let bytes = fs::read(path)?;
+        if bytes.starts_with(b"NNQ8") {
+            return QuantizedNetwork::from_bytes(&bytes).map(AnyModel::Quantized);
+        }
+        // the kind is told by a field only it has, so a corrupt file reports the error of its own parser
+        let kind: JsonKind = serde_json::from_slice(&bytes)?;
+        let model = match (kind.members, kind.layers) {
+            (Some(_), _) => serde_json::from_slice(&bytes).map(AnyModel::Ensemble),
+            (None, Some(_)) => serde_json::from_slice(&bytes).map(AnyModel::Sequential),
+            (None, None) => serde_json::from_slice(&bytes).map(AnyModel::Network),
+        };
+        model.map_err(PersistError::from)
+    }
+}
+
+// the top level fields of a JSON model file which tell an ensemble and a `Sequential` model from a network
+#[derive(Deserialize)]
+struct JsonKind {
+    members: Option<IgnoredAny>,
+    layers: Option<IgnoredAny>,
+}
+
+impl Model for AnyModel {
+    fn predict(&self, input_list: &[f32]) -> Vec<f32> {
+        dispatch!(self, model => Model::predict(model, input_list))
+    }
+
+    fn predict_into(&self, input_list: &[f32], output_list: &mut [f32]) {
+        dispatch!(self, model => Model::predict_into(model, input_list, output_list))
+    }
+
+    fn predict_batch(&self, inputs: ArrayView2<f32>) -> Array2<f32> {
+        dispatch!(self, model => Model::predict_batch(model, inputs))
+    }
+
+    fn input_shape(&self) -> Vec<usize> {
+        dispatch!(self, model => Model::input_shape(model))
+    }
+
+    fn output_shape(&self) -> Vec<usize> {
+        dispatch!(self, model => Model::output_shape(model))
+    }
+
+    fn metadata(&self) -> Metadata {
+        dispatch!(self, model => model.metadata())
+    }
+}
+
+macro_rules! impl_from {
+    ($($variant:ident => $model:ident),*) => {
+        $(impl From<$model> for AnyModel {
+            fn from(model: $model) -> Self {
+                AnyModel::$variant(model)
+            }
+        })*
+    };
+}
+
+impl_from!(Network => NeuralNetwork, Ensemble => Ensemble, Quantized => QuantizedNetwork, Sequential => Sequential);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::persist;
+
+    #[test]
+    fn every_model_predicts_through_the_trait() {
+        let network = NeuralNetwork::new_with_seed(4, 5, 3, 0.1, 3);
+        let inputs = Array2::from_shape_fn((2, 4), |(i, j)| (i * 4 + j) as f32 / 8.0);
+        let expected = network.predict(&[0.0, 0.125, 0.25, 0.375]);
+        let models: Vec<AnyModel> = vec![
+            network.clone().into(),
+            Ensemble::new(vec![network.clone(), network.clone()]).unwrap().into(),
+            QuantizedNetwork::from_network(&network).into(),
+            Sequential::from(&network).into(),
+        ];
+        for model in &models {
+            assert_eq!((model.input_shape(), model.output_shape()), (vec![4], vec![3]));
+            let batch = model.predict_batch(inputs.view());
+            assert_eq!(batch.dim(), (2, 3));
+            for (b, e) in batch.row(0).iter().zip(&expected) {
+                assert!((b - e).abs() < 1e-2, "{}", model.metadata());
+            }
+            assert!((batch[[1, 2]] - model.predict(&[0.5, 0.625, 0.75, 0.875])[2]).abs() < 1e-5);
+        }
+        assert_eq!(models[0].metadata(), Metadata { kind: "mlp".to_string(), description: "4-5-3 sigmoid".to_string(), parameters: 35 });
+        assert_eq!(models[1].metadata().parameters, 70);
+        assert_eq!(models[3].metadata().parameters, 35 + 5 + 3);
+
+        let dir = std::env::temp_dir().join(format!("nn-model-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        network.save(dir.join("m.model")).unwrap();
+        persist::save_json(&Sequential::from(&network), dir.join("s.model")).unwrap();
+        QuantizedNetwork::from_network(&network).save(dir.join("m.q8")).unwrap();
+        onnx::save_network(&network, dir.join("m.onnx")).unwrap();
+        let kinds: Vec<String> = ["m.model", "s.model", "m.q8", "m.onnx"].iter()
+            .map(|file| AnyModel::load(dir.join(file)).unwrap().metadata().kind).collect();
+        assert_eq!(kinds, ["mlp", "sequential", "int8 mlp", "sequential"]);
+        fs::write(dir.join("e.model"), r#"{"members": [], "combination": "Average"}"#).unwrap();
+        assert!(AnyModel::load(dir.join("e.model")).unwrap_err().to_string().contains("an ensemble needs members"));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+//! NumPy `.npy` and `.npz` files of the weights matrices
+//!
+//! the Python version of the book keeps the weights in `n.wih` and `n.who`, with the same hidden*input and
+//! output*hidden layout as `NeuralNetwork`. `numpy.save`/`numpy.load` read and write a single `.npy` matrix,
+//! `numpy.savez`/`numpy.load` an `.npz` archive with both matrices as `wih.npy` and `who.npy`.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem;
+use std::path::Path;
+use zip::result::ZipError;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::{Float, Matrix, NeuralNetwork};
+
+const MAGIC: &[u8; 6] = b"\x93NUMPY";
+
+/// error while reading or writing a NumPy file
+#[derive(Debug)]
+pub enum NpyError {
+    Io(io::Error),
+    Zip(ZipError),
+    /// the file is not a float matrix in the `.npy` format
+    Invalid(String),
+}
+
+impl fmt::Display for NpyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NpyError::Io(err) => write!(f, "can not access the NumPy file: {}", err),
+            NpyError::Zip(err) => write!(f, "invalid npz archive: {}", err),
+            NpyError::Invalid(message) => write!(f, "invalid npy file: {}", message),
+        }
+    }
+}
+
+impl Error for NpyError {}
+
+impl From<io::Error> for NpyError {
+    fn from(err: io::Error) -> Self {
+        NpyError::Io(err)
+    }
+}
+
+impl From<ZipError> for NpyError {
+    fn from(err: ZipError) -> Self {
+        NpyError::Zip(err)
+    }
+}
+
+/// write a matrix in the `.npy` format, as `<f4` for f32 and `<f8` for f64 values in C order
+pub fn write_npy<T: Float, W: Write>(mut writer: W, matrix: &Matrix<T>) -> io::Result<()> {
+    let size = mem::size_of::<T>();
+    let (rows, cols) = matrix.dim();
+    let mut header = format!("{{'descr': '<f{}', 'fortran_order': False, 'shape': ({}, {}), }}", size, rows, cols);
+    // like numpy the header is padded with spaces and ends with a newline, so the data starts at a multiple of 64
+    let len = MAGIC.len() + 4 + header.len() + 1;
+    header.push_str(&" ".repeat((64 - len % 64) % 64));
+    header.push('\n');
+
+    writer.write_all(MAGIC)?;
+    writer.write_all(&[1, 0])?;
+    writer.write_all(&(header.len() as u16).to_le_bytes())?;
+    writer.write_all(header.as_bytes())?;
+    for value in matrix.iter() {
+        if size == 4 {
+            writer.write_all(&value.as_f32().to_le_bytes())?;
+        } else {
+            writer.write_all(&value.to_f64().unwrap().to_le_bytes())?;
+        }
+    }
+    Ok(())
+}
+
+/// read a 2 dimensional `.npy` matrix of f4 or f8 values, in C or Fortran order and either byte order
+pub fn read_npy<T: Float, R: Read>(mut reader: R) -> Result<Matrix<T>, NpyError> {
+    let mut prefix = [0u8; 8];
+    reader.read_exact(&mut prefix)?;
+    if &prefix[..6] != MAGIC {
+        return Err(NpyError::Invalid("missing the \\x93NUMPY magic string".to_string()));
+    }
+    // version 1 has a 2 byte header length, version 2 and 3 a 4 byte one
+    let header_len = match prefix[6] {
+        1 => {
+            let mut len = [0u8; 2];
+            reader.read_exact(&mut len)?;
+            u16::from_le_bytes(len) as usize
+        }
+        2 | 3 => {
+            let mut len = [0u8; 4];
+            reader.read_exact(&mut len)?;
+            u32::from_le_bytes(len) as usize
+        }
+        version => return Err(NpyError::Invalid(format!("unsupported version {}", version))),
+    };
+    let mut header = vec![0u8; header_len];
+    reader.read_exact(&mut header)?;
+    let header = String::from_utf8_lossy(&header);
+
+    let descr = header_value(&header, "descr")?.trim_start_matches('\'').split('\'').next().unwrap_or_default().to_string();
+    let fortran_order = header_value(&header, "fortran_order")?.starts_with("True");
+    let shape: Vec<usize> = header_value(&header, "shape")?
+        .trim_start_matches('(')
+        .split(')')
+        .next()
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|dim| !dim.is_empty())
+        .map(|dim| dim.parse().map_err(|_| NpyError::Invalid(format!("invalid shape dimension {}", dim))))
+        .collect::<Result<_, _>>()?;
+    let (rows, cols) = match shape[..] {
+        [rows, cols] => (rows, cols),
+        _ => return Err(NpyError::Invalid(format!("expected a matrix, not an array of shape {:?}", shape))),
+    };
+
+    let (little_endian, size) = match descr.as_str() {
+        "<f4" | "=f4" => (true, 4),
+        ">f4" => (false, 4),
+        "<f8" | "=f8" => (true, 8),
+        ">f8" => (false, 8),
+        _ => return Err(NpyError::Invalid(format!("unsupported data type {}", descr))),
+    };
+    // the shape of a corrupt file can be larger than any file, so the data is read up to its end
+    let len = rows.checked_mul(cols).and_then(|count| count.checked_mul(size))
+        .ok_or_else(|| NpyError::Invalid(format!("the shape {:?} is too large", shape)))?;
+    let mut data = vec![];
+    reader.take(len as u64).read_to_end(&mut data)?;
+    if data.len() < len {
+        return Err(NpyError::Invalid(format!("expected {} bytes of data, the file has {}", len, data.len())));
+    }
+    let values: Vec<T> = data
+        .chunks(size)
+        .map(|bytes| {
+            let value = match (size, little_endian) {
+                (4, true) => f32::from_le_bytes(bytes.try_into().unwrap()) as f64,
+                (4, false) => f32::from_be_bytes(bytes.try_into().unwrap()) as f64,
+                (_, true) => f64::from_le_bytes(bytes.try_into().unwrap()),
+                (_, false) => f64::from_be_bytes(bytes.try_into().unwrap()),
+            };
+            T::from(value).unwrap()
+        })
+        .collect();
+
+    if fortran_order {
+        let matrix = Matrix::from_shape_vec((cols, rows), values).unwrap();
+        Ok(matrix.reversed_axes().as_standard_layout().into_owned())
+    } else {
+        Ok(Matrix::from_shape_vec((rows, cols), values).unwrap())
+    }
+}
+
+// the text after `'key':` in the header dictionary
+fn header_value<'a>(header: &'a str, key: &str) -> Result<&'a str, NpyError> {
+    let pattern = format!("'{}':", key);
+    header
+        .find(&pattern)
+        .map(|start| header[start + pattern.len()..].trim_start())
+        .ok_or_else(|| NpyError::Invalid(format!("the header has no {}", key)))
+}
+
+/// save both weights matrices like `numpy.save("wih.npy", n.wih)` and `numpy.save("who.npy", n.who)`
+pub fn save_npy<T: Float, P: AsRef<Path>, Q: AsRef<Path>>(network: &NeuralNetwork<T>, wih_path: P, who_path: Q) -> Result<(), NpyError> {
+    let (weight_ih, weight_ho) = network.weights();
+    for (matrix, path) in [(weight_ih, wih_path.as_ref()), (weight_ho, who_path.as_ref())] {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_npy(&mut writer, matrix)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+/// create a network from two `.npy` files written by `save_npy` or `numpy.save`
+pub fn load_npy<T: Float, P: AsRef<Path>, Q: AsRef<Path>>(wih_path: P, who_path: Q, learning_rate: f32) -> Result<NeuralNetwork<T>, NpyError> {
+    let weight_ih = read_npy(BufReader::new(File::open(wih_path)?))?;
+    let weight_ho = read_npy(BufReader::new(File::open(who_path)?))?;
+    from_weights(weight_ih, weight_ho, learning_rate)
+}
+
+/// save both weights matrices like `numpy.savez(path, wih=n.wih, who=n.who)`
+pub fn save_npz<T: Float, P: AsRef<Path>>(network: &NeuralNetwork<T>, path: P) -> Result<(), NpyError> {
+    let (weight_ih, weight_ho) = network.weights();
+    let mut archive = ZipWriter::new(BufWriter::new(File::create(path)?));
+    // numpy.savez does not compress the arrays either
+    let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+    for (name, matrix) in [("wih.npy", weight_ih), ("who.npy", weight_ho)] {
+        archive.start_file(name, options)?;
+        write_npy(&mut archive, matrix)?;
+    }
+    archive.finish()?.flush()?;
+    Ok(())
+}
+
+/// create a network from the `wih` and `who` arrays of an `.npz` archive, also from `numpy.savez_compressed`
+pub fn load_npz<T: Float, P: AsRef<Path>>(path: P, learning_rate: f32) -> Result<NeuralNetwork<T>, NpyError> {
+    let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+    let weight_ih = read_npy(archive.by_name("wih.npy")?)?;
+    let weight_ho = read_npy(archive.by_name("who.npy")?)?;
+    from_weights(weight_ih, weight_ho, learning_rate)
+}
+
+fn from_weights<T: Float>(weight_ih: Matrix<T>, weight_ho: Matrix<T>, learning_rate: f32) -> Result<NeuralNetwork<T>, NpyError> {
+    if weight_ho.ncols() != weight_ih.nrows() {
+        return Err(NpyError::Invalid(format!(
+            "who has {} columns but wih has {} rows", weight_ho.ncols(), weight_ih.nrows()
+        )));
+    }
+    Ok(NeuralNetwork::from_weights(weight_ih, weight_ho, learning_rate))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn npy_matches_numpy() {
+        let matrix = Matrix::from_shape_vec((2, 3), vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        let mut bytes = vec![];
+        write_npy(&mut bytes, &matrix).unwrap();
+        // the header numpy.save writes for numpy.arange(1, 7, dtype="<f4").reshape(2, 3)
+        let header = "{'descr': '<f4', 'fortran_order': False, 'shape': (2, 3), }";
+        assert_eq!(&bytes[..10], b"\x93NUMPY\x01\x00\x76\x00");
+        assert_eq!(&bytes[10..10 + header.len()], header.as_bytes());
+        assert_eq!(bytes[127], b'\n');
+        assert_eq!(bytes.len(), 128 + 6 * 4);
+        assert_eq!(read_npy::<f32, _>(&bytes[..]).unwrap(), matrix);
+
+        // the same matrix as big endian f8 values in Fortran order
+        let header = "{'descr': '>f8', 'fortran_order': True, 'shape': (2, 3), }\n";
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        for value in [1.0f64, 4.0, 2.0, 5.0, 3.0, 6.0] {
+            bytes.extend(value.to_be_bytes());
+        }
+        assert_eq!(read_npy::<f32, _>(&bytes[..]).unwrap(), matrix);
+        assert!(read_npy::<f32, _>(&bytes[..bytes.len() - 1]).is_err());
+
+        // a huge shape is an error, not an allocation
+        let header = format!("{{'descr': '<f8', 'fortran_order': False, 'shape': ({}, {}), }}\n", usize::MAX / 2, 3);
+        let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+        bytes.extend((header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        assert!(matches!(read_npy::<f32, _>(&bytes[..]), Err(NpyError::Invalid(_))));
+        let header = header.replace(&(usize::MAX / 2).to_string(), "100000000");
+        bytes.truncate(10);
+        bytes[8..10].copy_from_slice(&(header.len() as u16).to_le_bytes());
+        bytes.extend(header.as_bytes());
+        assert!(matches!(read_npy::<f32, _>(&bytes[..]), Err(NpyError::Invalid(_))));
+    }
+
+    #[test]
+    fn npz_round_trip() {
+        let nn: NeuralNetwork<f64> = NeuralNetwork::new_with_seed(5, 4, 3, 0.2, 3);
+        let path = env::temp_dir().join(format!("nn-npz-round-trip-{}.npz", std::process::id()));
+        save_npz(&nn, &path).unwrap();
+        let loaded: NeuralNetwork<f64> = load_npz(&path, 0.2).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.weights(), nn.weights());
+        assert_eq!(loaded.predict(&[0.1, 0.2, 0.3, 0.4, 0.5]), nn.predict(&[0.1, 0.2, 0.3, 0.4, 0.5]));
+    }
+}
@@ -0,0 +1,474 @@
+//! ONNX export and import of multi layer perceptrons
+//!
+//! only a chain of `MatMul`/`Gemm` (with an optional bias `Add`) and `Sigmoid`/`Relu`/`Tanh`/`Softmax` nodes is
+//! supported, which covers `NeuralNetwork` and the dense `Sequential` models. The files are written with IR
+//! version 8 and opset 13 and can be run with onnxruntime or opened in Netron.
+
+use ndarray::{Array1, Array2};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::activation::Activation;
+use crate::layers::{ActivationLayer, AnyLayer, Dense, Tensor};
+use crate::protobuf::{Decoder, Encoder, Value};
+use crate::sequential::{Loss, Sequential};
+use crate::NeuralNetwork;
+
+const IR_VERSION: i64 = 8;
+const OPSET_VERSION: i64 = 13;
+// TensorProto.DataType.FLOAT
+const FLOAT: i64 = 1;
+// AttributeProto.AttributeType.INT
+const ATTRIBUTE_INT: i64 = 2;
+
+/// error while exporting or importing an ONNX model
+#[derive(Debug)]
+pub enum OnnxError {
+    Io(io::Error),
+    /// the file is not a valid ONNX model
+    Decode(String),
+    /// the model uses an operator or a layer which is not supported
+    Unsupported(String),
+}
+
+impl fmt::Display for OnnxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OnnxError::Io(err) => write!(f, "can not access the ONNX file: {}", err),
+            OnnxError::Decode(message) => write!(f, "invalid ONNX model: {}", message),
+            OnnxError::Unsupported(message) => write!(f, "unsupported ONNX model: {}", message),
+        }
+    }
+}
+
+impl Error for OnnxError {}
+
+impl From<io::Error> for OnnxError {
+    fn from(err: io::Error) -> Self {
+        OnnxError::Io(err)
+    }
+}
+
+impl From<String> for OnnxError {
+    fn from(message: String) -> Self {
+        OnnxError::Decode(message)
+    }
+}
+
+// one node of the exported chain, the weights are outputs * inputs like in `Dense`
+enum Operation {
+    MatMul(Array2<f32>),
+    Gemm(Array2<f32>, Array1<f32>),
+    Activation(Activation),
+}
+
+/// encode a neural network as `MatMul -> Sigmoid -> MatMul -> Sigmoid`, or its hidden activation instead of the first sigmoid
+pub fn export_network(network: &NeuralNetwork) -> Vec<u8> {
+    let (weight_ih, weight_ho) = network.weights();
+    let operations = vec![
+        Operation::MatMul(weight_ih.clone()),
+        Operation::Activation(network.activation()),
+        Operation::MatMul(weight_ho.clone()),
+        Operation::Activation(Activation::Sigmoid),
+    ];
+    encode_model(weight_ih.ncols(), &operations)
+}
+
+/// encode a model of dense and activation layers, every dense layer becomes a `Gemm` node
+pub fn export_sequential(model: &Sequential) -> Result<Vec<u8>, OnnxError> {
+    if model.input_shape().len() != 1 {
+        return Err(OnnxError::Unsupported("only models with a flat input can be exported".to_string()));
+    }
+    let mut operations = vec![];
+    for layer in model.layers() {
+        operations.push(match layer {
+            AnyLayer::Dense(dense) => Operation::Gemm(to_matrix(dense.weights()), dense.bias().iter().copied().collect()),
+            AnyLayer::Activation(activation) => Operation::Activation(activation.activation()),
+            AnyLayer::Flatten(_) => continue,
+            other => return Err(OnnxError::Unsupported(format!("can not export {:?}", other))),
+        });
+    }
+    Ok(encode_model(model.input_shape()[0], &operations))
+}
+
+/// export a neural network to an `.onnx` file
+pub fn save_network<P: AsRef<Path>>(network: &NeuralNetwork, path: P) -> Result<(), OnnxError> {
+    Ok(fs::write(path, export_network(network))?)
+}
+
+/// export a dense `Sequential` model to an `.onnx` file
+pub fn save_sequential<P: AsRef<Path>>(model: &Sequential, path: P) -> Result<(), OnnxError> {
+    Ok(fs::write(path, export_sequential(model)?)?)
+}
+
+/// decode an ONNX model into a `Sequential` model of dense and activation layers
+///
+/// the loss of the model is the mean squared error and the learning rate is 0.1, both can be changed before
+/// the model is trained further.
+pub fn import(bytes: &[u8]) -> Result<Sequential, OnnxError> {
+    let mut graph = None;
+    for field in Decoder::new(bytes) {
+        if let (7, value) = field? {
+            graph = Some(value.as_bytes()?);
+        }
+    }
+    let graph = decode_graph(graph.ok_or_else(|| OnnxError::Decode("the model has no graph".to_string()))?)?;
+
+    let mut current = graph.inputs.iter()
+        .find(|input| !graph.initializers.contains_key(&input.0))
+        .ok_or_else(|| OnnxError::Decode("the graph has no input".to_string()))?
+        .clone();
+    let mut layers: Vec<AnyLayer> = vec![];
+    for node in &graph.nodes {
+        if node.inputs.first() != Some(&current.0) || node.outputs.is_empty() {
+            return Err(OnnxError::Unsupported(format!("node {} is not part of a chain of layers", node.op_type)));
+        }
+        let initializer = |i: usize| {
+            node.inputs.get(i)
+                .and_then(|name| graph.initializers.get(name))
+                .ok_or_else(|| OnnxError::Unsupported(format!("input {} of {} must be an initializer", i, node.op_type)))
+        };
+        match node.op_type.as_str() {
+            "MatMul" => {
+                let weights = initializer(1)?.clone().into_dimensionality().map_err(|_| "MatMul weights must be a matrix".to_string())?;
+                let weights: Array2<f32> = weights.reversed_axes();
+                let bias = Array1::zeros(weights.nrows());
+                layers.push(Dense::from_weights(weights.into_dyn(), bias.into_dyn()).into());
+            }
+            "Gemm" => {
+                if node.int("transA", 0) != 0 {
+                    return Err(OnnxError::Unsupported("Gemm with transA".to_string()));
+                }
+                let b: Array2<f32> = initializer(1)?.clone().into_dimensionality().map_err(|_| "Gemm weights must be a matrix".to_string())?;
+                let weights = if node.int("transB", 0) != 0 { b } else { b.reversed_axes() } * node.float("alpha", 1.0);
+                let bias = match node.inputs.get(2).filter(|name| !name.is_empty()) {
+                    Some(_) => initializer(2)?.iter().map(|c| c * node.float("beta", 1.0)).collect(),
+                    None => Array1::zeros(weights.nrows()),
+                };
+                if bias.len() != weights.nrows() {
+                    return Err(OnnxError::Unsupported("Gemm bias must have one value per output".to_string()));
+                }
+                layers.push(Dense::from_weights(weights.as_standard_layout().into_owned().into_dyn(), bias.into_dyn()).into());
+            }
+            "Add" => {
+                let c = initializer(1)?;
+                match layers.last_mut() {
+                    Some(AnyLayer::Dense(dense)) if c.len() == dense.bias().len() => {
+                        let bias = dense.bias() + &c.iter().copied().collect::<Array1<f32>>().into_dyn();
+                        *dense = Dense::from_weights(dense.weights().clone(), bias);
+                    }
+                    _ => return Err(OnnxError::Unsupported("Add must be the bias of a MatMul".to_string())),
+                }
+            }
+            "Sigmoid" => layers.push(ActivationLayer::new(Activation::Sigmoid).into()),
+            "Relu" => layers.push(ActivationLayer::new(Activation::Relu).into()),
+            "Tanh" => layers.push(ActivationLayer::new(Activation::Tanh).into()),
+            "Softmax" => {
+                if ![-1, 1].contains(&node.int("axis", -1)) {
+                    return Err(OnnxError::Unsupported("Softmax must be over the features".to_string()));
+                }
+                layers.push(ActivationLayer::new(Activation::Softmax).into());
+            }
+            other => return Err(OnnxError::Unsupported(format!("operator {}", other))),
+        }
+        current = (node.outputs[0].clone(), vec![]);
+    }
+
+    // the input size is the last dimension of the graph input, or the inputs of the first dense layer
+    let input_size = graph.inputs.iter()
+        .find(|input| !graph.initializers.contains_key(&input.0))
+        .and_then(|input| input.1.last().copied().filter(|&size| size > 0))
+        .or_else(|| layers.iter().find_map(|layer| match layer {
+            AnyLayer::Dense(dense) => Some(dense.weights().shape()[1] as i64),
+            _ => None,
+        }))
+        .ok_or_else(|| OnnxError::Decode("the input size is unknown".to_string()))?;
+    // a hand edited file can chain matrices which do not fit, which would fail in the first predict
+    let mut width = input_size as usize;
+    for (i, layer) in layers.iter().enumerate() {
+        if let AnyLayer::Dense(dense) = layer {
+            let (outputs, inputs) = (dense.weights().shape()[0], dense.weights().shape()[1]);
+            if inputs != width {
+                return Err(OnnxError::Unsupported(format!("layer {} has {} inputs, but its input has {} values", i, inputs, width)));
+            }
+            width = outputs;
+        }
+    }
+    Ok(layers.into_iter().fold(Sequential::new(&[input_size as usize], Loss::MeanSquaredError, 0.1), Sequential::push))
+}
+
+/// import an `.onnx` file, see `import`
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Sequential, OnnxError> {
+    import(&fs::read(path)?)
+}
+
+fn to_matrix(tensor: &Tensor) -> Array2<f32> {
+    tensor.clone().into_dimensionality().unwrap()
+}
+
+fn encode_model(inputs: usize, operations: &[Operation]) -> Vec<u8> {
+    let mut outputs = inputs;
+    let mut model = Encoder::new();
+    model
+        .varint(1, IR_VERSION)
+        .string(2, "nn")
+        .string(3, env!("CARGO_PKG_VERSION"))
+        .message(7, |graph| {
+            let mut current = "input".to_string();
+            for (i, operation) in operations.iter().enumerate() {
+                let output = if i + 1 == operations.len() { "output".to_string() } else { format!("layer{}", i) };
+                let (weight, bias) = (format!("layer{}.weight", i), format!("layer{}.bias", i));
+                match operation {
+                    Operation::MatMul(weights) => {
+                        // MatMul multiplies the [batch, inputs] input with an inputs * outputs matrix
+                        encode_node(graph, "MatMul", &[&current, &weight], &output, |_| {});
+                        encode_tensor(graph, &weight, &weights.t().as_standard_layout().into_owned().into_dyn());
+                        outputs = weights.nrows();
+                    }
+                    Operation::Gemm(weights, b) => {
+                        encode_node(graph, "Gemm", &[&current, &weight, &bias], &output, |node| {
+                            node.message(5, |attribute| {
+                                attribute.string(1, "transB").varint(3, 1).varint(20, ATTRIBUTE_INT);
+                            });
+                        });
+                        encode_tensor(graph, &weight, &weights.clone().into_dyn());
+                        encode_tensor(graph, &bias, &b.clone().into_dyn());
+                        outputs = weights.nrows();
+                    }
+                    Operation::Activation(activation) => {
+                        let op_type = match activation {
+                            Activation::Sigmoid => "Sigmoid",
+                            Activation::Relu => "Relu",
+                            Activation::Tanh => "Tanh",
+                            Activation::Softmax => "Softmax",
+                        };
+                        encode_node(graph, op_type, &[&current], &output, |_| {});
+                    }
+                }
+                current = output;
+            }
+            graph.string(2, "nn");
+            graph.message(11, |value_info| encode_value_info(value_info, "input", inputs));
+            graph.message(12, |value_info| encode_value_info(value_info, "output", outputs));
+        })
+        .message(8, |opset| {
+            opset.string(1, "").varint(2, OPSET_VERSION);
+        });
+    model.into_bytes()
+}
+
+fn encode_node(graph: &mut Encoder, op_type: &str, inputs: &[&str], output: &str, attributes: impl FnOnce(&mut Encoder)) {
+    graph.message(1, |node| {
+        for input in inputs {
+            node.string(1, input);
+        }
+        node.string(2, output).string(3, output).string(4, op_type);
+        attributes(node);
+    });
+}
+
+fn encode_tensor(graph: &mut Encoder, name: &str, tensor: &Tensor) {
+    graph.message(5, |t| {
+        let dims: Vec<i64> = tensor.shape().iter().map(|&d| d as i64).collect();
+        // raw_data holds the little endian floats like a packed float field
+        let data: Vec<f32> = tensor.iter().copied().collect();
+        t.packed_varints(1, &dims).varint(2, FLOAT).string(8, name).packed_floats(9, &data);
+    });
+}
+
+// a [batch, size] float tensor, the batch size is a symbolic dimension
+fn encode_value_info(value_info: &mut Encoder, name: &str, size: usize) {
+    value_info.string(1, name).message(2, |type_proto| {
+        type_proto.message(1, |tensor_type| {
+            tensor_type.varint(1, FLOAT).message(2, |shape| {
+                shape.message(1, |dim| {
+                    dim.string(2, "batch");
+                });
+                shape.message(1, |dim| {
+                    dim.varint(1, size as i64);
+                });
+            });
+        });
+    });
+}
+
+struct Node {
+    op_type: String,
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    ints: HashMap<String, i64>,
+    floats: HashMap<String, f32>,
+}
+
+impl Node {
+    fn int(&self, name: &str, default: i64) -> i64 {
+        self.ints.get(name).copied().unwrap_or(default)
+    }
+
+    fn float(&self, name: &str, default: f32) -> f32 {
+        self.floats.get(name).copied().unwrap_or(default)
+    }
+}
+
+struct Graph {
+    nodes: Vec<Node>,
+    initializers: HashMap<String, Tensor>,
+    // name and dims of every graph input, a symbolic dimension is -1
+    inputs: Vec<(String, Vec<i64>)>,
+}
+
+fn decode_graph(bytes: &[u8]) -> Result<Graph, OnnxError> {
+    let mut graph = Graph { nodes: vec![], initializers: HashMap::new(), inputs: vec![] };
+    for field in Decoder::new(bytes) {
+        match field? {
+            (1, value) => graph.nodes.push(decode_node(value.as_bytes()?)?),
+            (5, value) => {
+                let (name, tensor) = decode_tensor(value.as_bytes()?)?;
+                graph.initializers.insert(name, tensor);
+            }
+            (11, value) => graph.inputs.push(decode_value_info(value.as_bytes()?)?),
+            _ => {}
+        }
+    }
+    Ok(graph)
+}
+
+fn decode_node(bytes: &[u8]) -> Result<Node, OnnxError> {
+    let mut node = Node { op_type: String::new(), inputs: vec![], outputs: vec![], ints: HashMap::new(), floats: HashMap::new() };
+    for field in Decoder::new(bytes) {
+        match field? {
+            (1, value) => node.inputs.push(value.as_string()?),
+            (2, value) => node.outputs.push(value.as_string()?),
+            (4, value) => node.op_type = value.as_string()?,
+            (5, value) => {
+                let mut name = String::new();
+                let (mut int, mut float) = (None, None);
+                for field in Decoder::new(value.as_bytes()?) {
+                    match field? {
+                        (1, value) => name = value.as_string()?,
+                        (2, value) => float = Some(value.as_f32()?),
+                        (3, value) => int = Some(value.as_i64()?),
+                        _ => {}
+                    }
+                }
+                if let Some(int) = int {
+                    node.ints.insert(name.clone(), int);
+                }
+                if let Some(float) = float {
+                    node.floats.insert(name, float);
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(node)
+}
+
+fn decode_tensor(bytes: &[u8]) -> Result<(String, Tensor), OnnxError> {
+    let (mut name, mut dims, mut data_type, mut data) = (String::new(), vec![], FLOAT, vec![]);
+    for field in Decoder::new(bytes) {
+        match field? {
+            (1, value) => dims.extend(value.varints()?),
+            (2, value) => data_type = value.as_i64()?,
+            (4, value) => data.extend(value.floats()?),
+            (8, value) => name = value.as_string()?,
+            (9, value) => data.extend(Value::Bytes(value.as_bytes()?).floats()?),
+            _ => {}
+        }
+    }
+    if data_type != FLOAT {
+        return Err(OnnxError::Unsupported(format!("tensor {} is not a float tensor", name)));
+    }
+    let shape: Vec<usize> = dims.iter().map(|&d| d as usize).collect();
+    let tensor = Tensor::from_shape_vec(shape, data).map_err(|_| format!("tensor {} does not match its dims", name))?;
+    Ok((name, tensor))
+}
+
+fn decode_value_info(bytes: &[u8]) -> Result<(String, Vec<i64>), OnnxError> {
+    let (mut name, mut dims) = (String::new(), vec![]);
+    for field in Decoder::new(bytes) {
+        match field? {
+            (1, value) => name = value.as_string()?,
+            (2, type_proto) => {
+                // TypeProto.tensor_type.shape.dim.dim_value
+                for field in Decoder::new(type_proto.as_bytes()?) {
+                    if let (1, tensor_type) = field? {
+                        for field in Decoder::new(tensor_type.as_bytes()?) {
+                            if let (2, shape) = field? {
+                                for field in Decoder::new(shape.as_bytes()?) {
+                                    if let (1, dim) = field? {
+                                        let mut size = -1;
+                                        for field in Decoder::new(dim.as_bytes()?) {
+                                            if let (1, value) = field? {
+                                                size = value.as_i64()?;
+                                            }
+                                        }
+                                        dims.push(size);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((name, dims))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray_rand::rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn assert_same_predictions(expected: impl Fn(&[f32]) -> Vec<f32>, model: &Sequential, inputs: usize) {
+        for offset in 0..3 {
+            let input_list: Vec<f32> = (0..inputs).map(|i| ((i + offset) as f32 * 0.37).sin()).collect();
+            let (expected, output) = (expected(&input_list), model.predict(&input_list));
+            assert!(expected.iter().zip(&output).all(|(e, o)| (e - o).abs() < 1e-6), "{:?} {:?}", expected, output);
+        }
+    }
+
+    #[test]
+    fn network_round_trip() {
+        let nn = NeuralNetwork::new_with_seed(6, 5, 3, 0.1, 1);
+        let model = import(&export_network(&nn)).unwrap();
+        assert_eq!(model.input_shape(), &[6]);
+        assert_eq!(model.output_shape(), vec![3]);
+        assert_same_predictions(|input| nn.predict(input), &model, 6);
+    }
+
+    #[test]
+    fn sequential_round_trip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let mut dense = Dense::new(4, 8, &mut rng);
+        // a non zero bias, so the Gemm bias is tested too
+        dense = Dense::from_weights(dense.weights().clone(), Tensor::from_shape_fn(vec![8], |i| i[0] as f32 * 0.1 - 0.3));
+        let model = Sequential::new(&[4], Loss::CrossEntropy, 0.1)
+            .push(dense)
+            .push(ActivationLayer::new(Activation::Relu))
+            .push(Dense::new(8, 3, &mut rng))
+            .push(ActivationLayer::new(Activation::Tanh))
+            .push(Dense::new(3, 2, &mut rng))
+            .push(ActivationLayer::new(Activation::Softmax));
+        let imported = import(&export_sequential(&model).unwrap()).unwrap();
+        assert_eq!(imported.layers().len(), 6);
+        assert_same_predictions(|input| model.predict(input), &imported, 4);
+
+        let conv = Sequential::new(&[1, 4, 4], Loss::CrossEntropy, 0.1);
+        assert!(matches!(export_sequential(&conv), Err(OnnxError::Unsupported(_))));
+        assert!(matches!(import(&[0xff]), Err(OnnxError::Decode(_))));
+
+        // matrices which do not fit together
+        let mismatched = Sequential::new(&[4], Loss::MeanSquaredError, 0.1)
+            .push(Dense::new(4, 3, &mut rng))
+            .push(Dense::new(5, 2, &mut rng));
+        assert!(matches!(import(&export_sequential(&mismatched).unwrap()), Err(OnnxError::Unsupported(_))));
+    }
+}
@@ -0,0 +1,52 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Matrix;
+
+/// how the weight updates are applied to the weights
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Optimizer {
+    /// plain stochastic gradient descent, W += learning_rate * update
+    #[default]
+    Sgd,
+    /// SGD with momentum, V = momentum * V + update and W += learning_rate * V
+    Momentum { momentum: f32 },
+}
+
+impl std::fmt::Display for Optimizer {
+    /// a short name, e.g. `momentum 0.9`
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Optimizer::Sgd => write!(f, "sgd"),
+            Optimizer::Momentum { momentum } => write!(f, "momentum {}", momentum),
+        }
+    }
+}
+
+/// the velocities of the momentum optimizer, one for each weights matrix
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct OptimizerState<T = f32> {
+    pub(crate) velocity_ih: Matrix<T>,
+    pub(crate) velocity_ho: Matrix<T>,
+}
+
+/// learning rate schedule, evaluated with the number of weight updates done so far
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum Schedule {
+    /// always the base learning rate
+    #[default]
+    Constant,
+    /// multiply the learning rate by `gamma` every `step_size` weight updates
+    StepDecay { step_size: u64, gamma: f32 },
+}
+
+impl Schedule {
+    /// learning rate after `step` weight updates
+    pub fn learning_rate(&self, base_learning_rate: f32, step: u64) -> f32 {
+        match *self {
+            Schedule::Constant => base_learning_rate,
+            Schedule::StepDecay { step_size, gamma } => {
+                base_learning_rate * gamma.powi((step / step_size.max(1)) as i32)
+            }
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! saving and loading models and checkpoints as JSON files
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// error while saving or loading a model file
+#[derive(Debug)]
+pub enum PersistError {
+    Io(io::Error),
+    Format(serde_json::Error),
+    /// a binary model file (e.g. a quantised model) which can not be decoded
+    Invalid(String),
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PersistError::Io(err) => write!(f, "can not access the model file: {}", err),
+            PersistError::Format(err) => write!(f, "invalid model file: {}", err),
+            PersistError::Invalid(message) => write!(f, "invalid model file: {}", message),
+        }
+    }
+}
+
+impl Error for PersistError {}
+
+impl From<io::Error> for PersistError {
+    fn from(err: io::Error) -> Self {
+        PersistError::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for PersistError {
+    fn from(err: serde_json::Error) -> Self {
+        PersistError::Format(err)
+    }
+}
+
+/// write `value` to `path` as JSON.
+/// The file is written next to `path` first and then renamed, so an interrupted save never leaves a broken file.
+pub fn save_json<T: Serialize, P: AsRef<Path>>(value: &T, path: P) -> Result<(), PersistError> {
+    save_with(path, |writer| Ok(serde_json::to_writer(writer, value)?))
+}
+
+/// write the bytes of a binary model file to `path`, with the same temporary file as `save_json`
+pub fn save_bytes<P: AsRef<Path>>(bytes: &[u8], path: P) -> Result<(), PersistError> {
+    save_with(path, |writer| Ok(writer.write_all(bytes)?))
+}
+
+fn save_with<P, F>(path: P, write: F) -> Result<(), PersistError>
+where
+    P: AsRef<Path>,
+    F: FnOnce(&mut BufWriter<File>) -> Result<(), PersistError>,
+{
+    let path = path.as_ref();
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    write(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+    fs::rename(&temp_path, path)?;
+    Ok(())
+}
+
+/// read a JSON file written by `save_json`
+pub fn load_json<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, PersistError> {
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
@@ -0,0 +1,240 @@
+//! minimal protocol buffers wire format encoder and decoder, enough for the ONNX and TensorBoard files
+
+/// builds a message field by field
+#[derive(Debug, Default)]
+pub(crate) struct Encoder {
+    bytes: Vec<u8>,
+}
+
+impl Encoder {
+    pub(crate) fn new() -> Encoder {
+        Encoder::default()
+    }
+
+    pub(crate) fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+
+    fn key(&mut self, field: u32, wire_type: u8) {
+        write_varint(&mut self.bytes, ((field as u64) << 3) | wire_type as u64);
+    }
+
+    /// int32, int64, uint64, bool and enum fields, negative values take 10 bytes like in protobuf
+    pub(crate) fn varint(&mut self, field: u32, value: i64) -> &mut Encoder {
+        self.key(field, 0);
+        write_varint(&mut self.bytes, value as u64);
+        self
+    }
+
+    pub(crate) fn double(&mut self, field: u32, value: f64) -> &mut Encoder {
+        self.key(field, 1);
+        self.bytes.extend(value.to_le_bytes());
+        self
+    }
+
+    pub(crate) fn float(&mut self, field: u32, value: f32) -> &mut Encoder {
+        self.key(field, 5);
+        self.bytes.extend(value.to_le_bytes());
+        self
+    }
+
+    pub(crate) fn bytes(&mut self, field: u32, value: &[u8]) -> &mut Encoder {
+        self.key(field, 2);
+        write_varint(&mut self.bytes, value.len() as u64);
+        self.bytes.extend(value);
+        self
+    }
+
+    pub(crate) fn string(&mut self, field: u32, value: &str) -> &mut Encoder {
+        self.bytes(field, value.as_bytes())
+    }
+
+    /// an embedded message built by `build`
+    pub(crate) fn message(&mut self, field: u32, build: impl FnOnce(&mut Encoder)) -> &mut Encoder {
+        let mut message = Encoder::new();
+        build(&mut message);
+        self.bytes(field, &message.bytes)
+    }
+
+    pub(crate) fn packed_floats(&mut self, field: u32, values: &[f32]) -> &mut Encoder {
+        let bytes: Vec<u8> = values.iter().flat_map(|v| v.to_le_bytes()).collect();
+        self.bytes(field, &bytes)
+    }
+
+    pub(crate) fn packed_varints(&mut self, field: u32, values: &[i64]) -> &mut Encoder {
+        let mut bytes = vec![];
+        for &value in values {
+            write_varint(&mut bytes, value as u64);
+        }
+        self.bytes(field, &bytes)
+    }
+}
+
+/// the value of a decoded field
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+impl<'a> Value<'a> {
+    pub(crate) fn as_i64(&self) -> Result<i64, String> {
+        match self {
+            Value::Varint(v) => Ok(*v as i64),
+            _ => Err("expected a varint field".to_string()),
+        }
+    }
+
+    pub(crate) fn as_f32(&self) -> Result<f32, String> {
+        match self {
+            Value::Fixed32(v) => Ok(f32::from_bits(*v)),
+            _ => Err("expected a float field".to_string()),
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> Result<&'a [u8], String> {
+        match self {
+            Value::Bytes(bytes) => Ok(bytes),
+            _ => Err("expected a length delimited field".to_string()),
+        }
+    }
+
+    pub(crate) fn as_string(&self) -> Result<String, String> {
+        String::from_utf8(self.as_bytes()?.to_vec()).map_err(|_| "invalid utf-8 string".to_string())
+    }
+
+    /// a repeated float field, packed or one value per field
+    pub(crate) fn floats(&self) -> Result<Vec<f32>, String> {
+        match self {
+            Value::Fixed32(v) => Ok(vec![f32::from_bits(*v)]),
+            Value::Bytes(bytes) if bytes.len() % 4 == 0 => {
+                Ok(bytes.chunks(4).map(|b| f32::from_le_bytes(b.try_into().unwrap())).collect())
+            }
+            _ => Err("invalid repeated float field".to_string()),
+        }
+    }
+
+    /// a repeated integer field, packed or one value per field
+    pub(crate) fn varints(&self) -> Result<Vec<i64>, String> {
+        match self {
+            Value::Varint(v) => Ok(vec![*v as i64]),
+            Value::Bytes(bytes) => {
+                let mut bytes = *bytes;
+                let mut values = vec![];
+                while !bytes.is_empty() {
+                    values.push(read_varint(&mut bytes)? as i64);
+                }
+                Ok(values)
+            }
+            _ => Err("invalid repeated integer field".to_string()),
+        }
+    }
+}
+
+/// iterates over the fields of a message
+pub(crate) struct Decoder<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Decoder<'a> {
+        Decoder { bytes }
+    }
+
+    fn next_field(&mut self) -> Result<(u32, Value<'a>), String> {
+        let key = read_varint(&mut self.bytes)?;
+        let value = match key & 7 {
+            0 => Value::Varint(read_varint(&mut self.bytes)?),
+            1 => Value::Fixed64(u64::from_le_bytes(take(&mut self.bytes, 8)?.try_into().unwrap())),
+            2 => {
+                let len = read_varint(&mut self.bytes)? as usize;
+                Value::Bytes(take(&mut self.bytes, len)?)
+            }
+            5 => Value::Fixed32(u32::from_le_bytes(take(&mut self.bytes, 4)?.try_into().unwrap())),
+            wire_type => return Err(format!("unsupported wire type {}", wire_type)),
+        };
+        Ok(((key >> 3) as u32, value))
+    }
+}
+
+impl<'a> Iterator for Decoder<'a> {
+    type Item = Result<(u32, Value<'a>), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.bytes.is_empty() {
+            return None;
+        }
+        let field = self.next_field();
+        if field.is_err() {
+            // stop after the first error
+            self.bytes = &[];
+        }
+        Some(field)
+    }
+}
+
+pub(crate) fn write_varint(bytes: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        bytes.push((value as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Result<u64, String> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or("unexpected end of message")?;
+        *bytes = rest;
+        value |= ((byte & 0x7f) as u64) << shift;
+        if byte < 0x80 {
+            return Ok(value);
+        }
+    }
+    Err("varint is too long".to_string())
+}
+
+fn take<'a>(bytes: &mut &'a [u8], len: usize) -> Result<&'a [u8], String> {
+    if bytes.len() < len {
+        return Err("unexpected end of message".to_string());
+    }
+    let (head, tail) = bytes.split_at(len);
+    *bytes = tail;
+    Ok(head)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_round_trip() {
+        let mut encoder = Encoder::new();
+        encoder
+            .varint(1, 300)
+            .varint(2, -1)
+            .string(3, "sigmoid")
+            .packed_floats(6, &[1.0, -2.0])
+            .message(7, |m| {
+                m.packed_varints(1, &[3, 784]);
+            })
+            .double(8, 0.5)
+            .float(9, 0.25);
+        let bytes = encoder.into_bytes();
+        // 300 is the varint example of the protobuf documentation
+        assert_eq!(&bytes[..3], &[0x08, 0xac, 0x02]);
+
+        let fields: Vec<(u32, Value)> = Decoder::new(&bytes).collect::<Result<_, _>>().unwrap();
+        assert_eq!(fields[0], (1, Value::Varint(300)));
+        assert_eq!(fields[1].1.as_i64().unwrap(), -1);
+        assert_eq!(fields[2].1.as_string().unwrap(), "sigmoid");
+        assert_eq!(fields[3].1.floats().unwrap(), vec![1.0, -2.0]);
+        let (_, dims) = Decoder::new(fields[4].1.as_bytes().unwrap()).next().unwrap().unwrap();
+        assert_eq!(dims.varints().unwrap(), vec![3, 784]);
+        assert_eq!(fields[5], (8, Value::Fixed64(0.5f64.to_bits())));
+        assert_eq!(fields[6].1.as_f32().unwrap(), 0.25);
+        assert!(Decoder::new(&bytes[..bytes.len() - 1]).any(|field| field.is_err()));
+    }
+}
@@ -0,0 +1,243 @@
+//! post-training int8 quantisation of a trained network
+//!
+//! every row of a weights matrix gets its own scale, `w ≈ scale * q` with `q` in [-127, 127]. The inputs of a
+//! layer are quantised the same way with one scale per vector, so both matrix products only multiply and add
+//! integers, the result is converted back with the product of the two scales.
+
+use std::fs;
+use std::path::Path;
+
+use crate::persist::{self, PersistError};
+use crate::{Activation, Matrix, NeuralNetwork};
+
+// "NNQ8" followed by the format version
+const MAGIC: &[u8; 4] = b"NNQ8";
+const VERSION: u8 = 2;
+// the hidden activations in the order of their byte values
+const ACTIVATIONS: [Activation; 3] = [Activation::Sigmoid, Activation::Relu, Activation::Tanh];
+
+/// int8 matrix with one f32 scale per row
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedMatrix {
+    rows: usize,
+    cols: usize,
+    values: Vec<i8>,
+    scales: Vec<f32>,
+}
+
+impl QuantizedMatrix {
+    /// quantise every row with scale max(|w|) / 127
+    pub fn quantize(matrix: &Matrix) -> QuantizedMatrix {
+        let (rows, cols) = matrix.dim();
+        let mut values = Vec::with_capacity(rows * cols);
+        let mut scales = Vec::with_capacity(rows);
+        for row in matrix.rows() {
+            let (scale, quantized) = quantize_vector(row.iter().copied());
+            scales.push(scale);
+            values.extend(quantized);
+        }
+        QuantizedMatrix { rows, cols, values, scales }
+    }
+
+    /// the f32 matrix the quantised values stand for
+    pub fn dequantize(&self) -> Matrix {
+        Matrix::from_shape_fn((self.rows, self.cols), |(r, c)| self.values[r * self.cols + c] as f32 * self.scales[r])
+    }
+
+    pub fn dim(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    pub fn scales(&self) -> &[f32] {
+        &self.scales
+    }
+
+    // matrix * vector with i32 accumulators, the vector is quantised with its own scale
+    fn matvec(&self, vector: &[f32]) -> Vec<f32> {
+        let (scale, quantized) = quantize_vector(vector.iter().copied());
+        self.values
+            .chunks(self.cols)
+            .zip(&self.scales)
+            .map(|(row, row_scale)| {
+                let sum: i32 = row.iter().zip(&quantized).map(|(&w, &x)| w as i32 * x as i32).sum();
+                sum as f32 * row_scale * scale
+            })
+            .collect()
+    }
+
+    // number of bytes `write` adds
+    fn encoded_len(&self) -> usize {
+        2 * 4 + self.scales.len() * 4 + self.values.len()
+    }
+
+    fn write(&self, bytes: &mut Vec<u8>) {
+        bytes.extend((self.rows as u32).to_le_bytes());
+        bytes.extend((self.cols as u32).to_le_bytes());
+        for scale in &self.scales {
+            bytes.extend(scale.to_le_bytes());
+        }
+        bytes.extend(self.values.iter().map(|&v| v as u8));
+    }
+
+    fn read(reader: &mut Reader) -> Result<QuantizedMatrix, PersistError> {
+        let rows = reader.u32()? as usize;
+        let cols = reader.u32()? as usize;
+        let scales = (0..rows).map(|_| reader.f32()).collect::<Result<Vec<_>, _>>()?;
+        let values = reader.take(rows * cols)?.iter().map(|&v| v as i8).collect();
+        Ok(QuantizedMatrix { rows, cols, values, scales })
+    }
+}
+
+/// a neural network with int8 weights, for fast inference only
+#[derive(Debug, Clone, PartialEq)]
+pub struct QuantizedNetwork {
+    weight_ih: QuantizedMatrix,
+    weight_ho: QuantizedMatrix,
+    activation: Activation,
+}
+
+impl QuantizedNetwork {
+    /// quantise the weights of a trained network
+    pub fn from_network(network: &NeuralNetwork) -> QuantizedNetwork {
+        let (weight_ih, weight_ho) = network.weights();
+        QuantizedNetwork {
+            weight_ih: QuantizedMatrix::quantize(weight_ih),
+            weight_ho: QuantizedMatrix::quantize(weight_ho),
+            activation: network.activation(),
+        }
+    }
+
+    pub fn weights(&self) -> (&QuantizedMatrix, &QuantizedMatrix) {
+        (&self.weight_ih, &self.weight_ho)
+    }
+
+    /// the activation of the hidden layer
+    pub fn activation(&self) -> Activation {
+        self.activation
+    }
+
+    /// query the quantised network like `NeuralNetwork::predict`
+    pub fn predict(&self, input_list: &[f32]) -> Vec<f32> {
+        if input_list.len() != self.weight_ih.cols {
+            panic!("input list length does not match input nodes");
+        }
+        let hidden_output_list: Vec<f32> = self.weight_ih.matvec(input_list).into_iter().map(|x| self.activation.value(x)).collect();
+        self.weight_ho.matvec(&hidden_output_list).into_iter().map(|x| Activation::Sigmoid.value(x)).collect()
+    }
+
+    /// size of the model file in bytes, about a quarter of the f32 weights
+    pub fn encoded_len(&self) -> usize {
+        MAGIC.len() + 2 + self.weight_ih.encoded_len() + self.weight_ho.encoded_len()
+    }
+
+    /// encode as `NNQ8`, a version byte, the hidden activation byte and both matrices (rows, cols, row scales,
+    /// int8 values, little endian)
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.push(VERSION);
+        bytes.push(ACTIVATIONS.iter().position(|&activation| activation == self.activation).unwrap() as u8);
+        self.weight_ih.write(&mut bytes);
+        self.weight_ho.write(&mut bytes);
+        bytes
+    }
+
+    /// decode a model written by `to_bytes`
+    pub fn from_bytes(bytes: &[u8]) -> Result<QuantizedNetwork, PersistError> {
+        let mut reader = Reader { bytes };
+        if reader.take(4)? != MAGIC {
+            return Err(PersistError::Invalid("not a quantised model".to_string()));
+        }
+        let version = reader.take(1)?[0];
+        if version != VERSION {
+            return Err(PersistError::Invalid(format!("unsupported quantised model version {}", version)));
+        }
+        let byte = reader.take(1)?[0];
+        let activation = *ACTIVATIONS.get(byte as usize).ok_or_else(|| PersistError::Invalid(format!("unknown activation {}", byte)))?;
+        let weight_ih = QuantizedMatrix::read(&mut reader)?;
+        let weight_ho = QuantizedMatrix::read(&mut reader)?;
+        if weight_ho.cols != weight_ih.rows {
+            return Err(PersistError::Invalid("the weights matrices do not fit together".to_string()));
+        }
+        if !reader.bytes.is_empty() {
+            return Err(PersistError::Invalid(format!("{} unexpected bytes after the weights", reader.bytes.len())));
+        }
+        Ok(QuantizedNetwork { weight_ih, weight_ho, activation })
+    }
+
+    /// save the quantised model in the compact binary format
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PersistError> {
+        persist::save_bytes(&self.to_bytes(), path)
+    }
+
+    /// load a model written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<QuantizedNetwork, PersistError> {
+        QuantizedNetwork::from_bytes(&fs::read(path)?)
+    }
+}
+
+// symmetric quantisation with scale max(|x|) / 127, an all zero vector gets scale 1
+fn quantize_vector(values: impl Iterator<Item = f32> + Clone) -> (f32, Vec<i8>) {
+    let max = values.clone().fold(0.0f32, |max, v| max.max(v.abs()));
+    let scale = if max > 0.0 { max / 127.0 } else { 1.0 };
+    (scale, values.map(|v| (v / scale).round().clamp(-127.0, 127.0) as i8).collect())
+}
+
+// reads little endian values, a short file is an invalid model
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], PersistError> {
+        if self.bytes.len() < len {
+            return Err(PersistError::Invalid("unexpected end of the quantised model".to_string()));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn u32(&mut self) -> Result<u32, PersistError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f32(&mut self) -> Result<f32, PersistError> {
+        Ok(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantised_network_predicts_like_the_f32_network() {
+        let nn = NeuralNetwork::new_with_seed(20, 30, 10, 0.1, 4);
+        let quantized = QuantizedNetwork::from_network(&nn);
+
+        // every weight is within half a quantisation step of the f32 weight
+        let (weight_ih, _) = nn.weights();
+        let error = weight_ih - &quantized.weights().0.dequantize();
+        for (row, scale) in error.rows().into_iter().zip(quantized.weights().0.scales()) {
+            assert!(row.iter().all(|e| e.abs() <= scale / 2.0 + 1e-7));
+        }
+
+        let input_list: Vec<f32> = (0..20).map(|i| 0.01 + 0.99 * (i % 7) as f32 / 6.0).collect();
+        let expected = nn.predict(&input_list);
+        let output = quantized.predict(&input_list);
+        assert!(expected.iter().zip(&output).all(|(e, o)| (e - o).abs() < 0.02), "{:?} {:?}", expected, output);
+
+        let bytes = quantized.to_bytes();
+        assert_eq!(bytes.len(), 6 + 2 * 8 + (30 + 10) * 4 + 20 * 30 + 30 * 10);
+        assert_eq!(quantized.encoded_len(), bytes.len());
+        assert_eq!(QuantizedNetwork::from_bytes(&bytes).unwrap(), quantized);
+        assert!(QuantizedNetwork::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(QuantizedNetwork::from_bytes(&[&bytes[..], &[0]].concat()).is_err());
+
+        let mut nn = nn;
+        nn.set_activation(Activation::Tanh);
+        let quantized = QuantizedNetwork::from_bytes(&QuantizedNetwork::from_network(&nn).to_bytes()).unwrap();
+        let (expected, output) = (nn.predict(&input_list), quantized.predict(&input_list));
+        assert!(expected.iter().zip(&output).all(|(e, o)| (e - o).abs() < 0.02), "{:?} {:?}", expected, output);
+    }
+}
@@ -0,0 +1,62 @@
+use ndarray::Array;
+use ndarray_rand::rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::{Float, Matrix};
+
+/// the mode of a neural network, some parts of training (e.g. dropout) are only active in `Mode::Train`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Mode {
+    /// the network is being trained, dropout is applied to the hidden layer
+    #[default]
+    Train,
+    /// the network is being evaluated, every node takes part in the forward pass
+    Eval,
+}
+
+/// regularisation settings used by `NeuralNetwork::train`
+///
+/// all of them are disabled (zero) by default, so a network behaves exactly like before unless configured.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub struct Regularization {
+    /// L1 penalty, the weight update subtracts `l1 * sign(W)` which pushes small weights to exactly zero
+    pub l1: f32,
+    /// L2 penalty (weight decay), the weight update subtracts `l2 * W`
+    pub l2: f32,
+    /// probability of dropping a hidden node while training, must be in range [0, 1)
+    pub dropout: f32,
+}
+
+impl Regularization {
+    /// gradient of the L1 and L2 penalties with respect to the weights matrix
+    pub fn penalty_gradient<T: Float>(&self, weights: &Matrix<T>) -> Matrix<T> {
+        let (l1, l2) = (T::from_f32(self.l1), T::from_f32(self.l2));
+        weights.mapv(|w| l2 * w + l1 * sign(w))
+    }
+
+    /// create an inverted dropout mask for `nodes` nodes.
+    /// kept nodes are scaled by 1 / (1 - dropout) so the expected signal is the same as in eval mode,
+    /// that is why `predict` does not need to rescale anything.
+    pub fn dropout_mask<T: Float, R: Rng>(&self, nodes: usize, rng: &mut R) -> Matrix<T> {
+        assert!((0.0..1.0).contains(&self.dropout), "dropout must be in range [0, 1)");
+        let keep_scale = T::from_f32(1.0 / (1.0 - self.dropout));
+        Array::from_shape_fn((nodes, 1), |_| {
+            if rng.gen::<f32>() < self.dropout {
+                T::zero()
+            } else {
+                keep_scale
+            }
+        })
+    }
+}
+
+// sign(0) is 0, the L1 penalty should not move a weight which is already zero
+fn sign<T: Float>(x: T) -> T {
+    if x > T::zero() {
+        T::one()
+    } else if x < T::zero() {
+        -T::one()
+    } else {
+        T::zero()
+    }
+}
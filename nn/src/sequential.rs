@@ -0,0 +1,274 @@
+//! a model built from a stack of layers, e.g. a small convolutional network for MNIST
+
+use ndarray::{Array2, Axis, Ix2};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::activation::Activation;
+use crate::autograd::{Tape, Var};
+use crate::layers::{ActivationLayer, AnyLayer, Dense, Layer, Tensor};
+use crate::persist::{self, PersistError};
+use crate::{Mode, NeuralNetwork, Sample};
+
+/// loss function of a `Sequential` model
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Loss {
+    /// mean of the squared errors, the loss of `NeuralNetwork`
+    #[default]
+    MeanSquaredError,
+    /// -sum(target * ln(output)), used with a softmax output layer
+    CrossEntropy,
+}
+
+impl Loss {
+    /// the loss of one batch recorded on a tape, averaged over the samples
+    pub fn graph<'t>(&self, output: Var<'t>, target: Var<'t>) -> Var<'t> {
+        match self {
+            Loss::MeanSquaredError => (output - target).square().mean(),
+            Loss::CrossEntropy => {
+                let batch = output.shape()[0].max(1) as f32;
+                -(target * output.clamp_min(f32::EPSILON).ln()).sum().scale(1.0 / batch)
+            }
+        }
+    }
+
+    /// loss of one batch, averaged over the samples
+    pub fn loss(&self, output: &Array2<f32>, target: &Array2<f32>) -> f32 {
+        self.loss_and_gradient(output, target).0
+    }
+
+    /// gradient of `loss` with respect to the outputs
+    pub fn gradient(&self, output: &Array2<f32>, target: &Array2<f32>) -> Array2<f32> {
+        self.loss_and_gradient(output, target).1
+    }
+
+    /// `loss` and `gradient` from one pass over the tape
+    pub fn loss_and_gradient(&self, output: &Array2<f32>, target: &Array2<f32>) -> (f32, Array2<f32>) {
+        let tape = Tape::new();
+        let output_var = tape.var(output.clone().into_dyn());
+        let loss = self.graph(output_var, tape.var(target.clone().into_dyn()));
+        (loss.value().sum(), loss.backward().wrt(output_var).into_dimensionality().unwrap())
+    }
+}
+
+/// a stack of layers trained with mini batch gradient descent
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Sequential {
+    /// shape of one input sample, e.g. `[784]` for MNIST
+    input_shape: Vec<usize>,
+    layers: Vec<AnyLayer>,
+    loss: Loss,
+    learning_rate: f32,
+}
+
+impl Sequential {
+    /// create an empty model, the inputs are passed through unchanged until layers are pushed
+    pub fn new(input_shape: &[usize], loss: Loss, learning_rate: f32) -> Sequential {
+        Sequential {
+            input_shape: input_shape.to_vec(),
+            layers: vec![],
+            loss,
+            learning_rate,
+        }
+    }
+
+    /// add a layer on top of the model
+    pub fn push(mut self, layer: impl Into<AnyLayer>) -> Sequential {
+        self.layers.push(layer.into());
+        self
+    }
+
+    pub fn layers(&self) -> &[AnyLayer] {
+        &self.layers
+    }
+
+    pub fn layers_mut(&mut self) -> &mut [AnyLayer] {
+        &mut self.layers
+    }
+
+    pub fn input_shape(&self) -> &[usize] {
+        &self.input_shape
+    }
+
+    /// shape of one output sample
+    pub fn output_shape(&self) -> Vec<usize> {
+        self.layers.iter().fold(self.input_shape.clone(), |shape, layer| layer.output_shape(&shape))
+    }
+
+    pub fn learning_rate(&self) -> f32 {
+        self.learning_rate
+    }
+
+    pub fn set_learning_rate(&mut self, learning_rate: f32) {
+        self.learning_rate = learning_rate;
+    }
+
+    /// save the model as a JSON model file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PersistError> {
+        persist::save_json(self, path)
+    }
+
+    /// load a model from a model file written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Sequential, PersistError> {
+        persist::load_json(path)
+    }
+
+    /// forward pass of a `[batch, input_shape...]` tensor in eval mode, e.g. batch normalisation uses the
+    /// running statistics of training, `train_batch` is the forward pass of training
+    pub fn forward(&self, batch: &Tensor) -> Tensor {
+        self.layers.iter().fold(batch.clone(), |x, layer| layer.forward(&x, Mode::Eval))
+    }
+
+    /// output of the model for one sample
+    pub fn predict(&self, input: &[f32]) -> Vec<f32> {
+        let mut shape = vec![1];
+        shape.extend(&self.input_shape);
+        let input = Tensor::from_shape_vec(shape, input.to_vec()).expect("input does not match the input shape");
+        self.forward(&input).into_raw_vec()
+    }
+
+    /// train the model with one mini batch, returns the loss of the batch before the update
+    pub fn train_batch(&mut self, samples: &[Sample]) -> f32 {
+        if samples.is_empty() {
+            return 0.0;
+        }
+        let mut shape = vec![samples.len()];
+        shape.extend(&self.input_shape);
+        let inputs = samples.iter().flat_map(|sample| sample.input.iter().copied()).collect();
+        let targets = samples.iter().flat_map(|sample| sample.target.iter().copied()).collect();
+        let input = Tensor::from_shape_vec(shape, inputs).expect("input does not match the input shape");
+        let target = Array2::from_shape_vec((samples.len(), samples[0].target.len()), targets).unwrap();
+
+        // keep the input of every layer, the last entry is the output of the model
+        let mut activations = vec![input];
+        for layer in &mut self.layers {
+            let output = layer.train_forward(activations.last().unwrap());
+            activations.push(output);
+        }
+
+        let output = flatten_batch(activations.last().unwrap());
+        let (loss, grad) = self.loss.loss_and_gradient(&output, &target);
+        let mut grad = grad.into_dyn().into_shape(activations.last().unwrap().raw_dim()).unwrap();
+        for (i, layer) in self.layers.iter_mut().enumerate().rev() {
+            grad = layer.backward(&activations[i], &activations[i + 1], &grad);
+        }
+        for layer in &mut self.layers {
+            layer.update(self.learning_rate);
+        }
+        loss
+    }
+}
+
+/// the same model as the neural network of the book: dense layers without bias, the hidden activation and a sigmoid
+impl From<&NeuralNetwork> for Sequential {
+    fn from(nn: &NeuralNetwork) -> Self {
+        let (weight_ih, weight_ho) = nn.weights();
+        let hidden = Dense::from_weights(weight_ih.clone().into_dyn(), Tensor::zeros(vec![weight_ih.nrows()]));
+        let output = Dense::from_weights(weight_ho.clone().into_dyn(), Tensor::zeros(vec![weight_ho.nrows()]));
+        Sequential::new(&[weight_ih.ncols()], Loss::MeanSquaredError, nn.learning_rate())
+            .push(hidden)
+            .push(ActivationLayer::new(nn.activation()))
+            .push(output)
+            .push(ActivationLayer::new(Activation::Sigmoid))
+    }
+}
+
+// [batch, features] copy of the model output
+pub(crate) fn flatten_batch(tensor: &Tensor) -> Array2<f32> {
+    let batch = tensor.len_of(Axis(0));
+    tensor.as_standard_layout().into_owned().into_shape((batch, tensor.len() / batch.max(1)))
+        .unwrap().into_dimensionality::<Ix2>().unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layers::{BatchNorm, Conv2d, Flatten, MaxPool2d, Reshape};
+    use ndarray::Array;
+    use ndarray_rand::rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    fn samples() -> Vec<Sample> {
+        // a vertical and a horizontal bar on a 6x6 image
+        (0..2u8)
+            .map(|label| {
+                let input = (0..36).map(|i| if (label == 0 && i % 6 == 2) || (label == 1 && i / 6 == 2) { 1.0 } else { 0.0 }).collect();
+                let mut target = vec![0.0; 2];
+                target[label as usize] = 1.0;
+                Sample { input, target, label }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn conv_model_learns_bars() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let mut model = Sequential::new(&[36], Loss::CrossEntropy, 0.1)
+            .push(Reshape::new(&[1, 6, 6]))
+            .push(Conv2d::new(1, 4, 3, &mut rng).padding(1))
+            .push(ActivationLayer::new(Activation::Relu))
+            .push(MaxPool2d::new(2))
+            .push(Flatten::new())
+            .push(Dense::new(36, 2, &mut rng))
+            .push(ActivationLayer::new(Activation::Softmax));
+        assert_eq!(model.output_shape(), vec![2]);
+
+        let samples = samples();
+        let first = model.train_batch(&samples);
+        let mut last = first;
+        for _ in 0..50 {
+            last = model.train_batch(&samples);
+        }
+        assert!(last < first / 4.0, "loss did not decrease: {} -> {}", first, last);
+        for sample in &samples {
+            let output = model.predict(&sample.input);
+            assert!(output[sample.label as usize] > 0.5);
+        }
+    }
+
+    #[test]
+    fn batch_norm_predicts_with_the_running_statistics() {
+        let mut rng = ChaCha8Rng::seed_from_u64(4);
+        let model = Sequential::new(&[4], Loss::MeanSquaredError, 0.1)
+            .push(Dense::new(4, 3, &mut rng))
+            .push(BatchNorm::new(3));
+        // the statistics of a batch of one sample would normalise every output to beta
+        let (a, b) = (model.predict(&[0.9, 0.1, 0.5, 0.3]), model.predict(&[0.1, 0.8, 0.2, 0.6]));
+        assert_ne!(a, b);
+        assert!(a.iter().any(|&o| o != 0.0));
+    }
+
+    #[test]
+    fn conv_gradient_matches_finite_differences() {
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let mut conv = Conv2d::new(2, 3, 3, &mut rng).stride(2).padding(1);
+        let input = Array::from_shape_fn((1, 2, 5, 5), |(_, c, y, x)| ((c * 25 + y * 5 + x) as f32 * 0.37).sin()).into_dyn();
+        let output = conv.forward(&input, Mode::Train);
+        assert_eq!(output.shape(), &[1, 3, 3, 3]);
+
+        // the loss is the sum of the outputs, so the output gradient is all ones
+        let grad_input = conv.backward(&input, &output, &Tensor::ones(output.raw_dim()));
+        let eps = 1e-2;
+        for index in [[0, 0, 0, 0], [0, 1, 2, 3], [0, 1, 4, 4]] {
+            let mut plus = input.clone();
+            plus[&index[..]] += eps;
+            let mut minus = input.clone();
+            minus[&index[..]] -= eps;
+            let numeric = (conv.forward(&plus, Mode::Train).sum() - conv.forward(&minus, Mode::Train).sum()) / (2.0 * eps);
+            assert!((numeric - grad_input[&index[..]]).abs() < 1e-2, "{} != {}", numeric, grad_input[&index[..]]);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Conv2d: the kernel of 5 pixels is larger than the input of 3 pixels with a padding of 0")]
+    fn kernel_larger_than_the_input_is_reported() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        Sequential::new(&[1, 3, 3], Loss::MeanSquaredError, 0.1).push(Conv2d::new(1, 2, 5, &mut rng)).output_shape();
+    }
+
+    #[test]
+    #[should_panic(expected = "MaxPool2d: the kernel of 4 pixels")]
+    fn pool_window_larger_than_the_input_is_reported() {
+        Sequential::new(&[1, 3, 3], Loss::MeanSquaredError, 0.1).push(MaxPool2d::new(4)).output_shape();
+    }
+}
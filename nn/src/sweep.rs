@@ -0,0 +1,307 @@
+//! hyperparameter search, there is no best way to choose the number of hidden nodes or the learning rate,
+//! so several networks are trained with different values and ranked by their validation accuracy
+//!
+//! a `Sweep` is usually loaded from a JSON config file, e.g.
+//! `{"search": {"random": {"trials": 8}}, "hidden_nodes": [100, 200], "learning_rate": [0.05, 0.1, 0.2],
+//! "activation": ["Sigmoid", "Tanh"], "optimizer": ["Sgd", {"Momentum": {"momentum": 0.9}}], "parallel": 4}`.
+//! Missing fields get the values of the book.
+
+use ndarray_rand::rand::seq::SliceRandom;
+use ndarray_rand::rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use crate::evaluation::Evaluation;
+use crate::persist::{self, PersistError};
+use crate::trainer::{TrainError, Trainer, TrainingData};
+use crate::{Activation, NeuralNetwork, Optimizer};
+
+/// how the trials are chosen from the values of a `Sweep`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Search {
+    /// every combination of the values
+    #[default]
+    Grid,
+    /// `trials` different combinations drawn with the seed of the sweep
+    Random { trials: usize },
+}
+
+/// the hyperparameters of one trial
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Hyperparameters {
+    pub hidden_nodes: usize,
+    pub learning_rate: f32,
+    pub epochs: usize,
+    /// the activation of the hidden layer
+    pub activation: Activation,
+    pub optimizer: Optimizer,
+}
+
+/// the result of one trial
+#[derive(Debug, Clone)]
+pub struct TrialResult {
+    /// position of the trial in `Sweep::trials`
+    pub trial: usize,
+    pub hyperparameters: Hyperparameters,
+    /// evaluation of the validation split after the best epoch, the trials are ranked by it
+    pub validation: Evaluation,
+    pub best_epoch: usize,
+    /// number of epochs trained, less than `hyperparameters.epochs` when the trial stopped early
+    pub epochs_trained: usize,
+    pub seconds: f32,
+    /// the trained network with the weights of the best epoch
+    pub network: NeuralNetwork,
+}
+
+/// the values to search and the training settings shared by all trials
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sweep {
+    pub search: Search,
+    pub hidden_nodes: Vec<usize>,
+    pub learning_rate: Vec<f32>,
+    pub epochs: Vec<usize>,
+    pub activation: Vec<Activation>,
+    pub optimizer: Vec<Optimizer>,
+    pub batch_size: usize,
+    /// ratio of the training data held out to rank the trials, must be greater than 0
+    pub validation_split: f32,
+    pub patience: Option<usize>,
+    /// seed of the weights, the validation split and the random search, the same for every trial
+    pub seed: u64,
+    /// number of trials trained at the same time
+    pub parallel: usize,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Sweep {
+            search: Search::Grid,
+            hidden_nodes: vec![200],
+            learning_rate: vec![0.1],
+            epochs: vec![1],
+            activation: vec![Activation::Sigmoid],
+            optimizer: vec![Optimizer::Sgd],
+            batch_size: 1,
+            validation_split: 0.1,
+            patience: None,
+            seed: 0,
+            parallel: 1,
+        }
+    }
+}
+
+impl Sweep {
+    /// load a sweep from a JSON config file, the values are checked like by `run`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Sweep, PersistError> {
+        let sweep: Sweep = persist::load_json(path)?;
+        sweep.check().map_err(PersistError::Invalid)?;
+        Ok(sweep)
+    }
+
+    // the values which would panic in the trainer or the network of a trial
+    fn check(&self) -> Result<(), String> {
+        let lists = [
+            ("hidden_nodes", self.hidden_nodes.is_empty()),
+            ("learning_rate", self.learning_rate.is_empty()),
+            ("epochs", self.epochs.is_empty()),
+            ("activation", self.activation.is_empty()),
+            ("optimizer", self.optimizer.is_empty()),
+        ];
+        if let Some((name, _)) = lists.iter().find(|(_, empty)| *empty) {
+            return Err(format!("{} needs at least one value", name));
+        }
+        if self.hidden_nodes.contains(&0) {
+            return Err("the number of hidden nodes must be greater than 0".to_string());
+        }
+        if self.search == (Search::Random { trials: 0 }) {
+            return Err("a random search needs at least one trial".to_string());
+        }
+        if !(self.validation_split > 0.0 && self.validation_split < 1.0) {
+            return Err(format!("the validation split must be in range (0, 1) to rank the trials, not {}", self.validation_split));
+        }
+        if self.batch_size == 0 {
+            return Err("the batch size must be greater than 0".to_string());
+        }
+        if self.activation.contains(&Activation::Softmax) {
+            return Err("softmax can not be the activation of the hidden layer".to_string());
+        }
+        Ok(())
+    }
+
+    /// the hyperparameters of every trial, in grid order for a grid search
+    pub fn trials(&self) -> Vec<Hyperparameters> {
+        let mut trials = vec![];
+        for &hidden_nodes in &self.hidden_nodes {
+            for &learning_rate in &self.learning_rate {
+                for &epochs in &self.epochs {
+                    for &activation in &self.activation {
+                        for &optimizer in &self.optimizer {
+                            trials.push(Hyperparameters { hidden_nodes, learning_rate, epochs, activation, optimizer });
+                        }
+                    }
+                }
+            }
+        }
+        if let Search::Random { trials: count } = self.search {
+            trials.shuffle(&mut ChaCha8Rng::seed_from_u64(self.seed));
+            trials.truncate(count);
+        }
+        trials
+    }
+
+    /// train a network for every trial and return the results ranked by validation accuracy, the best first.
+    /// `on_trial` is called whenever a trial is finished, e.g. to print the progress
+    pub fn run<F>(&self, input_nodes: usize, output_nodes: usize, data: &TrainingData, on_trial: F) -> Result<Vec<TrialResult>, TrainError>
+    where
+        F: Fn(&TrialResult) + Sync,
+    {
+        self.check().map_err(TrainError::Invalid)?;
+        let trials = self.trials();
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(vec![]);
+
+        thread::scope(|scope| {
+            for _ in 0..self.parallel.clamp(1, trials.len().max(1)) {
+                scope.spawn(|| {
+                    loop {
+                        let trial = next.fetch_add(1, Ordering::SeqCst);
+                        let Some(&hyperparameters) = trials.get(trial) else {
+                            break;
+                        };
+                        let result = self.train(trial, hyperparameters, input_nodes, output_nodes, data);
+                        if let Ok(result) = &result {
+                            on_trial(result);
+                        }
+                        results.lock().unwrap().push(result);
+                    }
+                });
+            }
+        });
+
+        let mut results = results.into_inner().unwrap().into_iter().collect::<Result<Vec<_>, _>>()?;
+        results.sort_by(|a, b| {
+            b.validation.accuracy.total_cmp(&a.validation.accuracy).then(a.validation.loss.total_cmp(&b.validation.loss))
+        });
+        Ok(results)
+    }
+
+    fn train(&self, trial: usize, hyperparameters: Hyperparameters, input_nodes: usize, output_nodes: usize, data: &TrainingData)
+        -> Result<TrialResult, TrainError>
+    {
+        let start = Instant::now();
+        let mut network = NeuralNetwork::new_with_seed(input_nodes as i32, hyperparameters.hidden_nodes as i32,
+            output_nodes as i32, hyperparameters.learning_rate, self.seed);
+        network.set_activation(hyperparameters.activation);
+        network.set_optimizer(hyperparameters.optimizer);
+        let trainer = Trainer::new()
+            .epochs(hyperparameters.epochs)
+            .batch_size(self.batch_size)
+            .validation_split(self.validation_split)
+            .seed(self.seed);
+        let trainer = match self.patience {
+            Some(patience) => trainer.patience(patience),
+            None => trainer,
+        };
+        let report = trainer.fit(&mut network, data)?;
+        let best_epoch = report.best_epoch.unwrap_or(0);
+        Ok(TrialResult {
+            trial,
+            hyperparameters,
+            validation: report.history.get(best_epoch).and_then(|metrics| metrics.validation).unwrap_or_default(),
+            best_epoch,
+            epochs_trained: report.history.len(),
+            seconds: start.elapsed().as_secs_f32(),
+            network,
+        })
+    }
+}
+
+/// write the ranked results as a csv table, one line per trial
+pub fn write_results<W: Write>(writer: W, results: &[TrialResult]) -> io::Result<()> {
+    let mut writer = csv::Writer::from_writer(writer);
+    writer.write_record([
+        "rank", "trial", "hidden_nodes", "learning_rate", "epochs", "activation", "optimizer",
+        "best_epoch", "epochs_trained", "validation_loss", "validation_accuracy", "seconds",
+    ])?;
+    for (rank, result) in results.iter().enumerate() {
+        let hyperparameters = &result.hyperparameters;
+        writer.write_record([
+            (rank + 1).to_string(),
+            result.trial.to_string(),
+            hyperparameters.hidden_nodes.to_string(),
+            hyperparameters.learning_rate.to_string(),
+            hyperparameters.epochs.to_string(),
+            hyperparameters.activation.to_string(),
+            hyperparameters.optimizer.to_string(),
+            result.best_epoch.to_string(),
+            result.epochs_trained.to_string(),
+            result.validation.loss.to_string(),
+            result.validation.accuracy.to_string(),
+            format!("{:.1}", result.seconds),
+        ])?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Sample;
+
+    #[test]
+    fn sweep_ranks_the_trials() {
+        let sweep: Sweep = serde_json::from_str(r#"{
+            "hidden_nodes": [2, 6], "learning_rate": [0.001, 0.5], "epochs": [3],
+            "activation": ["Sigmoid", "Tanh"], "optimizer": ["Sgd", {"Momentum": {"momentum": 0.9}}], "parallel": 3
+        }"#).unwrap();
+        assert_eq!(sweep.trials().len(), 16);
+        assert_eq!(sweep.batch_size, 1);
+        let random = Sweep { search: Search::Random { trials: 5 }, ..sweep.clone() };
+        assert_eq!(random.trials().len(), 5);
+        assert!(random.trials().iter().all(|trial| sweep.trials().contains(trial)));
+        assert_eq!(random.trials(), random.trials());
+
+        // two separable classes
+        let samples: Vec<Sample> = (0..100).map(|i| {
+            let label = (i % 2) as u8;
+            let x = if label == 0 { 0.9 } else { 0.1 };
+            Sample { input: vec![x, 1.0 - x, 0.5], target: crate::dataset::one_hot_target(label, 2), label }
+        }).collect();
+        let finished = AtomicUsize::new(0);
+        let data = TrainingData::Samples(samples);
+        let results = sweep.run(3, 2, &data, |_| {
+            finished.fetch_add(1, Ordering::SeqCst);
+        }).unwrap();
+        assert_eq!((results.len(), finished.load(Ordering::SeqCst)), (16, 16));
+        assert!(results.windows(2).all(|pair| pair[0].validation.accuracy >= pair[1].validation.accuracy));
+        // a learning rate of 0.001 does not learn anything in 3 epochs
+        assert_eq!(results[0].hyperparameters.learning_rate, 0.5);
+        assert_eq!(results[0].network.activation(), results[0].hyperparameters.activation);
+
+        let mut table = vec![];
+        write_results(&mut table, &results).unwrap();
+        let table = String::from_utf8(table).unwrap();
+        assert_eq!(table.lines().count(), 17);
+        assert!(table.lines().nth(1).unwrap().starts_with("1,"));
+
+        // settings which the trials can not train with are an error, not a panic of a worker thread
+        let invalid = [
+            Sweep { validation_split: 0.0, ..sweep.clone() },
+            Sweep { activation: vec![Activation::Softmax], ..sweep.clone() },
+            Sweep { hidden_nodes: vec![2, 0], ..sweep.clone() },
+            Sweep { optimizer: vec![], ..sweep.clone() },
+            Sweep { search: Search::Random { trials: 0 }, ..sweep },
+        ];
+        for invalid in invalid {
+            assert!(matches!(invalid.run(3, 2, &data, |_| {}), Err(TrainError::Invalid(_))));
+        }
+    }
+}
@@ -0,0 +1,680 @@
+//! training loop with a validation split, early stopping, best weights tracking and resumable checkpoints
+
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use crate::dataset::{DatasetError, Sample};
+use crate::evaluation::{evaluate, Evaluation};
+use crate::loader::DataLoader;
+use crate::metrics::{Metrics, MetricsLogger};
+use crate::persist::{self, PersistError};
+use crate::{Mode, NeuralNetwork, Schedule, TrainStep};
+
+/// the samples a `Trainer` learns from
+#[derive(Debug, Clone)]
+pub enum TrainingData {
+    /// MNIST csv shards, streamed from disk by a `DataLoader` in every epoch
+    Shards(Vec<PathBuf>),
+    /// samples which are already in memory, they are treated as one shard
+    Samples(Vec<Sample>),
+}
+
+/// error while training
+#[derive(Debug)]
+pub enum TrainError {
+    /// the training data could not be read
+    Dataset(DatasetError),
+    /// a checkpoint could not be written
+    Checkpoint(PersistError),
+    /// the metrics could not be written
+    Metrics(io::Error),
+    /// the settings can not be trained, e.g. a sweep without a validation split
+    Invalid(String),
+}
+
+impl fmt::Display for TrainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrainError::Dataset(err) => write!(f, "{}", err),
+            TrainError::Checkpoint(err) => write!(f, "checkpoint: {}", err),
+            TrainError::Metrics(err) => write!(f, "metrics: {}", err),
+            TrainError::Invalid(message) => write!(f, "invalid settings: {}", message),
+        }
+    }
+}
+
+impl Error for TrainError {}
+
+impl From<DatasetError> for TrainError {
+    fn from(err: DatasetError) -> Self {
+        TrainError::Dataset(err)
+    }
+}
+
+impl From<PersistError> for TrainError {
+    fn from(err: PersistError) -> Self {
+        TrainError::Checkpoint(err)
+    }
+}
+
+/// metrics of one epoch
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EpochMetrics {
+    pub epoch: usize,
+    /// mean loss of the training samples, measured before each weight update
+    pub train_loss: f32,
+    /// evaluation of the validation split after the epoch, `None` without a validation split
+    pub validation: Option<Evaluation>,
+}
+
+/// result of `Trainer::fit`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrainingReport {
+    pub history: Vec<EpochMetrics>,
+    /// the epoch with the lowest validation loss
+    pub best_epoch: Option<usize>,
+    /// the network after `best_epoch`
+    pub best_network: Option<NeuralNetwork>,
+    /// whether training stopped before the last epoch because the validation loss did not improve
+    pub stopped_early: bool,
+    /// whether training was stopped by the stop flag, the last checkpoint can be resumed
+    pub interrupted: bool,
+}
+
+/// the complete state of a training run, written periodically by a `Trainer` with a checkpoint path
+///
+/// `Trainer::resume` continues exactly where the checkpoint was taken, the final weights are the same as the
+/// weights of an uninterrupted run with the same trainer settings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    /// the network including its optimizer state and the random number generator of the dropout masks
+    pub network: NeuralNetwork,
+    /// the learning rate the schedule starts from
+    pub base_learning_rate: f32,
+    /// number of weight updates so far, the step of the learning rate schedule
+    pub step: u64,
+    /// the current epoch
+    pub epoch: usize,
+    /// shard of the next batch to train in the current epoch
+    pub shard: usize,
+    /// row of the next batch to train in `shard`
+    pub row: usize,
+    /// the metrics of the finished epochs
+    pub report: TrainingReport,
+    // running loss of the current epoch
+    loss_sum: f32,
+    trained: usize,
+    // the mode of the network before training
+    mode: Mode,
+}
+
+impl Checkpoint {
+    fn start(network: NeuralNetwork) -> Checkpoint {
+        Checkpoint {
+            base_learning_rate: network.learning_rate(),
+            mode: network.mode(),
+            network,
+            step: 0,
+            epoch: 0,
+            shard: 0,
+            row: 0,
+            report: TrainingReport::default(),
+            loss_sum: 0.0,
+            trained: 0,
+        }
+    }
+
+    /// save the checkpoint to a JSON file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PersistError> {
+        persist::save_json(self, path)
+    }
+
+    /// load a checkpoint written by `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Checkpoint, PersistError> {
+        persist::load_json(path)
+    }
+}
+
+/// trains a `NeuralNetwork` for several epochs
+///
+/// a seeded part of the training data is held out as validation split and evaluated after every epoch.
+/// Training stops when the validation loss has not improved for `patience` epochs, and the weights of the
+/// best epoch are restored at the end.
+#[derive(Debug, Clone)]
+pub struct Trainer {
+    epochs: usize,
+    batch_size: usize,
+    threads: usize,
+    workers: usize,
+    validation_split: f32,
+    patience: Option<usize>,
+    restore_best: bool,
+    seed: u64,
+    schedule: Schedule,
+    checkpoint: Option<(PathBuf, u64)>,
+    stop: Option<Arc<AtomicBool>>,
+    pause: Option<Arc<AtomicBool>>,
+    metrics: Option<Arc<Mutex<MetricsLogger>>>,
+}
+
+impl Default for Trainer {
+    fn default() -> Self {
+        Trainer {
+            epochs: 1,
+            batch_size: 1,
+            threads: 1,
+            workers: 2,
+            validation_split: 0.0,
+            patience: None,
+            restore_best: true,
+            seed: 0,
+            schedule: Schedule::Constant,
+            checkpoint: None,
+            stop: None,
+            pause: None,
+            metrics: None,
+        }
+    }
+}
+
+impl Trainer {
+    /// create a trainer for one epoch of per-sample updates without validation
+    pub fn new() -> Trainer {
+        Trainer::default()
+    }
+
+    /// number of passes over the training data
+    pub fn epochs(mut self, epochs: usize) -> Self {
+        self.epochs = epochs;
+        self
+    }
+
+    /// number of samples per weight update, 1 updates the weights after every sample like the book does
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        assert!(batch_size > 0, "batch size must be greater than 0");
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// number of threads which share a batch, see `NeuralNetwork::train_batch`, 0 is used as 1
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// number of worker threads parsing the shards, 0 is used as 1
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// ratio of the training data held out for validation, in range [0, 1)
+    pub fn validation_split(mut self, validation_split: f32) -> Self {
+        assert!((0.0..1.0).contains(&validation_split), "validation split must be in range [0, 1)");
+        self.validation_split = validation_split;
+        self
+    }
+
+    /// stop when the validation loss has not improved for `patience` epochs
+    pub fn patience(mut self, patience: usize) -> Self {
+        self.patience = Some(patience);
+        self
+    }
+
+    /// whether the network gets the weights of the best epoch at the end, true by default
+    pub fn restore_best(mut self, restore_best: bool) -> Self {
+        self.restore_best = restore_best;
+        self
+    }
+
+    /// seed of the validation split
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// learning rate schedule, the network's learning rate is the base learning rate
+    pub fn schedule(mut self, schedule: Schedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// write a checkpoint to `path` every `every_steps` weight updates and at the end of every epoch
+    pub fn checkpoint<P: AsRef<Path>>(mut self, path: P, every_steps: u64) -> Self {
+        self.checkpoint = Some((path.as_ref().to_path_buf(), every_steps.max(1)));
+        self
+    }
+
+    /// stop training after the current batch when the flag is set, a checkpoint is written if configured
+    pub fn stop_flag(mut self, stop: Arc<AtomicBool>) -> Self {
+        self.stop = Some(stop);
+        self
+    }
+
+    /// wait after the current batch while the flag is set, the stop flag still stops a paused run
+    pub fn pause_flag(mut self, pause: Arc<AtomicBool>) -> Self {
+        self.pause = Some(pause);
+        self
+    }
+
+    /// record the loss, the learning rate and the gradient norms, see `metrics::MetricsLogger`
+    pub fn metrics(mut self, logger: MetricsLogger) -> Self {
+        self.metrics = Some(Arc::new(Mutex::new(logger)));
+        self
+    }
+
+    /// train the network on the data
+    pub fn fit(&self, network: &mut NeuralNetwork, data: &TrainingData) -> Result<TrainingReport, TrainError> {
+        let mut state = Checkpoint::start(network.clone());
+        let result = self.run(&mut state, data);
+        *network = state.network;
+        result?;
+        Ok(state.report)
+    }
+
+    /// continue a training run from a checkpoint, the trainer must have the same settings as the interrupted one
+    pub fn resume(&self, checkpoint: Checkpoint, data: &TrainingData) -> Result<(NeuralNetwork, TrainingReport), TrainError> {
+        let mut state = checkpoint;
+        state.report.interrupted = false;
+        self.run(&mut state, data)?;
+        Ok((state.network, state.report))
+    }
+
+    // train the remaining epochs, a checkpoint of a run which stopped early is already finished.
+    // The network gets its mode back whatever happens, even after an interrupt or an error
+    fn run(&self, state: &mut Checkpoint, data: &TrainingData) -> Result<(), TrainError> {
+        let result = if state.report.stopped_early { Ok(()) } else { self.train_epochs(state, data) };
+        if result.is_ok() && !state.report.interrupted && self.restore_best {
+            if let Some(best) = &state.report.best_network {
+                state.network = best.clone();
+            }
+        }
+        state.network.set_mode(state.mode);
+        result
+    }
+
+    fn train_epochs(&self, state: &mut Checkpoint, data: &TrainingData) -> Result<(), TrainError> {
+        // the validation samples are collected while the first epoch streams over the data.
+        // After resuming, the skipped part of the data is still read to collect them again.
+        let mut validation: Vec<Sample> = vec![];
+        let mut steps_since_checkpoint = 0;
+        let mut interval = Interval::default();
+
+        while state.epoch < self.epochs {
+            state.network.set_mode(Mode::Train);
+            let collect_validation = validation.is_empty() && self.validation_split > 0.0;
+            let start_shard = if collect_validation { 0 } else { state.shard };
+            let resume_position = (state.shard, state.row);
+            let mut interrupted = false;
+
+            self.for_each_batch(data, start_shard, |shard, row, samples| {
+                let trained_before = (shard, row) < resume_position;
+                let mut batch: Vec<&Sample> = Vec::with_capacity(samples.len());
+                for (i, sample) in samples.iter().enumerate() {
+                    if self.is_validation(shard, row + i) {
+                        if collect_validation {
+                            validation.push(sample.clone());
+                        }
+                    } else {
+                        batch.push(sample);
+                    }
+                }
+                if trained_before {
+                    return Ok(ControlFlow::Continue(()));
+                }
+                // a full interval is logged before the next batch, so the last one of an epoch goes with the validation
+                if self.metrics.as_ref().is_some_and(|logger| interval.steps >= logger.lock().unwrap().every_steps()) {
+                    self.log_metrics(state, &mut interval, None)?;
+                }
+
+                if self.batch_size == 1 {
+                    for sample in &batch {
+                        self.update_learning_rate(state);
+                        let step = state.network.train_step(&sample.input, &sample.target);
+                        state.loss_sum += step.loss;
+                        interval.add(&step);
+                        state.step += 1;
+                        steps_since_checkpoint += 1;
+                    }
+                } else if !batch.is_empty() {
+                    let batch: Vec<Sample> = batch.iter().map(|sample| (*sample).clone()).collect();
+                    self.update_learning_rate(state);
+                    let step = state.network.train_batch_step(&batch, self.threads);
+                    state.loss_sum += step.loss * batch.len() as f32;
+                    interval.add(&step);
+                    state.step += 1;
+                    steps_since_checkpoint += 1;
+                }
+                state.trained += batch.len();
+                state.shard = shard;
+                state.row = row + samples.len();
+
+                if let Some((path, every_steps)) = &self.checkpoint {
+                    if steps_since_checkpoint >= *every_steps {
+                        state.save(path)?;
+                        steps_since_checkpoint = 0;
+                    }
+                }
+                while self.pause.as_ref().is_some_and(|pause| pause.load(Ordering::SeqCst)) && !self.stopped() {
+                    thread::sleep(Duration::from_millis(50));
+                }
+                if self.stopped() {
+                    interrupted = true;
+                    return Ok(ControlFlow::Break(()));
+                }
+                Ok(ControlFlow::Continue(()))
+            })?;
+
+            if interrupted {
+                state.report.interrupted = true;
+                if interval.steps > 0 {
+                    self.log_metrics(state, &mut interval, None)?;
+                }
+                if let Some((path, _)) = &self.checkpoint {
+                    state.save(path)?;
+                }
+                return Ok(());
+            }
+
+            state.network.set_mode(Mode::Eval);
+            let metrics = EpochMetrics {
+                epoch: state.epoch,
+                train_loss: if state.trained > 0 { state.loss_sum / state.trained as f32 } else { 0.0 },
+                validation: if validation.is_empty() { None } else { Some(evaluate(&state.network, &validation)) },
+            };
+            state.report.history.push(metrics);
+            if interval.steps > 0 || metrics.validation.is_some() {
+                self.log_metrics(state, &mut interval, metrics.validation)?;
+            }
+            state.epoch += 1;
+            state.shard = 0;
+            state.row = 0;
+            state.loss_sum = 0.0;
+            state.trained = 0;
+
+            if let Some(evaluation) = metrics.validation {
+                let report = &mut state.report;
+                let best_loss = report.best_epoch.map_or(f32::INFINITY, |best| report.history[best].validation.unwrap().loss);
+                if evaluation.loss < best_loss {
+                    report.best_epoch = Some(metrics.epoch);
+                    report.best_network = Some(state.network.clone());
+                } else {
+                    let epochs_without_improvement = metrics.epoch - report.best_epoch.unwrap();
+                    if self.patience.is_some_and(|patience| epochs_without_improvement >= patience) {
+                        report.stopped_early = true;
+                    }
+                }
+            }
+
+            if let Some((path, _)) = &self.checkpoint {
+                state.save(path)?;
+                steps_since_checkpoint = 0;
+            }
+            if state.report.stopped_early {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.as_ref().is_some_and(|stop| stop.load(Ordering::SeqCst))
+    }
+
+    // write the metrics of the interval and start the next one
+    fn log_metrics(&self, state: &Checkpoint, interval: &mut Interval, validation: Option<Evaluation>) -> Result<(), TrainError> {
+        let Some(logger) = &self.metrics else {
+            return Ok(());
+        };
+        let steps = interval.steps.max(1) as f32;
+        let metrics = Metrics {
+            step: state.step,
+            epoch: state.epoch,
+            shard: state.shard,
+            loss: interval.loss / steps,
+            accuracy: validation.map(|evaluation| evaluation.accuracy),
+            validation_loss: validation.map(|evaluation| evaluation.loss),
+            learning_rate: state.network.learning_rate(),
+            gradient_norm_ih: interval.gradient_norm_ih / steps,
+            gradient_norm_ho: interval.gradient_norm_ho / steps,
+        };
+        *interval = Interval::default();
+        logger.lock().unwrap().log(&metrics).map_err(TrainError::Metrics)
+    }
+
+    fn update_learning_rate(&self, state: &mut Checkpoint) {
+        let learning_rate = self.schedule.learning_rate(state.base_learning_rate, state.step);
+        state.network.set_learning_rate(learning_rate);
+    }
+
+    // call `f` with the shard index, the row of the first sample and the samples of every batch,
+    // starting from shard `start_shard`
+    fn for_each_batch<F>(&self, data: &TrainingData, start_shard: usize, mut f: F) -> Result<(), TrainError>
+    where
+        F: FnMut(usize, usize, &[Sample]) -> Result<ControlFlow<()>, TrainError>,
+    {
+        match data {
+            TrainingData::Shards(shards) => {
+                // a multiple of the batch size, so the batches are only cut at the end of a shard
+                let loader = DataLoader::builder(shards[start_shard.min(shards.len())..].to_vec())
+                    .batch_size(self.batch_size * 100usize.div_ceil(self.batch_size))
+                    .workers(self.workers)
+                    .build();
+                for batch in loader {
+                    let batch = batch?;
+                    for (i, samples) in batch.samples.chunks(self.batch_size).enumerate() {
+                        if f(start_shard + batch.shard, batch.row + i * self.batch_size, samples)?.is_break() {
+                            return Ok(());
+                        }
+                    }
+                }
+            }
+            TrainingData::Samples(samples) => {
+                for (i, samples) in samples.chunks(self.batch_size).enumerate() {
+                    if f(0, i * self.batch_size, samples)?.is_break() {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // a sample belongs to the validation split depending on its position and the seed only,
+    // so the split is the same in every epoch whatever the batch size is
+    fn is_validation(&self, shard: usize, row: usize) -> bool {
+        if self.validation_split <= 0.0 {
+            return false;
+        }
+        let hash = splitmix64(self.seed ^ ((shard as u64) << 32) ^ row as u64);
+        ((hash >> 40) as f32 / (1u64 << 24) as f32) < self.validation_split
+    }
+}
+
+// sums of the weight updates since the last metrics record
+#[derive(Debug, Default)]
+struct Interval {
+    steps: u64,
+    loss: f32,
+    gradient_norm_ih: f32,
+    gradient_norm_ho: f32,
+}
+
+impl Interval {
+    fn add(&mut self, step: &TrainStep) {
+        self.steps += 1;
+        self.loss += step.loss;
+        self.gradient_norm_ih += step.gradient_norm_ih;
+        self.gradient_norm_ho += step.gradient_norm_ho;
+    }
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E3779B97F4A7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+    z ^ (z >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples() -> Vec<Sample> {
+        // two separable classes
+        (0..200).map(|i| {
+            let label = (i % 2) as u8;
+            let x = if label == 0 { 0.9 } else { 0.1 };
+            Sample { input: vec![x, 1.0 - x, 0.5], target: crate::dataset::one_hot_target(label, 2), label }
+        }).collect()
+    }
+
+    #[test]
+    fn validation_split_and_early_stopping() {
+        let mut nn = NeuralNetwork::new_with_seed(3, 4, 2, 0.3, 1);
+        let report = Trainer::new()
+            .epochs(50)
+            .validation_split(0.2)
+            .patience(2)
+            .fit(&mut nn, &TrainingData::Samples(samples()))
+            .unwrap();
+
+        let validation = report.history[0].validation.unwrap();
+        assert!(validation.count > 20 && validation.count < 60);
+        assert_eq!(validation.count, report.history.last().unwrap().validation.unwrap().count);
+
+        // the best weights are restored
+        let best_epoch = report.best_epoch.unwrap();
+        let best_loss = report.history[best_epoch].validation.unwrap().loss;
+        assert!(report.history.iter().all(|m| m.validation.unwrap().loss >= best_loss));
+        assert_eq!(nn.predict(&[0.9, 0.1, 0.5]), report.best_network.unwrap().predict(&[0.9, 0.1, 0.5]));
+    }
+
+    #[test]
+    fn early_stopping_stops_after_patience_epochs_without_improvement() {
+        // one full batch update per epoch, the learning rate turns negative after 3 updates, so the validation
+        // loss improves until epoch 2 and gets worse from epoch 3 on
+        let trainer = || Trainer::new()
+            .epochs(50)
+            .batch_size(200)
+            .validation_split(0.2)
+            .patience(2)
+            .schedule(Schedule::StepDecay { step_size: 3, gamma: -1.0 });
+        let data = TrainingData::Samples(samples());
+        let path = std::env::temp_dir().join(format!("nn-early-stopping-test-{}.json", std::process::id()));
+        let mut nn = NeuralNetwork::new_with_seed(3, 4, 2, 0.3, 1);
+        let report = trainer().checkpoint(&path, 1000).fit(&mut nn, &data).unwrap();
+
+        let losses: Vec<f32> = report.history.iter().map(|m| m.validation.unwrap().loss).collect();
+        assert!(losses.windows(2).take(2).all(|pair| pair[1] < pair[0]), "{:?}", losses);
+        assert!(losses[3..].iter().all(|&loss| loss > losses[2]), "{:?}", losses);
+        assert_eq!((report.best_epoch, report.history.len(), report.stopped_early), (Some(2), 5, true));
+
+        // the network gets the weights of epoch 2, not the ones of the last epoch
+        let mut last = NeuralNetwork::new_with_seed(3, 4, 2, 0.3, 1);
+        trainer().restore_best(false).fit(&mut last, &data).unwrap();
+        let best = report.best_network.unwrap();
+        assert_eq!(nn.weights(), best.weights());
+        assert_ne!(nn.weights(), last.weights());
+
+        // the checkpoint written before stopping is finished, resuming it does not train any further
+        let (resumed, resumed_report) = trainer().resume(Checkpoint::load(&path).unwrap(), &data).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(resumed_report.history, report.history);
+        assert_eq!(resumed.weights(), best.weights());
+    }
+
+    #[test]
+    fn metrics_are_logged_per_interval_and_epoch() {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut nn = NeuralNetwork::new_with_seed(3, 4, 2, 0.3, 1);
+        let report = Trainer::new()
+            .epochs(2)
+            .batch_size(10)
+            .validation_split(0.2)
+            .metrics(MetricsLogger::new(5).sink(sender))
+            .fit(&mut nn, &TrainingData::Samples(samples()))
+            .unwrap();
+        let records: Vec<Metrics> = receiver.try_iter().collect();
+
+        // about 16 updates per epoch, logged after 5, 10 and 15 updates and with the validation at the end
+        let epoch_ends: Vec<&Metrics> = records.iter().filter(|m| m.accuracy.is_some()).collect();
+        assert_eq!(epoch_ends.len(), 2);
+        assert_eq!(epoch_ends[1].step, records.last().unwrap().step);
+        assert_eq!(epoch_ends[0].validation_loss, Some(report.history[0].validation.unwrap().loss));
+        assert!(records.windows(2).all(|pair| pair[0].step < pair[1].step));
+        assert!(records.iter().all(|m| m.loss > 0.0 && m.gradient_norm_ih > 0.0 && m.gradient_norm_ho > 0.0));
+        assert!(records.iter().all(|m| m.learning_rate == 0.3));
+    }
+
+    #[test]
+    fn paused_run_waits_until_it_is_stopped() {
+        let (pause, stop) = (Arc::new(AtomicBool::new(true)), Arc::new(AtomicBool::new(false)));
+        let trainer = Trainer::new().epochs(3).pause_flag(pause.clone()).stop_flag(stop.clone());
+        let mut nn = NeuralNetwork::new_with_seed(3, 4, 2, 0.3, 1);
+        nn.set_mode(Mode::Eval);
+        let report = thread::scope(|scope| {
+            let run = scope.spawn(|| trainer.fit(&mut nn, &TrainingData::Samples(samples())).unwrap());
+            thread::sleep(Duration::from_millis(200));
+            assert!(!run.is_finished());
+            stop.store(true, Ordering::SeqCst);
+            run.join().unwrap()
+        });
+        assert!(report.interrupted);
+        assert!(report.history.is_empty());
+        // the interrupted run gives the network back in the mode it had before
+        assert_eq!(nn.mode(), Mode::Eval);
+    }
+
+    #[test]
+    fn resume_gives_the_same_weights() {
+        let path = std::env::temp_dir().join(format!("nn-checkpoint-test-{}.json", std::process::id()));
+        let data = TrainingData::Samples(samples());
+        let network = || {
+            let mut nn = NeuralNetwork::new_with_seed(3, 6, 2, 0.3, 3);
+            nn.set_optimizer(crate::Optimizer::Momentum { momentum: 0.9 });
+            nn.set_regularization(crate::Regularization { l1: 0.0, l2: 0.001, dropout: 0.3 });
+            nn
+        };
+        let trainer = || Trainer::new()
+            .epochs(3)
+            .batch_size(4)
+            .validation_split(0.1)
+            .schedule(Schedule::StepDecay { step_size: 20, gamma: 0.5 });
+
+        let mut uninterrupted = network();
+        let expected = trainer().fit(&mut uninterrupted, &data).unwrap();
+
+        // interrupt the run after every batch and resume it from the checkpoint until it is finished
+        let stop = Arc::new(AtomicBool::new(true));
+        let stopping = trainer().checkpoint(&path, 1000).stop_flag(stop.clone());
+        let mut interrupted = network();
+        assert!(stopping.fit(&mut interrupted, &data).unwrap().interrupted);
+        let mut checkpoint = Checkpoint::load(&path).unwrap();
+        assert_eq!((checkpoint.epoch, checkpoint.step), (0, 1));
+        loop {
+            let (_, report) = stopping.resume(checkpoint, &data).unwrap();
+            checkpoint = Checkpoint::load(&path).unwrap();
+            if !report.interrupted {
+                break;
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+        stop.store(false, Ordering::SeqCst);
+        // the last checkpoint was written at the end of the last epoch
+        assert_eq!(checkpoint.epoch, 3);
+
+        let (resumed, report) = trainer().resume(checkpoint, &data).unwrap();
+        assert_eq!(resumed.weight_ih, uninterrupted.weight_ih);
+        assert_eq!(resumed.predict(&[0.9, 0.1, 0.5]), uninterrupted.predict(&[0.9, 0.1, 0.5]));
+        assert_eq!(report.history, expected.history);
+    }
+}
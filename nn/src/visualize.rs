@@ -0,0 +1,257 @@
+//! images of the weights and the activations of a network, for the GUI and for reports
+//!
+//! the weights are drawn with a diverging colormap (blue for negative, white for zero, red for positive weights)
+//! and the activations with a black-red-yellow-white heat colormap.
+
+use image::{imageops, Rgb, RgbImage};
+
+use crate::dataset::unscale_pixels;
+use crate::{Matrix, NeuralNetwork, Sample};
+
+const BACKGROUND: Rgb<u8> = Rgb([64, 64, 64]);
+
+/// a grid of tiles, one per hidden unit, showing the incoming weights (a row of `weight_ih`) as a `tile` sized image
+///
+/// every tile is scaled by its own largest absolute weight and every weight is drawn as `scale`*`scale` pixels.
+pub fn weight_tiles(weights: &Matrix, tile: (u32, u32), scale: u32) -> RgbImage {
+    let (width, height) = tile;
+    if (width * height) as usize != weights.ncols() {
+        panic!("tile size does not match the number of weights per unit");
+    }
+    let columns = (weights.nrows() as f32).sqrt().ceil().max(1.0) as u32;
+    let rows = (weights.nrows() as u32).div_ceil(columns).max(1);
+    let mut image = grid(columns, rows, width * scale, height * scale);
+    for (unit, row) in weights.rows().into_iter().enumerate() {
+        let max = row.iter().fold(0.0f32, |max, w| max.max(w.abs()));
+        let (x0, y0) = cell_origin(unit as u32, columns, width * scale, height * scale);
+        for y in 0..height * scale {
+            for x in 0..width * scale {
+                let w = row[((y / scale) * width + x / scale) as usize];
+                image.put_pixel(x0 + x, y0 + y, diverging(if max > 0.0 { w / max } else { 0.0 }));
+            }
+        }
+    }
+    image
+}
+
+/// a grid of `cell`*`cell` squares, one per activation in [0, 1]
+pub fn activation_heatmap(activations: &[f32], cell: u32) -> RgbImage {
+    let columns = (activations.len() as f32).sqrt().ceil().max(1.0) as u32;
+    let rows = (activations.len() as u32).div_ceil(columns).max(1);
+    let mut image = grid(columns, rows, cell, cell);
+    for (i, &activation) in activations.iter().enumerate() {
+        let (x0, y0) = cell_origin(i as u32, columns, cell, cell);
+        for y in 0..cell {
+            for x in 0..cell {
+                image.put_pixel(x0 + x, y0 + y, heat(activation));
+            }
+        }
+    }
+    image
+}
+
+/// the hidden and the output activations of the network for one input, each as an `activation_heatmap`
+pub fn network_activations(network: &NeuralNetwork, input_list: &[f32], cell: u32) -> (RgbImage, RgbImage) {
+    let (hidden_output_vec, final_output_vec) = network.forward(input_list);
+    let hidden: Vec<f32> = hidden_output_vec.iter().copied().collect();
+    let output: Vec<f32> = final_output_vec.iter().copied().collect();
+    (activation_heatmap(&hidden, cell), activation_heatmap(&output, cell))
+}
+
+/// the gray image of a square sample like a 28*28 MNIST digit, every pixel drawn as `scale`*`scale` pixels
+pub fn sample_image(sample: &Sample, scale: u32) -> RgbImage {
+    let side = (sample.input.len() as f32).sqrt() as u32;
+    let pixels = unscale_pixels(&sample.input);
+    RgbImage::from_fn(side * scale, side * scale, |x, y| {
+        let gray = pixels[((y / scale) * side + x / scale) as usize];
+        Rgb([gray, gray, gray])
+    })
+}
+
+/// an explanation map (e.g. `explain::saliency_map`) drawn with the heat colormap over the gray square input image
+///
+/// the map is scaled by its largest value and blended in by its own value, so pixels without any signal stay gray.
+pub fn overlay(input_list: &[f32], map: &[f32], scale: u32) -> RgbImage {
+    assert_eq!(input_list.len(), map.len(), "the map does not match the input");
+    let side = (input_list.len() as f32).sqrt() as u32;
+    let pixels = unscale_pixels(input_list);
+    let max = map.iter().fold(0.0f32, |max, &v| max.max(v));
+    RgbImage::from_fn(side * scale, side * scale, |x, y| {
+        let i = ((y / scale) * side + x / scale) as usize;
+        let v = if max > 0.0 { (map[i] / max).max(0.0) } else { 0.0 };
+        let Rgb(color) = heat(v);
+        Rgb(color.map(|c| (pixels[i] as f32 * (1.0 - v) + c as f32 * v).round() as u8))
+    })
+}
+
+/// a mosaic of `columns`*`rows` samples, each with a caption above it (e.g. its label and row)
+///
+/// captions are drawn with a tiny built-in font which only knows digits, spaces and `#:>-.`, samples after the
+/// first `columns`*`rows` ones are left out.
+pub fn contact_sheet<'a>(tiles: impl IntoIterator<Item = (&'a Sample, String)>, columns: u32, rows: u32, scale: u32) -> RgbImage {
+    let (side, caption) = (28 * scale, (GLYPH_HEIGHT + 2) * scale);
+    let mut image = grid(columns, rows, side, caption + side);
+    for (i, (sample, text)) in tiles.into_iter().take((columns * rows) as usize).enumerate() {
+        let (x0, y0) = cell_origin(i as u32, columns, side, caption + side);
+        draw_text(&mut image, x0 + scale, y0 + scale, &text, scale, Rgb([255, 255, 255]));
+        imageops::replace(&mut image, &sample_image(sample, scale), x0 as i64, (y0 + caption) as i64);
+    }
+    image
+}
+
+const GLYPH_HEIGHT: u32 = 5;
+
+// 3*5 pixel glyphs, one row per byte with the left pixel in the highest of the 3 bits
+fn glyph(c: char) -> [u8; 5] {
+    match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b001, 0b001, 0b001],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        '#' => [0b101, 0b111, 0b101, 0b111, 0b101],
+        ':' => [0b000, 0b010, 0b000, 0b010, 0b000],
+        '>' => [0b100, 0b010, 0b001, 0b010, 0b100],
+        '-' => [0b000, 0b000, 0b111, 0b000, 0b000],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        _ => [0; 5],
+    }
+}
+
+/// draw `text` with the built-in 3*5 font, every font pixel as `scale`*`scale` pixels. Pixels outside are clipped
+pub fn draw_text(image: &mut RgbImage, x: u32, y: u32, text: &str, scale: u32, color: Rgb<u8>) {
+    for (i, c) in text.chars().enumerate() {
+        for (row, bits) in glyph(c).iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) == 0 {
+                    continue;
+                }
+                let (gx, gy) = (x + (i as u32 * 4 + column) * scale, y + row as u32 * scale);
+                for (dx, dy) in (0..scale).flat_map(|dx| (0..scale).map(move |dy| (dx, dy))) {
+                    if gx + dx < image.width() && gy + dy < image.height() {
+                        image.put_pixel(gx + dx, gy + dy, color);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// the counts of values in equally wide bins between the smallest and the largest value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Histogram {
+    pub min: f32,
+    pub max: f32,
+    pub counts: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn new<'a>(values: impl IntoIterator<Item = &'a f32>, bins: usize) -> Histogram {
+        assert!(bins > 0, "a histogram needs at least one bin");
+        let values: Vec<f32> = values.into_iter().copied().filter(|v| v.is_finite()).collect();
+        let min = values.iter().copied().fold(f32::INFINITY, f32::min);
+        let max = values.iter().copied().fold(f32::NEG_INFINITY, f32::max);
+        let mut counts = vec![0; bins];
+        for v in &values {
+            let bin = if max > min { ((v - min) / (max - min) * bins as f32) as usize } else { 0 };
+            counts[bin.min(bins - 1)] += 1;
+        }
+        if values.is_empty() {
+            return Histogram { min: 0.0, max: 0.0, counts };
+        }
+        Histogram { min, max, counts }
+    }
+
+    /// draw the bins as bars, the highest bar fills the height. Zero is marked with a vertical line if it is in range
+    pub fn render(&self, width: u32, height: u32) -> RgbImage {
+        let mut image = RgbImage::from_pixel(width, height, Rgb([255, 255, 255]));
+        let highest = self.counts.iter().copied().max().unwrap_or(0).max(1);
+        let bins = self.counts.len() as u32;
+        for x in 0..width {
+            let count = self.counts[(x * bins / width) as usize];
+            let bar = (count as f32 / highest as f32 * height as f32).round() as u32;
+            for y in height - bar..height {
+                image.put_pixel(x, y, Rgb([70, 110, 180]));
+            }
+        }
+        if self.min < 0.0 && self.max > 0.0 {
+            let x = (-self.min / (self.max - self.min) * width as f32) as u32;
+            for y in 0..height {
+                image.put_pixel(x.min(width - 1), y, Rgb([200, 40, 40]));
+            }
+        }
+        image
+    }
+}
+
+// an image of columns*rows cells separated and surrounded by 1 pixel lines
+fn grid(columns: u32, rows: u32, width: u32, height: u32) -> RgbImage {
+    RgbImage::from_pixel(columns * (width + 1) + 1, rows * (height + 1) + 1, BACKGROUND)
+}
+
+fn cell_origin(i: u32, columns: u32, width: u32, height: u32) -> (u32, u32) {
+    ((i % columns) * (width + 1) + 1, (i / columns) * (height + 1) + 1)
+}
+
+// -1 is blue, 0 is white and 1 is red
+fn diverging(v: f32) -> Rgb<u8> {
+    let fade = ((1.0 - v.abs().min(1.0)) * 255.0) as u8;
+    if v >= 0.0 {
+        Rgb([255, fade, fade])
+    } else {
+        Rgb([fade, fade, 255])
+    }
+}
+
+// 0 is black, then red, yellow and 1 is white
+fn heat(v: f32) -> Rgb<u8> {
+    let channel = |offset: f32| ((v * 3.0 - offset).clamp(0.0, 1.0) * 255.0) as u8;
+    Rgb([channel(0.0), channel(1.0), channel(2.0)])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_have_the_expected_layout() {
+        // 5 units of 2*3 weights in a 3*2 grid of 4*6 pixel tiles
+        let weights = Matrix::from_shape_fn((5, 6), |(unit, i)| if i == 0 { -1.0 } else { unit as f32 * 0.1 });
+        let tiles = weight_tiles(&weights, (2, 3), 2);
+        assert_eq!(tiles.dimensions(), (3 * 5 + 1, 2 * 7 + 1));
+        assert_eq!(*tiles.get_pixel(0, 0), BACKGROUND);
+        assert_eq!(*tiles.get_pixel(1, 1), Rgb([0, 0, 255]));
+        assert_eq!(*tiles.get_pixel(3, 1), Rgb([255, 255, 255]));
+
+        let heatmap = activation_heatmap(&[0.0, 1.0, 0.5], 4);
+        assert_eq!(heatmap.dimensions(), (2 * 5 + 1, 2 * 5 + 1));
+        assert_eq!(*heatmap.get_pixel(1, 1), Rgb([0, 0, 0]));
+        assert_eq!(*heatmap.get_pixel(6, 1), Rgb([255, 255, 255]));
+
+        let histogram = Histogram::new(&[-1.0, -0.5, 0.0, 0.2, 1.0, f32::NAN], 4);
+        assert_eq!((histogram.min, histogram.max), (-1.0, 1.0));
+        assert_eq!(histogram.counts, vec![1, 1, 2, 1]);
+        let bars = histogram.render(40, 10);
+        assert_eq!(*bars.get_pixel(25, 0), Rgb([70, 110, 180]));
+        assert_eq!(*bars.get_pixel(5, 0), Rgb([255, 255, 255]));
+
+        // a 2*1 sheet at scale 1: 28 pixel tiles with a 7 pixel caption, the "1" of the caption starts with one pixel
+        let samples = [Sample::from_mnist(1, &[255; 784]), Sample::from_mnist(7, &[0; 784])];
+        let sheet = contact_sheet(samples.iter().map(|s| (s, s.label.to_string())), 2, 1, 1);
+        assert_eq!(sheet.dimensions(), (2 * 29 + 1, 36 + 1));
+        assert_eq!((*sheet.get_pixel(2, 2), *sheet.get_pixel(3, 2)), (BACKGROUND, Rgb([255, 255, 255])));
+        assert_eq!(*sheet.get_pixel(1, 8), Rgb([255, 255, 255]));
+        assert_eq!(*sheet.get_pixel(30, 8), Rgb([0, 0, 0]));
+
+        // the strongest pixel is white, a pixel without signal keeps its gray value
+        let blended = overlay(&[0.01, 1.0, 0.01, 0.01], &[2.0, 0.0, 1.0, 0.0], 1);
+        assert_eq!(blended.pixels().copied().collect::<Vec<_>>(), vec![
+            Rgb([255, 255, 255]), Rgb([255, 255, 255]), Rgb([128, 64, 0]), Rgb([0, 0, 0]),
+        ]);
+    }
+}